@@ -0,0 +1,474 @@
+use glam::DVec3;
+use serde::Serialize;
+use std::io::Write as _;
+
+use orbiter_physics::elements::{lunar_orbit, OrbitalParameters};
+use orbiter_physics::frames::to_scene;
+use orbiter_physics::horizons::HorizonsElements;
+use orbiter_physics::lunar_theory::geocentric_state;
+use orbiter_physics::time_scale::{self, TimeScale};
+
+// Tabulates a body's position and velocity over a span of time without starting the app,
+// placed the way the app places it: ICRF relative to the body it orbits, in KM and KM/s and
+// in scene units (what `to_scene` turns the KM into, Y up). The Moon has the lunar theory
+// and built-in elements; any other body needs a Horizons ELEMENTS export. The planets'
+// series live in the app crate, so they aren't offered here.
+//
+//     cargo run -p orbiter-physics --features serde --bin ephemeris -- \
+//         --start 2024-04-08 --end 2024-04-09 --step 1h --format csv
+//
+// Dates are read like the app's date box ("now", "2024-04-08T18:17:00Z", "JD 2460409.26"),
+// calendar dates as UTC and Julian Dates as TDB unless they say otherwise.
+
+const USAGE: &str = "\
+Usage: ephemeris [options]
+
+  --body <name>        Body to tabulate. Anything but the Moon needs --elements. [moon,
+                       or the target of the --elements export]
+  --model <model>      meeus: the lunar theory, as the app does by default. Moon only.
+                       elements: the osculating ellipse with its secular rates. [meeus]
+  --elements <file>    Horizons ELEMENTS export to take the ellipse from, with --model
+                       elements, e.g. assets/horizons/moon.txt. Built-in elements
+                       otherwise, which are the Moon's.
+  --start <date>       First epoch, e.g. 2024-04-08T18:00 or \"JD 2460409.5 TDB\". [now]
+  --end <date>         Last epoch, included. [start + 1 day]
+  --step <step>        Seconds, or with an s, m, h or d suffix. [1h]
+  --format <format>    csv or json. [csv]
+  --output <file>      Written to standard output otherwise.
+";
+
+const SECONDS_PER_DAY: f64 = 86400.;
+// More rows than anyone means to ask for, most likely a step in the wrong unit.
+const MAX_ROWS: usize = 10_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Model {
+    Meeus,
+    Elements,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Options {
+    body: Option<String>, // Lowercase
+    model: Model,
+    elements: Option<String>,
+    start: f64, // Seconds past J2000, TDB
+    end: f64,
+    step: f64,
+    format: Format,
+    output: Option<String>,
+}
+
+// What's being tabulated, and about what.
+struct Target {
+    body: String,
+    center: String,
+    orbit: OrbitalParameters,
+}
+
+struct Row {
+    t: f64,
+    position: DVec3,
+    velocity: DVec3,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+
+    if let Err(err) = run(&args) {
+        eprintln!("ephemeris: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let options = parse_options(args)?;
+
+    let target = match &options.elements {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            elements_from_export(&text).map_err(|err| format!("{}: {}", path, err))?
+        }
+        None => Target {
+            body: "moon".to_string(),
+            center: "earth".to_string(),
+            orbit: lunar_orbit(),
+        },
+    };
+
+    if let Some(body) = options.body.as_ref().filter(|body| **body != target.body) {
+        return Err(format!("the elements are for \"{}\", not \"{}\"", target.body, body));
+    }
+
+    let rows = tabulate(&options, |t| match options.model {
+        Model::Meeus => Ok(geocentric_state(t)),
+        Model::Elements => target
+            .orbit
+            .simulation_state(t)
+            .map_err(|err| format!("at {}: {}", time_scale::iso_string(t, TimeScale::Tdb), err)),
+    })?;
+
+    let text = match options.format {
+        Format::Csv => csv(&rows),
+        Format::Json => json(&options, &target, &rows)?,
+    };
+
+    match &options.output {
+        Some(path) => std::fs::write(path, text).map_err(|err| format!("{}: {}", path, err)),
+        None => std::io::stdout()
+            .write_all(text.as_bytes())
+            .map_err(|err| err.to_string()),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut body = None;
+    let mut model = Model::Meeus;
+    let mut elements = None;
+    let mut start = None;
+    let mut end = None;
+    let mut step = 3600.;
+    let mut format = Format::Csv;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value\n\n{}", flag, USAGE))
+        };
+
+        match flag.as_str() {
+            "--body" => body = Some(value()?.to_lowercase()),
+            "--model" => {
+                model = match value()?.to_lowercase().as_str() {
+                    "meeus" => Model::Meeus,
+                    "elements" => Model::Elements,
+                    other => return Err(format!("unknown model \"{}\", meeus or elements", other)),
+                }
+            }
+            "--elements" => elements = Some(value()?),
+            "--start" => start = Some(parse_date(&value()?)?),
+            "--end" => end = Some(parse_date(&value()?)?),
+            "--step" => step = parse_step(&value()?)?,
+            "--format" => {
+                format = match value()?.to_lowercase().as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    other => return Err(format!("unknown format \"{}\", csv or json", other)),
+                }
+            }
+            "--output" | "-o" => output = Some(value()?),
+            other => return Err(format!("unknown option \"{}\"\n\n{}", other, USAGE)),
+        }
+    }
+
+    if elements.is_some() && model != Model::Elements {
+        return Err("--elements only goes with --model elements".to_string());
+    }
+    if let Some(body) = body.as_ref().filter(|body| *body != "moon" && elements.is_none()) {
+        return Err(format!(
+            "no ephemeris for \"{}\" without a Horizons export, give one with --model elements --elements <file>",
+            body
+        ));
+    }
+
+    let start = match start {
+        Some(start) => start,
+        None => parse_date("now")?,
+    };
+    let end = end.unwrap_or(start + SECONDS_PER_DAY);
+
+    if end < start {
+        return Err("the end comes before the start".to_string());
+    }
+    if ((end - start) / step) as usize >= MAX_ROWS {
+        return Err(format!("more than {} rows, use a longer step", MAX_ROWS));
+    }
+
+    Ok(Options {
+        body,
+        model,
+        elements,
+        start,
+        end,
+        step,
+        format,
+        output,
+    })
+}
+
+fn parse_date(text: &str) -> Result<f64, String> {
+    time_scale::parse_date(text, TimeScale::Tdb).map_err(|err| err.to_string())
+}
+
+// "3600", "90s", "30m", "1h", "0.5d"
+fn parse_step(text: &str) -> Result<f64, String> {
+    let text = text.trim();
+    let (number, unit) = match text.char_indices().last() {
+        Some((i, 's')) => (&text[..i], 1.),
+        Some((i, 'm')) => (&text[..i], 60.),
+        Some((i, 'h')) => (&text[..i], 3600.),
+        Some((i, 'd')) => (&text[..i], SECONDS_PER_DAY),
+        _ => (text, 1.),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(step) if step > 0. && step.is_finite() => Ok(step * unit),
+        _ => Err(format!("can't use \"{}\" as a step", text)),
+    }
+}
+
+// The ellipse from a Horizons export and the bodies it names. The export has no secular
+// rates; for the Moon the built-in ones are added on, as the app does when it loads one.
+fn elements_from_export(text: &str) -> Result<Target, String> {
+    let elements = HorizonsElements::parse(text).map_err(|err| err.to_string())?;
+    let body = elements.target.to_lowercase();
+    let mut orbit = elements.orbital_parameters();
+
+    if body == "moon" {
+        let rates = lunar_orbit();
+        orbit.longitude_asc_node_rate = rates.longitude_asc_node_rate;
+        orbit.arg_of_periapsis_rate = rates.arg_of_periapsis_rate;
+        orbit.inclination_rate = rates.inclination_rate;
+        orbit.eccentricity_rate = rates.eccentricity_rate;
+    }
+
+    Ok(Target {
+        body,
+        center: elements.center.to_lowercase(),
+        orbit,
+    })
+}
+
+fn tabulate(
+    options: &Options,
+    state: impl Fn(f64) -> Result<(DVec3, DVec3), String>,
+) -> Result<Vec<Row>, String> {
+    let count = ((options.end - options.start) / options.step).floor() as usize + 1;
+
+    // Stepped by multiplying rather than adding, so long tables don't drift.
+    (0..count)
+        .map(|i| {
+            let t = options.start + i as f64 * options.step;
+            let (position, velocity) = state(t)?;
+            Ok(Row { t, position, velocity })
+        })
+        .collect()
+}
+
+const COLUMNS: [&str; 16] = [
+    "jd_tdb", "utc", "x_km", "y_km", "z_km", "vx_km_s", "vy_km_s", "vz_km_s", "x_scene",
+    "y_scene", "z_scene", "vx_scene_s", "vy_scene_s", "vz_scene_s", "distance_km", "speed_km_s",
+];
+
+fn values(row: &Row) -> [String; 16] {
+    let scene_position = to_scene(row.position);
+    let scene_velocity = to_scene(row.velocity);
+
+    [
+        format!("{:.8}", time_scale::julian_date(row.t)),
+        utc_string(row.t),
+        format!("{:.6}", row.position.x),
+        format!("{:.6}", row.position.y),
+        format!("{:.6}", row.position.z),
+        format!("{:.9}", row.velocity.x),
+        format!("{:.9}", row.velocity.y),
+        format!("{:.9}", row.velocity.z),
+        format!("{:.9}", scene_position.x),
+        format!("{:.9}", scene_position.y),
+        format!("{:.9}", scene_position.z),
+        format!("{:.12}", scene_velocity.x),
+        format!("{:.12}", scene_velocity.y),
+        format!("{:.12}", scene_velocity.z),
+        format!("{:.6}", row.position.length()),
+        format!("{:.9}", row.velocity.length()),
+    ]
+}
+
+fn utc_string(t: f64) -> String {
+    let utc = time_scale::convert(t, TimeScale::Tdb, TimeScale::Utc);
+    match time_scale::calendar_date(utc) {
+        Some(date) => format!("{}", date.format("%Y-%m-%dT%H:%M:%S%.3fZ")),
+        None => format!("JD {:.8}", time_scale::julian_date(utc)),
+    }
+}
+
+fn csv(rows: &[Row]) -> String {
+    let mut text = COLUMNS.join(",");
+    text.push('\n');
+
+    for row in rows {
+        text.push_str(&values(row).join(","));
+        text.push('\n');
+    }
+
+    text
+}
+
+#[derive(Serialize)]
+struct Table<'a> {
+    body: &'a str,
+    center: &'a str,
+    frame: &'static str,
+    model: &'static str,
+    rows: Vec<JsonRow>,
+}
+
+// The CSV's columns, with the numbers left as numbers.
+#[derive(Serialize)]
+struct JsonRow {
+    jd_tdb: f64,
+    utc: String,
+    x_km: f64,
+    y_km: f64,
+    z_km: f64,
+    vx_km_s: f64,
+    vy_km_s: f64,
+    vz_km_s: f64,
+    x_scene: f64,
+    y_scene: f64,
+    z_scene: f64,
+    vx_scene_s: f64,
+    vy_scene_s: f64,
+    vz_scene_s: f64,
+    distance_km: f64,
+    speed_km_s: f64,
+}
+
+fn json(options: &Options, target: &Target, rows: &[Row]) -> Result<String, String> {
+    let model = match options.model {
+        Model::Meeus => "meeus",
+        Model::Elements => "elements",
+    };
+
+    let rows = rows
+        .iter()
+        .map(|row| {
+            let scene_position = to_scene(row.position);
+            let scene_velocity = to_scene(row.velocity);
+
+            JsonRow {
+                jd_tdb: time_scale::julian_date(row.t),
+                utc: utc_string(row.t),
+                x_km: row.position.x,
+                y_km: row.position.y,
+                z_km: row.position.z,
+                vx_km_s: row.velocity.x,
+                vy_km_s: row.velocity.y,
+                vz_km_s: row.velocity.z,
+                x_scene: scene_position.x,
+                y_scene: scene_position.y,
+                z_scene: scene_position.z,
+                vx_scene_s: scene_velocity.x,
+                vy_scene_s: scene_velocity.y,
+                vz_scene_s: scene_velocity.z,
+                distance_km: row.position.length(),
+                speed_km_s: row.velocity.length(),
+            }
+        })
+        .collect();
+
+    let table = Table {
+        body: &target.body,
+        center: &target.center,
+        frame: "ICRF",
+        model,
+        rows,
+    };
+
+    let mut text = serde_json::to_string_pretty(&table).map_err(|err| err.to_string())?;
+    text.push('\n');
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn steps_take_a_unit() {
+        assert_eq!(parse_step("90s"), Ok(90.));
+        assert_eq!(parse_step("0.5d"), Ok(43200.));
+        assert_eq!(parse_step("30m"), Ok(1800.));
+        assert_eq!(parse_step("3600"), Ok(3600.));
+        assert!(parse_step("-1").is_err());
+        assert!(parse_step("0").is_err());
+        assert!(parse_step("h").is_err());
+        assert!(parse_step("inf").is_err());
+    }
+
+    #[test]
+    fn options_are_checked() {
+        let options = parse_options(&args("--start 2000-01-01 --end 2000-01-02 --step 1h")).unwrap();
+        assert_eq!(((options.end - options.start) / options.step).round(), 24.);
+
+        let backwards = parse_options(&args("--start 2000-01-02 --end 2000-01-01"));
+        assert_eq!(backwards.err().as_deref(), Some("the end comes before the start"));
+
+        // 10,000 days a second apart
+        let too_many = parse_options(&args("--start 2451545 --end 2461545 --step 1s"));
+        assert!(too_many.err().unwrap().starts_with("more than"));
+
+        assert!(parse_options(&args("--body mars")).is_err());
+        assert!(parse_options(&args("--model elements --body mars")).is_err());
+        assert!(parse_options(&args("--body Moon")).is_ok());
+        assert!(parse_options(&args("--step")).is_err());
+    }
+
+    #[test]
+    fn elements_files_need_the_elements_model() {
+        let ignored = parse_options(&args("--elements moon.txt"));
+        assert_eq!(ignored.err().as_deref(), Some("--elements only goes with --model elements"));
+
+        let options = parse_options(&args("--model elements --body mars --elements mars.txt")).unwrap();
+        assert_eq!(options.body.as_deref(), Some("mars"));
+    }
+
+    #[test]
+    fn only_the_moon_gets_lunar_rates() {
+        let moon = include_str!("../../tests/fixtures/moon_2000-01-01.txt");
+        let target = elements_from_export(moon).unwrap();
+        assert_eq!((target.body.as_str(), target.center.as_str()), ("moon", "earth"));
+        assert_eq!(target.orbit.longitude_asc_node_rate, lunar_orbit().longitude_asc_node_rate);
+
+        let mars = moon.replace("Moon (301)", "Mars (499)");
+        let target = elements_from_export(&mars).unwrap();
+        assert_eq!(target.body, "mars");
+        assert_eq!(target.orbit.longitude_asc_node_rate, 0.);
+        assert_eq!(target.orbit.arg_of_periapsis_rate, 0.);
+    }
+
+    #[test]
+    fn json_is_escaped_by_serde() {
+        let options = parse_options(&args("--start 2000-01-01 --end 2000-01-01")).unwrap();
+        let rows = tabulate(&options, |t| Ok((geocentric_state(t).0, DVec3::ZERO))).unwrap();
+        let target = Target {
+            body: "mars \"red\"".to_string(),
+            center: "sun".to_string(),
+            orbit: lunar_orbit(),
+        };
+        let text = json(&options, &target, &rows).unwrap();
+
+        let table: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(table["body"], "mars \"red\"");
+        assert_eq!(table["center"], "sun");
+        assert_eq!(table["rows"].as_array().unwrap().len(), 1);
+        assert_eq!(table["rows"][0]["utc"], "2000-01-01T00:00:00.000Z");
+    }
+}
@@ -0,0 +1,192 @@
+use glam::DQuat;
+
+use crate::time_scale::{convert, TimeScale};
+
+// Earth's orientation as a function of time alone, so any date gets the right face of
+// the planet without anything being accumulated. Sidereal time follows IAU 2006 (Capitaine
+// et al. 2003), precession IAU 1976 (Lieske) and nutation the largest terms of IAU 1980,
+// as given in Meeus, Astronomical Algorithms ch. 21 and 22.
+//
+// `earth_orientation` takes UTC, used as UT1 (never more than 0.9 s apart) for the
+// rotation angle and converted to TT for precession and nutation. The other functions take
+// whichever scale their comment says.
+
+const SECONDS_PER_DAY: f64 = 86400.;
+const DAYS_PER_CENTURY: f64 = 36525.;
+const ARCSEC_TO_RAD: f64 = std::f64::consts::PI / (180. * 3600.);
+
+fn julian_centuries(t: f64) -> f64 {
+    t / (SECONDS_PER_DAY * DAYS_PER_CENTURY)
+}
+
+// Earth Rotation Angle in radians, `t` seconds of UT1 past J2000.
+pub fn earth_rotation_angle(t: f64) -> f64 {
+    let days = t / SECONDS_PER_DAY;
+    // Whole days are whole turns, keep them out of the product to hold on to precision.
+    let turns = days.fract() + 0.7790572732640 + 0.00273781191135448 * days;
+    (turns.fract() * 2. * std::f64::consts::PI).rem_euclid(2. * std::f64::consts::PI)
+}
+
+// Greenwich Mean Sidereal Time in radians, `t` seconds of UT1 past J2000.
+pub fn greenwich_mean_sidereal_time(t: f64) -> f64 {
+    let centuries = julian_centuries(t);
+    let polynomial = 0.014506 + 4612.156534 * centuries + 1.3915817 * centuries.powi(2)
+        - 0.00000044 * centuries.powi(3)
+        - 0.000029956 * centuries.powi(4)
+        - 0.0000000368 * centuries.powi(5);
+
+    (earth_rotation_angle(t) + polynomial * ARCSEC_TO_RAD).rem_euclid(2. * std::f64::consts::PI)
+}
+
+// Greenwich Apparent Sidereal Time in radians, GMST plus the equation of the equinoxes.
+pub fn greenwich_apparent_sidereal_time(t: f64) -> f64 {
+    (greenwich_mean_sidereal_time(t) + equation_of_the_equinoxes(t))
+        .rem_euclid(2. * std::f64::consts::PI)
+}
+
+// The nutation in longitude projected onto the equator, in radians, `t` seconds of TT.
+fn equation_of_the_equinoxes(t: f64) -> f64 {
+    let (nutation_longitude, nutation_obliquity) = nutation(t);
+    nutation_longitude * (mean_obliquity(t) + nutation_obliquity).cos()
+}
+
+// Mean obliquity of the ecliptic in radians (IAU 2006).
+pub fn mean_obliquity(t: f64) -> f64 {
+    let centuries = julian_centuries(t);
+    let arcsec = 84381.406 - 46.836769 * centuries - 0.0001831 * centuries.powi(2)
+        + 0.00200340 * centuries.powi(3);
+
+    arcsec * ARCSEC_TO_RAD
+}
+
+// Nutation in longitude and obliquity in radians, good to about half an arcsecond.
+pub fn nutation(t: f64) -> (f64, f64) {
+    let centuries = julian_centuries(t);
+
+    let moon_node = (125.04452 - 1934.136261 * centuries).to_radians();
+    let sun_longitude = (280.4665 + 36000.7698 * centuries).to_radians();
+    let moon_longitude = (218.3165 + 481267.8813 * centuries).to_radians();
+
+    let longitude = -17.20 * moon_node.sin() - 1.32 * (2. * sun_longitude).sin()
+        - 0.23 * (2. * moon_longitude).sin()
+        + 0.21 * (2. * moon_node).sin();
+    let obliquity = 9.20 * moon_node.cos() + 0.57 * (2. * sun_longitude).cos()
+        + 0.10 * (2. * moon_longitude).cos()
+        - 0.09 * (2. * moon_node).cos();
+
+    (longitude * ARCSEC_TO_RAD, obliquity * ARCSEC_TO_RAD)
+}
+
+// Rotates a vector from the mean equator and equinox of J2000 to the mean equator and
+// equinox of date.
+pub fn precession(t: f64) -> DQuat {
+    let centuries = julian_centuries(t);
+
+    let zeta = (2306.2181 * centuries + 0.30188 * centuries.powi(2) + 0.017998 * centuries.powi(3))
+        * ARCSEC_TO_RAD;
+    let z = (2306.2181 * centuries + 1.09468 * centuries.powi(2) + 0.018203 * centuries.powi(3))
+        * ARCSEC_TO_RAD;
+    let theta = (2004.3109 * centuries - 0.42665 * centuries.powi(2) - 0.041833 * centuries.powi(3))
+        * ARCSEC_TO_RAD;
+
+    DQuat::from_rotation_z(z) * DQuat::from_rotation_y(-theta) * DQuat::from_rotation_z(zeta)
+}
+
+// Rotates a vector from the mean equator and equinox of date to the true ones.
+pub fn nutation_rotation(t: f64) -> DQuat {
+    let (nutation_longitude, nutation_obliquity) = nutation(t);
+    let obliquity = mean_obliquity(t);
+
+    DQuat::from_rotation_x(obliquity + nutation_obliquity)
+        * DQuat::from_rotation_z(nutation_longitude)
+        * DQuat::from_rotation_x(-obliquity)
+}
+
+// Rotation from ITRF (x through Greenwich, z through the north pole) to ICRF at `t` seconds
+// of UTC past J2000, ignoring polar motion.
+pub fn earth_orientation(t: f64, precession_nutation: bool) -> DQuat {
+    if precession_nutation {
+        let tt = convert(t, TimeScale::Utc, TimeScale::Tt);
+        let to_true_of_date = nutation_rotation(tt) * precession(tt);
+        let sidereal_time = greenwich_mean_sidereal_time(t) + equation_of_the_equinoxes(tt);
+        to_true_of_date.inverse() * DQuat::from_rotation_z(sidereal_time)
+    } else {
+        DQuat::from_rotation_z(greenwich_mean_sidereal_time(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time_scale::J2000_JD;
+    use glam::DVec3;
+
+    fn seconds(jd: f64) -> f64 {
+        (jd - J2000_JD) * SECONDS_PER_DAY
+    }
+
+    fn arcsec(radians: f64) -> f64 {
+        radians / ARCSEC_TO_RAD
+    }
+
+    #[test]
+    fn rotation_angle_at_j2000() {
+        // IERS Conventions 2010, eq. 5.15.
+        assert!((earth_rotation_angle(0.).to_degrees() - 280.46061837504).abs() < 1e-9);
+    }
+
+    #[test]
+    fn matches_meeus_example_12_a_and_b() {
+        // 1987 April 10, 0h UT: 13h10m46.3668s. Meeus uses the IAU 1982 expression, which
+        // is within a few milliseconds of time of this one.
+        let gmst = greenwich_mean_sidereal_time(seconds(2446895.5));
+        assert!((gmst.to_degrees() - 197.693195).abs() < 1e-4);
+
+        // 19h21m UT the same day.
+        let gmst = greenwich_mean_sidereal_time(seconds(2446896.30625));
+        assert!((gmst.to_degrees() - 128.7378734).abs() < 1e-4);
+    }
+
+    #[test]
+    fn matches_meeus_example_22_a() {
+        // 1987 April 10, 0h TD. The series is cut down to the terms Meeus gives for half an
+        // arcsecond in longitude and a tenth in obliquity.
+        let t = seconds(2446895.5);
+        let (longitude, obliquity) = nutation(t);
+        assert!((arcsec(longitude) + 3.788).abs() < 0.5);
+        assert!((arcsec(obliquity) - 9.443).abs() < 0.1);
+
+        // 23°26'27.407", from the IAU 1980 expression, 0.04" off the IAU 2006 one.
+        assert!((arcsec(mean_obliquity(t)) - 84387.407).abs() < 0.1);
+    }
+
+    #[test]
+    fn matches_meeus_example_21_b() {
+        // θ Persei, moved by its proper motion to 2028 November 13.19 TD, precessed from
+        // J2000 to the mean equator and equinox of that date.
+        let direction = |ra: f64, dec: f64| {
+            let (ra, dec) = (ra.to_radians(), dec.to_radians());
+            DVec3::new(dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin())
+        };
+        let of_date = precession(seconds(2462088.69)) * direction(41.054063, 49.227750);
+
+        assert!(arcsec(of_date.angle_between(direction(41.547214, 49.348483))) < 0.1);
+    }
+
+    #[test]
+    fn pole_and_greenwich_follow_the_models() {
+        let t = seconds(2460409.5); // 2024 April 8
+        let tt = convert(t, TimeScale::Utc, TimeScale::Tt);
+
+        let orientation = earth_orientation(t, true);
+        let pole = (nutation_rotation(tt) * precession(tt)).inverse() * DVec3::Z;
+        assert!(arcsec((orientation * DVec3::Z).angle_between(pole)) < 1e-6);
+        // A quarter century of precession tips the pole about 500" away from ICRF's.
+        assert!((arcsec(pole.angle_between(DVec3::Z)) - 2004.3 * 0.243).abs() < 20.);
+
+        // Without precession and nutation Greenwich sits at the mean sidereal time.
+        let greenwich = earth_orientation(t, false) * DVec3::X;
+        let right_ascension = greenwich.y.atan2(greenwich.x).rem_euclid(2. * std::f64::consts::PI);
+        assert!((right_ascension - greenwich_mean_sidereal_time(t)).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,497 @@
+use glam::DVec3;
+use ndarray::{arr1, arr2, Array2};
+use std::f64::consts::PI;
+
+use crate::frames::ReferenceFrame;
+use crate::kepler::{self, KeplerError};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+// Classical orbital elements and the two-body motion they describe. Positions and
+// velocities come out relative to the parent, in the frame the elements are given in
+// (`state_vector`) or in ICRF (`simulation_state`).
+
+pub const G: f64 = 6.67e-20; // In KM!
+
+const SECONDS_PER_CENTURY: f64 = 36525. * 86400.;
+
+// Eccentricities this close to 1 are treated as parabolic.
+const PARABOLIC_TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(Component)
+)]
+pub struct OrbitalParameters {
+    pub semimajor_axis: f64,     // KM
+    pub longitude_asc_node: f64, // Radians
+    pub arg_of_periapsis: f64,   // Radians
+    pub inclination: f64,        // Radians
+    pub eccentricity: f64,       // Unitless
+    pub mass_of_parent: f64,     // KG
+    pub grav_parameter: f64,     // KM^3s^-2
+    pub period: f64,             // Seconds
+    pub rotational_period: f64,  // Seconds
+    pub mean_anomaly_at_epoch: f64, // Angles
+    pub epoch: f64,              // Seconds past J2000
+    pub frame: ReferenceFrame,   // Frame the elements are given in
+    // Secular drift of the elements from `epoch` on, per second. Radians apart from
+    // eccentricity.
+    pub longitude_asc_node_rate: f64,
+    pub arg_of_periapsis_rate: f64,
+    pub inclination_rate: f64,
+    pub eccentricity_rate: f64,
+}
+
+impl Default for OrbitalParameters {
+    fn default() -> Self {
+        OrbitalParameters {
+            semimajor_axis: 0.,
+            longitude_asc_node: 0.,
+            arg_of_periapsis: 0.,
+            inclination: 0.,
+            eccentricity: 0.,
+            mass_of_parent: 0.,
+            grav_parameter: 0.,
+            period: 0.,
+            rotational_period: 0.,
+            mean_anomaly_at_epoch: 0.,
+            epoch: 0.,
+            frame: ReferenceFrame::Icrf,
+            longitude_asc_node_rate: 0.,
+            arg_of_periapsis_rate: 0.,
+            inclination_rate: 0.,
+            eccentricity_rate: 0.,
+        }
+    }
+}
+
+impl OrbitalParameters {
+    pub fn position(self, t: f64) -> Result<DVec3, KeplerError> {
+        Ok(self.state_vector(t)?.0)
+    }
+
+    pub fn velocity(self, t: f64) -> Result<DVec3, KeplerError> {
+        Ok(self.state_vector(t)?.1)
+    }
+
+    // Position (KM) and velocity (KM/s) relative to the parent at `t` seconds past J2000.
+    pub fn state_vector(self, t: f64) -> Result<(DVec3, DVec3), KeplerError> {
+        let mut elements = self.osculating(t);
+
+        if elements.is_closed() {
+            elements.period = 2. * PI * (elements.semimajor_axis.powf(3.) / elements.grav_parameter).sqrt();
+        }
+
+        let mean_anomaly = elements.mean_anomaly(t);
+        let anomaly = elements.anomaly(mean_anomaly)?;
+        let true_anomaly = elements.true_anomaly(anomaly);
+
+        Ok(elements.state_at_true_anomaly(true_anomaly))
+    }
+
+    // The ellipse at `t`, once the element rates have acted on it, with the rates taken
+    // out. The mean anomaly is left alone, the body keeps moving round at the mean motion
+    // while the ellipse turns under it.
+    pub fn osculating(&self, t: f64) -> OrbitalParameters {
+        let dt = t - self.epoch;
+
+        OrbitalParameters {
+            longitude_asc_node: self.longitude_asc_node + self.longitude_asc_node_rate * dt,
+            arg_of_periapsis: self.arg_of_periapsis + self.arg_of_periapsis_rate * dt,
+            inclination: self.inclination + self.inclination_rate * dt,
+            eccentricity: (self.eccentricity + self.eccentricity_rate * dt).max(0.),
+            longitude_asc_node_rate: 0.,
+            arg_of_periapsis_rate: 0.,
+            inclination_rate: 0.,
+            eccentricity_rate: 0.,
+            ..*self
+        }
+    }
+
+    // Largest difference in Ω, ω, i or e between two sets of elements.
+    pub fn element_change(&self, other: &OrbitalParameters) -> f64 {
+        [
+            self.longitude_asc_node - other.longitude_asc_node,
+            self.arg_of_periapsis - other.arg_of_periapsis,
+            self.inclination - other.inclination,
+            self.eccentricity - other.eccentricity,
+        ]
+        .iter()
+        .fold(0., |max: f64, change| max.max(change.abs()))
+    }
+
+    // State vector in ICRF, the frame everything in the simulation is kept in.
+    pub fn simulation_state(&self, t: f64) -> Result<(DVec3, DVec3), KeplerError> {
+        let (posn, vel) = self.state_vector(t)?;
+        Ok((self.frame.to_icrf(posn, t), self.frame.to_icrf(vel, t)))
+    }
+
+    // Position (KM) and velocity (KM/s) relative to the parent at a given true anomaly.
+    pub fn state_at_true_anomaly(&self, true_anomaly: f64) -> (DVec3, DVec3) {
+        let semi_latus_rectum = self.semi_latus_rectum();
+        let distance = semi_latus_rectum / (1. + self.eccentricity * true_anomaly.cos());
+
+        // Perifocal frame: x towards periapsis, z along the angular momentum.
+        let x = distance * true_anomaly.cos();
+        let y = distance * true_anomaly.sin();
+        let z = 0.;
+
+        let speed_scale = (self.grav_parameter / semi_latus_rectum).sqrt();
+        let v_x = -speed_scale * true_anomaly.sin();
+        let v_y = speed_scale * (self.eccentricity + true_anomaly.cos());
+
+        let trans = self.perifocal_to_reference();
+        let final_coords = trans.dot(&arr1(&[x, y, z]));
+        let final_velocity = trans.dot(&arr1(&[v_x, v_y, 0.]));
+
+        (
+            DVec3::new(final_coords[0], final_coords[1], final_coords[2]),
+            DVec3::new(final_velocity[0], final_velocity[1], final_velocity[2]),
+        )
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.eccentricity < 1. - PARABOLIC_TOLERANCE
+    }
+
+    pub fn is_parabolic(&self) -> bool {
+        (self.eccentricity - 1.).abs() <= PARABOLIC_TOLERANCE
+    }
+
+    // Unsigned semimajor axis for ellipses, negative for hyperbolas.
+    fn signed_semimajor_axis(&self) -> f64 {
+        if self.is_closed() {
+            self.semimajor_axis
+        } else {
+            -self.semimajor_axis.abs()
+        }
+    }
+
+    pub fn semi_latus_rectum(&self) -> f64 {
+        if self.is_parabolic() {
+            2. * self.semimajor_axis
+        } else {
+            self.signed_semimajor_axis() * (1. - self.eccentricity * self.eccentricity)
+        }
+    }
+
+    // Builds elements from a position (KM) and velocity (KM/s) in ICRF relative to a parent
+    // with gravitational parameter `mu`, observed at `epoch` seconds past J2000.
+    pub fn from_state_vector(r: DVec3, v: DVec3, mu: f64, epoch: f64) -> OrbitalParameters {
+        const SMALL: f64 = 1e-10;
+
+        let distance = r.length();
+        let speed = v.length();
+        let h = r.cross(v);
+        let node = DVec3::new(-h.y, h.x, 0.);
+        let e_vec = ((speed * speed - mu / distance) * r - r.dot(v) * v) / mu;
+        let eccentricity = e_vec.length();
+
+        let energy = speed * speed / 2. - mu / distance;
+        let semimajor_axis = -mu / (2. * energy);
+        let inclination = (h.z / h.length()).clamp(-1., 1.).acos();
+
+        // Equatorial orbits have no ascending node, measure from the x axis instead.
+        let longitude_asc_node = if node.length() > SMALL {
+            node.y.atan2(node.x).rem_euclid(2. * PI)
+        } else {
+            0.
+        };
+
+        let arg_of_periapsis = if eccentricity < SMALL {
+            0.
+        } else if node.length() > SMALL {
+            let w = (node.dot(e_vec) / (node.length() * eccentricity)).clamp(-1., 1.).acos();
+            if e_vec.z < 0. { 2. * PI - w } else { w }
+        } else {
+            let w = e_vec.y.atan2(e_vec.x).rem_euclid(2. * PI);
+            if h.z < 0. { 2. * PI - w } else { w }
+        };
+
+        // Circular orbits have no periapsis, measure from the node (or x axis) instead.
+        let true_anomaly = if eccentricity > SMALL {
+            let nu = (e_vec.dot(r) / (eccentricity * distance)).clamp(-1., 1.).acos();
+            if r.dot(v) < 0. { 2. * PI - nu } else { nu }
+        } else {
+            let reference = if node.length() > SMALL { node.normalize() } else { DVec3::X };
+            let u = reference.dot(r / distance).clamp(-1., 1.).acos();
+            if reference.cross(r).dot(h) < 0. { 2. * PI - u } else { u }
+        };
+
+        let (semimajor_axis, mean_anomaly, period) = if (eccentricity - 1.).abs() <= PARABOLIC_TOLERANCE {
+            // Parabolas keep their periapsis distance in `semimajor_axis`.
+            let d = (true_anomaly / 2.).tan();
+            (h.length_squared() / (2. * mu), d + d * d * d / 3., f64::INFINITY)
+        } else if eccentricity > 1. {
+            let h_anomaly = 2.
+                * (((eccentricity - 1.) / (eccentricity + 1.)).sqrt() * (true_anomaly / 2.).tan()).atanh();
+            (
+                semimajor_axis,
+                eccentricity * h_anomaly.sinh() - h_anomaly,
+                f64::INFINITY,
+            )
+        } else {
+            let eccentric_anomaly = 2.
+                * (((1. - eccentricity) / (1. + eccentricity)).sqrt() * (true_anomaly / 2.).tan()).atan();
+            (
+                semimajor_axis,
+                (eccentric_anomaly - eccentricity * eccentric_anomaly.sin()).rem_euclid(2. * PI),
+                2. * PI * (semimajor_axis.powf(3.) / mu).sqrt(),
+            )
+        };
+
+        OrbitalParameters {
+            semimajor_axis,
+            longitude_asc_node,
+            arg_of_periapsis,
+            inclination,
+            eccentricity,
+            mass_of_parent: mu / G,
+            grav_parameter: mu,
+            period,
+            rotational_period: 0.,
+            mean_anomaly_at_epoch: mean_anomaly,
+            epoch,
+            frame: ReferenceFrame::Icrf,
+            ..Default::default()
+        }
+    }
+
+    // Rotation from the perifocal frame into the frame the elements are given in.
+    fn perifocal_to_reference(&self) -> Array2<f64> {
+        // cos Ω cos ω − sin Ω sin ω cos i 
+        let i_1_1 = self.longitude_asc_node.cos() * self.arg_of_periapsis.cos() - self.longitude_asc_node.sin() * self.arg_of_periapsis.sin() * self.inclination.cos();
+        // − cos Ω sin ω − sin Ω cos ω cos i
+        let i_1_2  = - self.longitude_asc_node.cos() * self.arg_of_periapsis.sin() - self.longitude_asc_node.sin() * self.arg_of_periapsis.cos() * self.inclination.cos();
+        // sin Ω sin i
+        let i_1_3 = self.longitude_asc_node.sin() * self.inclination.sin();
+
+        // sin Ω cos ω + cos Ω sin ω cos i
+        let i_2_1 = self.longitude_asc_node.sin() * self.arg_of_periapsis.cos() + self.longitude_asc_node.cos() * self.arg_of_periapsis.sin() * self.inclination.cos();
+        // − sin Ω sin ω + cos Ω cos ω cos i
+        let i_2_2 = - self.longitude_asc_node.sin() * self.arg_of_periapsis.sin() + self.longitude_asc_node.cos() * self.arg_of_periapsis.cos() * self.inclination.cos();
+        // − cos Ω sin i
+        let i_2_3 = - self.longitude_asc_node.cos() * self.inclination.sin();
+
+        // sin ω sin i
+        let i_3_1 = self.arg_of_periapsis.sin() * self.inclination.sin();
+        // cos ω sin i
+        let i_3_2 = self.arg_of_periapsis.cos() * self.inclination.sin();
+        // cos i
+        let i_3_3 = self.inclination.cos();
+
+        arr2(&[
+            [i_1_1, i_1_2, i_1_3],
+            [i_2_1, i_2_2, i_2_3],
+            [i_3_1, i_3_2, i_3_3],
+        ])
+    }
+
+    pub fn mean_anomaly(&self, t: f64) -> f64 {
+        if !self.is_closed() {
+            return self.mean_anomaly_at_epoch + self.mean_motion() * (t - self.epoch);
+        }
+
+        let dt = (t - self.epoch) % self.period;
+        (self.mean_anomaly_at_epoch + self.mean_motion() * dt) % (2. * std::f64::consts::PI)
+    }
+
+    // Eccentric, hyperbolic or parabolic anomaly depending on the kind of orbit.
+    pub fn anomaly(&self, mean_anomaly: f64) -> Result<f64, KeplerError> {
+        if self.is_parabolic() {
+            kepler::parabolic_anomaly(mean_anomaly)
+        } else if self.is_closed() {
+            self.eccentric_anomaly(mean_anomaly)
+        } else {
+            self.hyperbolic_anomaly(mean_anomaly)
+        }
+    }
+
+    pub fn eccentric_anomaly(&self, mean_anomaly: f64) -> Result<f64, KeplerError> {
+        kepler::eccentric_anomaly(mean_anomaly, self.eccentricity)
+    }
+
+    pub fn hyperbolic_anomaly(&self, mean_anomaly: f64) -> Result<f64, KeplerError> {
+        kepler::hyperbolic_anomaly(mean_anomaly, self.eccentricity)
+    }
+
+    pub fn mean_motion(&self) -> f64 {
+        if self.is_parabolic() {
+            (self.grav_parameter / (2. * self.semimajor_axis.powf(3.))).sqrt()
+        } else if self.is_closed() {
+            (2. * std::f64::consts::PI) / self.period
+        } else {
+            (self.grav_parameter / self.semimajor_axis.abs().powf(3.)).sqrt()
+        }
+    }
+
+    pub fn true_anomaly(&self, anomaly: f64) -> f64 {
+        if self.is_parabolic() {
+            return 2. * anomaly.atan();
+        }
+
+        if !self.is_closed() {
+            let e = self.eccentricity;
+            return 2. * (((e + 1.) / (e - 1.)).sqrt() * (anomaly / 2.).tanh()).atan();
+        }
+
+        2. * (((1. + self.eccentricity) / (1. - self.eccentricity)).sqrt() * (anomaly / 2.).tan())
+            .atan()
+    }
+
+    pub fn distance(&self, anomaly: f64) -> f64 {
+        if self.is_parabolic() {
+            return self.semimajor_axis * (1. + anomaly * anomaly);
+        }
+
+        if !self.is_closed() {
+            return self.signed_semimajor_axis() * (1. - self.eccentricity * anomaly.cosh());
+        }
+
+        self.semimajor_axis * (1. - self.eccentricity * anomaly.cos())
+    }
+}
+
+// The Moon's elements at J2000. The app loads better ones from
+// assets/horizons/moon.txt and uses these only until that file has loaded.
+//
+// EC= 6.476694128611285E-02 QR= 3.565283199467715E+05 IN= 5.240010829674768E+00
+// OM= 1.239837028145578E+02 W = 3.081359034620368E+02 Tp=  2451533.965359285008
+// N = 1.546268358955514E-04 MA= 1.407402571142365E+02 TA= 1.451550311169052E+02
+// A = 3.812186883524646E+05 AD= 4.059090567581577E+05 PR= 2.328185776517964E+06
+
+pub fn lunar_orbit() -> OrbitalParameters {
+    let mass_of_parent = 5.9722e+24;
+
+    // Mean rates from Meeus ch. 47 with general precession taken out, as the elements are
+    // referred to the J2000 ecliptic rather than that of date. The nodes go round in 18.6
+    // years and the line of apsides in 8.85.
+    OrbitalParameters {
+        mean_anomaly_at_epoch: 2.45638088,
+        semimajor_axis: 3.812186883524646E+05,
+        eccentricity: 6.476694128611285E-02,
+        inclination: 0.0914554418,
+        arg_of_periapsis: 5.37798606,
+        longitude_asc_node: 2.16392383,
+        mass_of_parent,
+        grav_parameter: G * mass_of_parent,
+        period: 2360584.6848,
+        // The app turns the Moon from its rotation model rather than at a fixed rate
+        rotational_period: 0.,
+        epoch: -43200., // 2000-01-01 00:00 TDB, half a day before J2000
+        frame: ReferenceFrame::Ecliptic,
+        longitude_asc_node_rate: (-1935.5332604f64).to_radians() / SECONDS_PER_CENTURY,
+        arg_of_periapsis_rate: 6003.1500178f64.to_radians() / SECONDS_PER_CENTURY,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EARTH_MU: f64 = 398600.4418;
+
+    fn close(a: DVec3, b: DVec3, tolerance: f64) -> bool {
+        (a - b).length() <= tolerance * b.length().max(1.)
+    }
+
+    #[test]
+    fn state_vector_round_trips_through_elements() {
+        let states = [
+            // Low, nearly circular and inclined.
+            (DVec3::new(6778., 0., 0.), DVec3::new(0., 5.2, 5.5)),
+            // Molniya-like.
+            (DVec3::new(-1500., 6000., 2500.), DVec3::new(-8.5, -1.5, 3.)),
+            // Escaping.
+            (DVec3::new(7000., 100., -300.), DVec3::new(0.5, 12., 1.)),
+            // Retrograde.
+            (DVec3::new(0., 42164., 0.), DVec3::new(3.07, 0., -0.05)),
+        ];
+
+        for &(r, v) in &states {
+            let elements = OrbitalParameters::from_state_vector(r, v, EARTH_MU, 1000.);
+            let (position, velocity) = elements.state_vector(1000.).unwrap();
+            assert!(close(position, r, 1e-9), "{:?} vs {:?}", position, r);
+            assert!(close(velocity, v, 1e-9), "{:?} vs {:?}", velocity, v);
+        }
+    }
+
+    #[test]
+    fn anomalies_and_distance_agree() {
+        let elements = OrbitalParameters::from_state_vector(
+            DVec3::new(-1500., 6000., 2500.),
+            DVec3::new(-8.5, -1.5, 3.),
+            EARTH_MU,
+            0.,
+        );
+
+        for i in 0..12 {
+            let t = i as f64 * 1000.;
+            let mean_anomaly = elements.mean_anomaly(t);
+            let eccentric_anomaly = elements.anomaly(mean_anomaly).unwrap();
+            let true_anomaly = elements.true_anomaly(eccentric_anomaly);
+            let distance = elements.distance(eccentric_anomaly);
+
+            let position = elements.position(t).unwrap();
+            assert!((position.length() - distance).abs() < 1e-6);
+            let (from_true_anomaly, _) = elements.state_at_true_anomaly(true_anomaly);
+            assert!(close(from_true_anomaly, position, 1e-9));
+        }
+    }
+
+    #[test]
+    fn closed_orbits_repeat_after_a_period() {
+        let elements = OrbitalParameters::from_state_vector(
+            DVec3::new(6778., 0., 0.),
+            DVec3::new(0., 5.2, 5.5),
+            EARTH_MU,
+            0.,
+        );
+        assert!(elements.is_closed());
+
+        let start = elements.position(0.).unwrap();
+        let after = elements.position(elements.period).unwrap();
+        assert!(close(after, start, 1e-9));
+    }
+
+    #[test]
+    fn energy_is_conserved() {
+        let elements = lunar_orbit();
+        let energy = |t: f64| {
+            let (r, v) = elements.osculating(t).state_vector(t).unwrap();
+            v.length_squared() / 2. - elements.grav_parameter / r.length()
+        };
+
+        let start = energy(0.);
+        for &t in &[1e5, 1e6, -3e7] {
+            assert!(((energy(t) - start) / start).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rates_turn_the_ellipse() {
+        let elements = lunar_orbit();
+        let year = 365.25 * 86400.;
+        let later = elements.osculating(elements.epoch + year);
+
+        // The node regresses by about 19.3 degrees a year.
+        let change = later.longitude_asc_node - elements.longitude_asc_node;
+        assert!((change.to_degrees() + 19.35).abs() < 0.05);
+        assert_eq!(later.longitude_asc_node_rate, 0.);
+        // The argument of periapsis moves fastest, about 60 degrees a year.
+        assert!((later.element_change(&elements).to_degrees() - 60.03).abs() < 0.05);
+    }
+
+    #[test]
+    fn simulation_state_is_in_icrf() {
+        let elements = lunar_orbit();
+        let (ecliptic, _) = elements.state_vector(0.).unwrap();
+        let (icrf, _) = elements.simulation_state(0.).unwrap();
+        assert!(close(icrf, crate::frames::ecliptic_to_icrf(ecliptic), 1e-12));
+    }
+}
@@ -0,0 +1,157 @@
+use glam::{DQuat, DVec3};
+
+use crate::earth_orientation::{
+    earth_orientation, greenwich_apparent_sidereal_time, greenwich_mean_sidereal_time,
+    nutation_rotation, precession,
+};
+
+// Reference frames and the conversions between them. Everything in the simulation
+// (`SimulationPosition`, `RelativePosition`, `RelativeVelocity`) is in ICRF, data that
+// comes in some other frame is converted on the way in.
+//
+// ICRF      J2000 mean equator and equinox. x towards the vernal equinox, z towards the
+//           north celestial pole. The ~20 mas frame bias between the two is ignored.
+// Ecliptic  Ecliptic and equinox of J2000, the frame Horizons gives elements in by
+//           default. Same x axis as ICRF, tilted by the J2000 obliquity about it.
+// ITRF      Earth-fixed. x through Greenwich, z through the north pole, polar motion
+//           ignored. Rotates with the Earth, so conversions need the time.
+// TEME      True equator, mean equinox of date. What SGP4 produces.
+//
+// Bevy is Y-up and right-handed, so ICRF maps onto the scene as
+//
+//     scene X =  ICRF x  (vernal equinox)
+//     scene Y =  ICRF z  (north celestial pole, Earth's spin axis)
+//     scene Z = -ICRF y
+//
+// which is a -90 degree rotation about x, scaled by REAL_TO_WORLD. Being a rotation and
+// not a reflection, positions and orientations go through the same mapping.
+
+pub const REAL_TO_WORLD: f64 = 500. / 12742.; // 100 in world unit to 12,742 KM (Earth width)
+
+// Obliquity of the J2000 ecliptic as used by JPL (IAU 1976), 84381.448 arcseconds.
+pub const J2000_OBLIQUITY: f64 = 84381.448 / 3600. * std::f64::consts::PI / 180.;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum ReferenceFrame {
+    #[default]
+    Icrf,
+    Ecliptic,
+    Itrf,
+    Teme,
+}
+
+impl ReferenceFrame {
+    // `t` is seconds past J2000, only the Earth-fixed and of-date frames depend on it.
+    // Velocities in ITRF come out without the w x r term of the Earth's rotation.
+    pub fn to_icrf(self, vector: DVec3, t: f64) -> DVec3 {
+        match self {
+            ReferenceFrame::Icrf => vector,
+            ReferenceFrame::Ecliptic => ecliptic_to_icrf(vector),
+            ReferenceFrame::Itrf => itrf_to_icrf(vector, t),
+            ReferenceFrame::Teme => teme_to_icrf(vector, t),
+        }
+    }
+
+    pub fn from_icrf(self, vector: DVec3, t: f64) -> DVec3 {
+        match self {
+            ReferenceFrame::Icrf => vector,
+            ReferenceFrame::Ecliptic => icrf_to_ecliptic(vector),
+            ReferenceFrame::Itrf => icrf_to_itrf(vector, t),
+            ReferenceFrame::Teme => teme_to_icrf_rotation(t).inverse() * vector,
+        }
+    }
+}
+
+pub fn ecliptic_to_icrf(vector: DVec3) -> DVec3 {
+    DQuat::from_rotation_x(J2000_OBLIQUITY) * vector
+}
+
+pub fn icrf_to_ecliptic(vector: DVec3) -> DVec3 {
+    DQuat::from_rotation_x(-J2000_OBLIQUITY) * vector
+}
+
+pub fn itrf_to_icrf(vector: DVec3, t: f64) -> DVec3 {
+    earth_orientation(t, true) * vector
+}
+
+pub fn icrf_to_itrf(vector: DVec3, t: f64) -> DVec3 {
+    earth_orientation(t, true).inverse() * vector
+}
+
+pub fn teme_to_icrf(vector: DVec3, t: f64) -> DVec3 {
+    teme_to_icrf_rotation(t) * vector
+}
+
+// TEME is the true equator of date with its x axis on the mean equinox, so it's a turn
+// by the equation of the equinoxes away from true of date.
+fn teme_to_icrf_rotation(t: f64) -> DQuat {
+    let equation_of_equinoxes = greenwich_apparent_sidereal_time(t) - greenwich_mean_sidereal_time(t);
+    let to_true_of_date = nutation_rotation(t) * precession(t);
+
+    to_true_of_date.inverse() * DQuat::from_rotation_z(equation_of_equinoxes)
+}
+
+// ICRF to scene axes, see the table above.
+pub fn scene_axes() -> DQuat {
+    DQuat::from_rotation_x(-std::f64::consts::FRAC_PI_2)
+}
+
+// Converts a position in ICRF (KM) to world/scene coordinates.
+pub fn to_scene(position: DVec3) -> DVec3 {
+    scene_axes() * position * REAL_TO_WORLD
+}
+
+// Converts a rotation of ICRF axes to a scene rotation.
+pub fn to_scene_rotation(rotation: DQuat) -> DQuat {
+    let axes = scene_axes();
+    axes * rotation * axes.inverse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAMES: [ReferenceFrame; 4] = [
+        ReferenceFrame::Icrf,
+        ReferenceFrame::Ecliptic,
+        ReferenceFrame::Itrf,
+        ReferenceFrame::Teme,
+    ];
+
+    #[test]
+    fn conversions_round_trip() {
+        let vector = DVec3::new(7000., -1200., 3500.);
+        for &frame in &FRAMES {
+            for &t in &[-3e9, 0., 7.5e8] {
+                let back = frame.from_icrf(frame.to_icrf(vector, t), t);
+                assert!((back - vector).length() < 1e-8, "{:?} at {}", frame, t);
+            }
+        }
+    }
+
+    #[test]
+    fn ecliptic_pole_is_tilted_by_the_obliquity() {
+        let pole = ecliptic_to_icrf(DVec3::Z);
+        assert!((pole.angle_between(DVec3::Z) - J2000_OBLIQUITY).abs() < 1e-12);
+        assert!((ecliptic_to_icrf(DVec3::X) - DVec3::X).length() < 1e-15);
+    }
+
+    #[test]
+    fn teme_is_close_to_icrf_near_j2000() {
+        // Precession and nutation only amount to arcseconds a few days from J2000.
+        let teme = teme_to_icrf(DVec3::X, 86400.);
+        assert!(teme.angle_between(DVec3::X) < 1e-4);
+    }
+
+    #[test]
+    fn scene_is_y_up() {
+        let up = to_scene(DVec3::Z * 12742.);
+        assert!((up - DVec3::Y * 500.).length() < 1e-9);
+        let y = to_scene(DVec3::Y);
+        assert!((y.normalize() + DVec3::Z).length() < 1e-12);
+        let rotation = to_scene_rotation(DQuat::from_rotation_z(1.));
+        assert!((rotation * DVec3::Y - DVec3::Y).length() < 1e-12);
+    }
+}
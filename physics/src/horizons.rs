@@ -0,0 +1,344 @@
+use glam::DVec3;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::elements::{OrbitalParameters, G};
+use crate::frames::ReferenceFrame;
+use crate::time_scale::J2000_JD;
+
+// Parses JPL Horizons ELEMENTS and VECTORS exports: the body on the "Target body name"
+// line, orbiting the body on the "Center body name" line, and its osculating elements or
+// its position and velocity at one or more epochs.
+//
+// Symbol meaning:
+//
+// JDTDB    Julian Day Number, Barycentric Dynamical Time
+//   EC     Eccentricity, e
+//   QR     Periapsis distance, q (km)
+//   IN     Inclination w.r.t X-Y plane, i (degrees)
+//   OM     Longitude of Ascending Node, OMEGA, (degrees)
+//   W      Argument of Perifocus, w (degrees)
+//   Tp     Time of periapsis (Julian Day Number)
+//   N      Mean motion, n (degrees/sec)
+//   MA     Mean anomaly, M (degrees)
+//   TA     True anomaly, nu (degrees)
+//   A      Semi-major axis, a (km)
+//   AD     Apoapsis distance (km)
+//   PR     Sidereal orbit period (sec)
+//   X Y Z  Position (km)
+//   VX..VZ Velocity (km/sec)
+
+const SECONDS_PER_DAY: f64 = 86400.;
+const KM_PER_AU: f64 = 149597870.7;
+
+#[derive(Debug, Clone)]
+pub struct HorizonsElements {
+    pub target: String,
+    pub center: String,
+    pub grav_parameter: Option<f64>, // KM^3s^-2, from the "Keplerian GM" line
+    pub frame: ReferenceFrame,
+    pub records: Vec<ElementRecord>,
+}
+
+// One osculating element set, converted to KM and seconds. Angles stay in degrees.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ElementRecord {
+    pub jd_tdb: f64,
+    pub eccentricity: f64,
+    pub periapsis_distance: f64,
+    pub inclination: f64,
+    pub longitude_asc_node: f64,
+    pub arg_of_periapsis: f64,
+    pub time_of_periapsis: f64, // Julian Day Number
+    pub mean_motion: f64,       // Degrees per second
+    pub mean_anomaly: f64,
+    pub true_anomaly: f64,
+    pub semimajor_axis: f64,
+    pub apoapsis_distance: f64,
+    pub period: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct HorizonsVectors {
+    pub target: String,
+    pub center: String,
+    pub frame: ReferenceFrame,
+    pub records: Vec<VectorRecord>,
+}
+
+// Position and velocity in KM and KM/s, in the export's frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VectorRecord {
+    pub jd_tdb: f64,
+    pub position: DVec3,
+    pub velocity: DVec3,
+}
+
+#[derive(Debug)]
+pub enum HorizonsError {
+    Io(std::io::Error),
+    MissingHeader(&'static str),
+    MissingElement(&'static str),
+    InvalidNumber(String),
+    NoRecords,
+}
+
+impl fmt::Display for HorizonsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HorizonsError::Io(err) => write!(f, "could not read elements file: {}", err),
+            HorizonsError::MissingHeader(header) => write!(f, "missing \"{}\" header line", header),
+            HorizonsError::MissingElement(key) => write!(f, "element {} missing from record", key),
+            HorizonsError::InvalidNumber(value) => write!(f, "could not parse \"{}\" as a number", value),
+            HorizonsError::NoRecords => write!(f, "no records between $$SOE and $$EOE"),
+        }
+    }
+}
+
+impl std::error::Error for HorizonsError {}
+
+impl From<std::io::Error> for HorizonsError {
+    fn from(err: std::io::Error) -> Self {
+        HorizonsError::Io(err)
+    }
+}
+
+// Seconds past J2000 for a Julian Day Number.
+pub fn jd_to_j2000_seconds(jd: f64) -> f64 {
+    (jd - J2000_JD) * SECONDS_PER_DAY
+}
+
+impl HorizonsElements {
+    pub fn parse(text: &str) -> Result<HorizonsElements, HorizonsError> {
+        let (target, center) = bodies(text)?;
+        let grav_parameter = match header_value(text, "Keplerian GM") {
+            Some(value) => Some(parse_number(value.split_whitespace().next().unwrap_or(""))?),
+            None => None,
+        };
+
+        // Distances are either KM or AU, times either seconds or days.
+        let units = header_value(text, "Output units").unwrap_or("KM-S");
+        let distance_scale = if units.starts_with("AU") { KM_PER_AU } else { 1. };
+        let time_scale = if units.contains("-D") { SECONDS_PER_DAY } else { 1. };
+
+        let records = records(text)?
+            .into_iter()
+            .map(|(jd, values)| ElementRecord::from_values(jd, &values, distance_scale, time_scale))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(HorizonsElements {
+            target,
+            center,
+            grav_parameter,
+            frame: frame(text),
+            records,
+        })
+    }
+
+    // Elements of the first record, relative to the center body.
+    pub fn orbital_parameters(&self) -> OrbitalParameters {
+        OrbitalParameters {
+            frame: self.frame,
+            ..self.records[0].orbital_parameters(self.grav_parameter)
+        }
+    }
+}
+
+impl ElementRecord {
+    fn from_values(
+        jd_tdb: f64,
+        values: &HashMap<String, f64>,
+        distance_scale: f64,
+        time_scale: f64,
+    ) -> Result<ElementRecord, HorizonsError> {
+        let get = |key: &'static str| {
+            values
+                .get(key)
+                .copied()
+                .ok_or(HorizonsError::MissingElement(key))
+        };
+
+        Ok(ElementRecord {
+            jd_tdb,
+            eccentricity: get("EC")?,
+            periapsis_distance: get("QR")? * distance_scale,
+            inclination: get("IN")?,
+            longitude_asc_node: get("OM")?,
+            arg_of_periapsis: get("W")?,
+            time_of_periapsis: get("Tp")?,
+            mean_motion: get("N")? / time_scale,
+            mean_anomaly: get("MA")?,
+            true_anomaly: get("TA")?,
+            semimajor_axis: get("A")? * distance_scale,
+            apoapsis_distance: get("AD")? * distance_scale,
+            period: get("PR")? * time_scale,
+        })
+    }
+
+    pub fn orbital_parameters(&self, grav_parameter: Option<f64>) -> OrbitalParameters {
+        // Without a "Keplerian GM" line, recover GM from the mean motion: n^2 a^3.
+        let mean_motion = self.mean_motion.to_radians();
+        let mu = grav_parameter
+            .unwrap_or_else(|| mean_motion * mean_motion * self.semimajor_axis.abs().powf(3.));
+
+        // Parabolic orbits have no semimajor axis, OrbitalParameters keeps the periapsis
+        // distance there instead. Open orbits have no period either.
+        let parabolic = (self.eccentricity - 1.).abs() <= 1e-9;
+        let semimajor_axis = if parabolic { self.periapsis_distance } else { self.semimajor_axis };
+        let period = if self.eccentricity < 1. { self.period } else { f64::INFINITY };
+
+        OrbitalParameters {
+            semimajor_axis,
+            longitude_asc_node: self.longitude_asc_node.to_radians(),
+            arg_of_periapsis: self.arg_of_periapsis.to_radians(),
+            inclination: self.inclination.to_radians(),
+            eccentricity: self.eccentricity,
+            mass_of_parent: mu / G,
+            grav_parameter: mu,
+            period,
+            rotational_period: 0.,
+            mean_anomaly_at_epoch: self.mean_anomaly.to_radians(),
+            epoch: jd_to_j2000_seconds(self.jd_tdb),
+            frame: ReferenceFrame::Ecliptic,
+            ..Default::default()
+        }
+    }
+}
+
+impl HorizonsVectors {
+    pub fn parse(text: &str) -> Result<HorizonsVectors, HorizonsError> {
+        let (target, center) = bodies(text)?;
+
+        // Velocities in AU-D come out per day.
+        let units = header_value(text, "Output units").unwrap_or("KM-S");
+        let distance_scale = if units.starts_with("AU") { KM_PER_AU } else { 1. };
+        let time_scale = if units.contains("-D") { SECONDS_PER_DAY } else { 1. };
+
+        let records = records(text)?
+            .into_iter()
+            .map(|(jd_tdb, values)| {
+                let get = |key: &'static str| {
+                    values
+                        .get(key)
+                        .copied()
+                        .ok_or(HorizonsError::MissingElement(key))
+                };
+
+                Ok(VectorRecord {
+                    jd_tdb,
+                    position: DVec3::new(get("X")?, get("Y")?, get("Z")?) * distance_scale,
+                    velocity: DVec3::new(get("VX")?, get("VY")?, get("VZ")?) * distance_scale
+                        / time_scale,
+                })
+            })
+            .collect::<Result<Vec<_>, HorizonsError>>()?;
+
+        Ok(HorizonsVectors {
+            target,
+            center,
+            frame: frame(text),
+            records,
+        })
+    }
+}
+
+fn bodies(text: &str) -> Result<(String, String), HorizonsError> {
+    let target = header_value(text, "Target body name")
+        .map(body_name)
+        .ok_or(HorizonsError::MissingHeader("Target body name"))?;
+    let center = header_value(text, "Center body name")
+        .map(body_name)
+        .ok_or(HorizonsError::MissingHeader("Center body name"))?;
+
+    Ok((target, center))
+}
+
+// Older exports only have "Reference frame : Ecliptic of J2000.0", newer ones say
+// "Reference frame : ICRF" and put the plane on its own line. The ecliptic is the default
+// either way.
+fn frame(text: &str) -> ReferenceFrame {
+    let plane = header_value(text, "Reference plane")
+        .or(header_value(text, "Reference frame"))
+        .unwrap_or("Ecliptic");
+
+    if plane.to_lowercase().contains("ecliptic") {
+        ReferenceFrame::Ecliptic
+    } else {
+        ReferenceFrame::Icrf
+    }
+}
+
+// A record's Julian Date and its "KEY= value" pairs.
+type RawRecord = (f64, HashMap<String, f64>);
+
+// Every record between $$SOE and $$EOE.
+fn records(text: &str) -> Result<Vec<RawRecord>, HorizonsError> {
+    let start = text.find("$$SOE").ok_or(HorizonsError::NoRecords)? + "$$SOE".len();
+    let end = text[start..].find("$$EOE").map_or(text.len(), |end| start + end);
+
+    let mut records = Vec::new();
+
+    for line in text[start..end].lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        // Every record starts with "<JDTDB> = A.D. <calendar date>".
+        if !line.contains("= A.D.") && !line.contains("= B.C.") {
+            if let Some((_, values)) = records.last_mut() {
+                parse_pairs(line, values)?;
+            }
+            continue;
+        }
+
+        let jd = line.split('=').next().unwrap_or("").trim();
+        records.push((parse_number(jd)?, HashMap::new()));
+    }
+
+    if records.is_empty() {
+        return Err(HorizonsError::NoRecords);
+    }
+
+    Ok(records)
+}
+
+fn header_value<'a>(text: &'a str, header: &str) -> Option<&'a str> {
+    text.lines()
+        .map(str::trim)
+        .find(|line| line.starts_with(header))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, value)| value.trim())
+}
+
+// "Moon (301)      {source: DE441}" -> "Moon"
+fn body_name(value: &str) -> String {
+    let end = value.find(['(', '{']).unwrap_or(value.len());
+    value[..end].trim().to_string()
+}
+
+fn parse_number(value: &str) -> Result<f64, HorizonsError> {
+    value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| HorizonsError::InvalidNumber(value.trim().to_string()))
+}
+
+// "EC= 6.47E-02 QR= 3.56E+05 IN= 5.24E+00" -> {EC: .., QR: .., IN: ..}
+fn parse_pairs(line: &str, values: &mut HashMap<String, f64>) -> Result<(), HorizonsError> {
+    let mut segments = line.split('=');
+    let Some(first) = segments.next() else {
+        return Ok(());
+    };
+    let mut key = first.trim().to_string();
+
+    for segment in segments {
+        let mut tokens = segment.split_whitespace();
+        let value = tokens.next().unwrap_or("");
+        values.insert(key, parse_number(value)?);
+        key = tokens.collect::<Vec<_>>().join(" ");
+    }
+
+    Ok(())
+}
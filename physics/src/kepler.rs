@@ -0,0 +1,191 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+// Solvers for Kepler's equation. Each one starts from a guess that is good for any
+// eccentricity, refines it with Halley's method, and falls back to bisection whenever a
+// step would leave the interval the root is known to be in.
+
+const TOLERANCE: f64 = 1e-14;
+const MAX_ITERATIONS: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeplerError {
+    InvalidInput { mean_anomaly: f64, eccentricity: f64 },
+    NoConvergence { mean_anomaly: f64, eccentricity: f64, iterations: u32 },
+}
+
+impl fmt::Display for KeplerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeplerError::InvalidInput { mean_anomaly, eccentricity } => write!(
+                f,
+                "can't solve Kepler's equation for M = {}, e = {}",
+                mean_anomaly, eccentricity
+            ),
+            KeplerError::NoConvergence { mean_anomaly, eccentricity, iterations } => write!(
+                f,
+                "Kepler's equation didn't converge after {} iterations for M = {}, e = {}",
+                iterations, mean_anomaly, eccentricity
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeplerError {}
+
+// Solves M = E - e sin E for the eccentric anomaly E, 0 <= e < 1.
+pub fn eccentric_anomaly(mean_anomaly: f64, eccentricity: f64) -> Result<f64, KeplerError> {
+    if !mean_anomaly.is_finite() || !(0. ..1.).contains(&eccentricity) {
+        return Err(KeplerError::InvalidInput { mean_anomaly, eccentricity });
+    }
+
+    // Solve for M in [-pi, pi] and add the whole turns back afterwards.
+    let turns = ((mean_anomaly + PI) / (2. * PI)).floor();
+    let m = mean_anomaly - turns * 2. * PI;
+    let e = eccentricity;
+
+    // |E - M| = e |sin E| <= e, and E - e sin E - M increases with E.
+    let lower = m - e;
+    let upper = m + e;
+    // Danby's starting guess.
+    let guess = m + 0.85 * e * m.sin().signum();
+
+    let solved = halley_bisection(
+        |x| (x - e * x.sin() - m, 1. - e * x.cos(), e * x.sin()),
+        guess,
+        lower,
+        upper,
+    )
+    .ok_or(KeplerError::NoConvergence {
+        mean_anomaly,
+        eccentricity,
+        iterations: MAX_ITERATIONS,
+    })?;
+
+    Ok(solved + turns * 2. * PI)
+}
+
+// Solves M = e sinh H - H for the hyperbolic anomaly H, e > 1.
+pub fn hyperbolic_anomaly(mean_anomaly: f64, eccentricity: f64) -> Result<f64, KeplerError> {
+    if !mean_anomaly.is_finite() || eccentricity.is_nan() || eccentricity <= 1. {
+        return Err(KeplerError::InvalidInput { mean_anomaly, eccentricity });
+    }
+
+    // Solve for |M| and mirror, the equation is odd in H.
+    let m = mean_anomaly.abs();
+    let e = eccentricity;
+
+    // e sinh H = M + H >= M, and (e - 1) sinh H <= e sinh H - H = M.
+    let lower = (m / e).asinh();
+    let upper = (m / (e - 1.)).asinh();
+    let guess = (2. * m / e + 1.8).ln().clamp(lower, upper);
+
+    let solved = halley_bisection(
+        |x| (e * x.sinh() - x - m, e * x.cosh() - 1., e * x.sinh()),
+        guess,
+        lower,
+        upper,
+    )
+    .ok_or(KeplerError::NoConvergence {
+        mean_anomaly,
+        eccentricity,
+        iterations: MAX_ITERATIONS,
+    })?;
+
+    Ok(solved.copysign(mean_anomaly))
+}
+
+// Barker's equation, M = D + D^3 / 3, solved in closed form for D = tan(nu / 2).
+pub fn parabolic_anomaly(mean_anomaly: f64) -> Result<f64, KeplerError> {
+    if !mean_anomaly.is_finite() {
+        return Err(KeplerError::InvalidInput { mean_anomaly, eccentricity: 1. });
+    }
+
+    let a = 1.5 * mean_anomaly;
+    let b = (a + (a * a + 1.).sqrt()).cbrt();
+    Ok(b - 1. / b)
+}
+
+// Finds the root of an increasing function inside [lower, upper]. `f` returns the value
+// and its first and second derivatives.
+fn halley_bisection(f: impl Fn(f64) -> (f64, f64, f64), guess: f64, mut lower: f64, mut upper: f64) -> Option<f64> {
+    let mut x = guess.clamp(lower, upper);
+
+    for _ in 0..MAX_ITERATIONS {
+        let (value, d1, d2) = f(x);
+
+        if value == 0. {
+            return Some(x);
+        }
+
+        if value < 0. {
+            lower = x;
+        } else {
+            upper = x;
+        }
+
+        let denominator = 2. * d1 * d1 - value * d2;
+        let halley = if denominator != 0. {
+            x - 2. * value * d1 / denominator
+        } else {
+            f64::NAN
+        };
+
+        // Halley steps outside the bracket (or NaN) are replaced by bisection.
+        let next = if halley > lower && halley < upper {
+            halley
+        } else {
+            0.5 * (lower + upper)
+        };
+
+        if (next - x).abs() <= TOLERANCE * (1. + x.abs()) || upper - lower <= TOLERANCE * (1. + x.abs()) {
+            return Some(next);
+        }
+
+        x = next;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eccentric_anomaly_solves_keplers_equation() {
+        for &e in &[0., 0.1, 0.5, 0.9, 0.99, 0.999999] {
+            for i in 0..36 {
+                let m = -PI + i as f64 * PI / 18.;
+                let big_e = eccentric_anomaly(m, e).unwrap();
+                assert!((big_e - e * big_e.sin() - m).abs() < 1e-12, "M = {}, e = {}", m, e);
+            }
+        }
+    }
+
+    #[test]
+    fn hyperbolic_anomaly_solves_keplers_equation() {
+        for &e in &[1.000001, 1.1, 2., 10.] {
+            for &m in &[-100., -1., -1e-3, 0., 1e-3, 1., 100.] {
+                let h = hyperbolic_anomaly(m, e).unwrap();
+                let residual = e * h.sinh() - h - m;
+                assert!(residual.abs() < 1e-10 * (1. + m.abs()), "M = {}, e = {}", m, e);
+            }
+        }
+    }
+
+    #[test]
+    fn parabolic_anomaly_solves_barkers_equation() {
+        for &m in &[-50., -1., 0., 0.5, 1000.] {
+            let d = parabolic_anomaly(m).unwrap();
+            assert!((d + d * d * d / 3. - m).abs() < 1e-10 * (1. + m.abs()));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(eccentric_anomaly(1., 1.5).is_err());
+        assert!(eccentric_anomaly(f64::NAN, 0.5).is_err());
+        assert!(hyperbolic_anomaly(1., 0.5).is_err());
+    }
+}
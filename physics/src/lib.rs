@@ -0,0 +1,18 @@
+// Orbital mechanics without a window: elements and their propagation, Kepler's equation,
+// time scales, reference frames, the lunar theory and reading Horizons elements.
+// Everything is in KM, seconds and radians, times are seconds past J2000.
+//
+// The app keeps these types on entities, the `bevy` feature derives what it needs for that.
+
+pub mod earth_orientation;
+pub mod elements;
+pub mod frames;
+pub mod horizons;
+pub mod kepler;
+pub mod lunar_theory;
+pub mod time_scale;
+
+pub use elements::OrbitalParameters;
+pub use frames::ReferenceFrame;
+pub use kepler::KeplerError;
+pub use time_scale::TimeScale;
@@ -0,0 +1,258 @@
+use glam::{DQuat, DVec3};
+
+use crate::earth_orientation::{mean_obliquity, precession};
+
+// The Moon's geocentric position from the truncated ELP-2000/82 series in Meeus,
+// Astronomical Algorithms ch. 47, good to about 10" in longitude and 4" in latitude. A
+// single ellipse is off by thousands of km within a month, this is close enough for
+// eclipses and moonrise times to come out right.
+
+const SECONDS_PER_CENTURY: f64 = 36525. * 86400.;
+// Mean distance the series in `LONGITUDE_DISTANCE_TERMS` is added to, KM.
+const MEAN_DISTANCE: f64 = 385000.56;
+
+// Table 47.A: multiples of D, M, M', F, then the sine coefficient for longitude (1e-6
+// degrees) and the cosine coefficient for distance (1e-3 KM).
+#[rustfmt::skip]
+const LONGITUDE_DISTANCE_TERMS: [(i8, i8, i8, i8, f64, f64); 60] = [
+    (0, 0, 1, 0, 6288774., -20905355.),
+    (2, 0, -1, 0, 1274027., -3699111.),
+    (2, 0, 0, 0, 658314., -2955968.),
+    (0, 0, 2, 0, 213618., -569925.),
+    (0, 1, 0, 0, -185116., 48888.),
+    (0, 0, 0, 2, -114332., -3149.),
+    (2, 0, -2, 0, 58793., 246158.),
+    (2, -1, -1, 0, 57066., -152138.),
+    (2, 0, 1, 0, 53322., -170733.),
+    (2, -1, 0, 0, 45758., -204586.),
+    (0, 1, -1, 0, -40923., -129620.),
+    (1, 0, 0, 0, -34720., 108743.),
+    (0, 1, 1, 0, -30383., 104755.),
+    (2, 0, 0, -2, 15327., 10321.),
+    (0, 0, 1, 2, -12528., 0.),
+    (0, 0, 1, -2, 10980., 79661.),
+    (4, 0, -1, 0, 10675., -34782.),
+    (0, 0, 3, 0, 10034., -23210.),
+    (4, 0, -2, 0, 8548., -21636.),
+    (2, 1, -1, 0, -7888., 24208.),
+    (2, 1, 0, 0, -6766., 30824.),
+    (1, 0, -1, 0, -5163., -8379.),
+    (1, 1, 0, 0, 4987., -16675.),
+    (2, -1, 1, 0, 4036., -12831.),
+    (2, 0, 2, 0, 3994., -10445.),
+    (4, 0, 0, 0, 3861., -11650.),
+    (2, 0, -3, 0, 3665., 14403.),
+    (0, 1, -2, 0, -2689., -7003.),
+    (2, 0, -1, 2, -2602., 0.),
+    (2, -1, -2, 0, 2390., 10056.),
+    (1, 0, 1, 0, -2348., 6322.),
+    (2, -2, 0, 0, 2236., -9884.),
+    (0, 1, 2, 0, -2120., 5751.),
+    (0, 2, 0, 0, -2069., 0.),
+    (2, -2, -1, 0, 2048., -4950.),
+    (2, 0, 1, -2, -1773., 4130.),
+    (2, 0, 0, 2, -1595., 0.),
+    (4, -1, -1, 0, 1215., -3958.),
+    (0, 0, 2, 2, -1110., 0.),
+    (3, 0, -1, 0, -892., 3258.),
+    (2, 1, 1, 0, -810., 2616.),
+    (4, -1, -2, 0, 759., -1897.),
+    (0, 2, -1, 0, -713., -2117.),
+    (2, 2, -1, 0, -700., 2354.),
+    (2, 1, -2, 0, 691., 0.),
+    (2, -1, 0, -2, 596., 0.),
+    (4, 0, 1, 0, 549., -1423.),
+    (0, 0, 4, 0, 537., -1117.),
+    (4, -1, 0, 0, 520., -1571.),
+    (1, 0, -2, 0, -487., -1739.),
+    (2, 1, 0, -2, -399., 0.),
+    (0, 0, 2, -2, -381., -4421.),
+    (1, 1, 1, 0, 351., 0.),
+    (3, 0, -2, 0, -340., 0.),
+    (4, 0, -3, 0, 330., 0.),
+    (2, -1, 2, 0, 327., 0.),
+    (0, 2, 1, 0, -323., 1165.),
+    (1, 1, -1, 0, 299., 0.),
+    (2, 0, 3, 0, 294., 0.),
+    (2, 0, -1, -2, 0., 8752.),
+];
+
+// Table 47.B: multiples of D, M, M', F, then the sine coefficient for latitude (1e-6
+// degrees).
+#[rustfmt::skip]
+const LATITUDE_TERMS: [(i8, i8, i8, i8, f64); 60] = [
+    (0, 0, 0, 1, 5128122.),
+    (0, 0, 1, 1, 280602.),
+    (0, 0, 1, -1, 277693.),
+    (2, 0, 0, -1, 173237.),
+    (2, 0, -1, 1, 55413.),
+    (2, 0, -1, -1, 46271.),
+    (2, 0, 0, 1, 32573.),
+    (0, 0, 2, 1, 17198.),
+    (2, 0, 1, -1, 9266.),
+    (0, 0, 2, -1, 8822.),
+    (2, -1, 0, -1, 8216.),
+    (2, 0, -2, -1, 4324.),
+    (2, 0, 1, 1, 4200.),
+    (2, 1, 0, -1, -3359.),
+    (2, -1, -1, 1, 2463.),
+    (2, -1, 0, 1, 2211.),
+    (2, -1, -1, -1, 2065.),
+    (0, 1, -1, -1, -1870.),
+    (4, 0, -1, -1, 1828.),
+    (0, 1, 0, 1, -1794.),
+    (0, 0, 0, 3, -1749.),
+    (0, 1, -1, 1, -1565.),
+    (1, 0, 0, 1, -1491.),
+    (0, 1, 1, 1, -1475.),
+    (0, 1, 1, -1, -1410.),
+    (0, 1, 0, -1, -1344.),
+    (1, 0, 0, -1, -1335.),
+    (0, 0, 3, 1, 1107.),
+    (4, 0, 0, -1, 1021.),
+    (4, 0, -1, 1, 833.),
+    (0, 0, 1, -3, 777.),
+    (4, 0, -2, 1, 671.),
+    (2, 0, 0, -3, 607.),
+    (2, 0, 2, -1, 596.),
+    (2, -1, 1, -1, 491.),
+    (2, 0, -2, 1, -451.),
+    (0, 0, 3, -1, 439.),
+    (2, 0, 2, 1, 422.),
+    (2, 0, -3, -1, 421.),
+    (2, 1, -1, 1, -366.),
+    (2, 1, 0, 1, -351.),
+    (4, 0, 0, 1, 331.),
+    (2, -1, 1, 1, 315.),
+    (2, -2, 0, -1, 302.),
+    (0, 0, 1, 3, -283.),
+    (2, 1, 1, -1, -229.),
+    (1, 1, 0, -1, 223.),
+    (1, 1, 0, 1, 223.),
+    (0, 1, -2, -1, -220.),
+    (2, 1, -1, -1, -220.),
+    (1, 0, 1, 1, -185.),
+    (2, -1, -2, -1, 181.),
+    (0, 1, 2, 1, -177.),
+    (4, 0, -2, -1, 176.),
+    (4, -1, -1, -1, 166.),
+    (1, 0, 1, -1, -164.),
+    (4, 0, 1, -1, 132.),
+    (1, 0, -1, -1, -119.),
+    (4, -1, 0, -1, 115.),
+    (2, -2, 0, 1, 107.),
+];
+
+// Geocentric ecliptic longitude and latitude (radians) and distance (KM) of the Moon,
+// referred to the mean ecliptic and equinox of date, `t` TDB seconds past J2000.
+pub fn geocentric_ecliptic_of_date(t: f64) -> (f64, f64, f64) {
+    let c = t / SECONDS_PER_CENTURY;
+    let (c2, c3, c4) = (c * c, c * c * c, c * c * c * c);
+
+    // Mean longitude, elongation, the Sun's and Moon's mean anomalies and the argument
+    // of latitude, in degrees.
+    let mean_longitude =
+        218.3164477 + 481267.88123421 * c - 0.0015786 * c2 + c3 / 538841. - c4 / 65194000.;
+    let elongation =
+        297.8501921 + 445267.1114034 * c - 0.0018819 * c2 + c3 / 545868. - c4 / 113065000.;
+    let sun_anomaly = 357.5291092 + 35999.0502909 * c - 0.0001536 * c2 + c3 / 24490000.;
+    let moon_anomaly =
+        134.9633964 + 477198.8675055 * c + 0.0087414 * c2 + c3 / 69699. - c4 / 14712000.;
+    let latitude_argument =
+        93.2720950 + 483202.0175233 * c - 0.0036539 * c2 - c3 / 3526000. + c4 / 863310000.;
+
+    // Venus, Jupiter and the flattening of the Earth.
+    let a1 = (119.75 + 131.849 * c).to_radians();
+    let a2 = (53.09 + 479264.290 * c).to_radians();
+    let a3 = (313.45 + 481266.484 * c).to_radians();
+
+    // Terms with the Sun's mean anomaly shrink with the eccentricity of Earth's orbit.
+    let eccentricity = 1. - 0.002516 * c - 0.0000074 * c2;
+
+    let l = mean_longitude.to_radians();
+    let d = elongation.to_radians();
+    let m = sun_anomaly.to_radians();
+    let mp = moon_anomaly.to_radians();
+    let f = latitude_argument.to_radians();
+
+    let argument = |dm: i8, mm: i8, mpm: i8, fm: i8| {
+        let scale = eccentricity.powi(mm.unsigned_abs() as i32);
+        let angle = dm as f64 * d + mm as f64 * m + mpm as f64 * mp + fm as f64 * f;
+        (angle, scale)
+    };
+
+    let mut sum_longitude = 0.;
+    let mut sum_distance = 0.;
+    for (dm, mm, mpm, fm, longitude, distance) in LONGITUDE_DISTANCE_TERMS {
+        let (angle, scale) = argument(dm, mm, mpm, fm);
+        sum_longitude += longitude * scale * angle.sin();
+        sum_distance += distance * scale * angle.cos();
+    }
+
+    let mut sum_latitude = 0.;
+    for (dm, mm, mpm, fm, latitude) in LATITUDE_TERMS {
+        let (angle, scale) = argument(dm, mm, mpm, fm);
+        sum_latitude += latitude * scale * angle.sin();
+    }
+
+    sum_longitude += 3958. * a1.sin() + 1962. * (l - f).sin() + 318. * a2.sin();
+    sum_latitude += -2235. * l.sin()
+        + 382. * a3.sin()
+        + 175. * (a1 - f).sin()
+        + 175. * (a1 + f).sin()
+        + 127. * (l - mp).sin()
+        - 115. * (l + mp).sin();
+
+    let longitude = (mean_longitude + sum_longitude / 1e6).to_radians();
+    let latitude = (sum_latitude / 1e6).to_radians();
+    let distance = MEAN_DISTANCE + sum_distance / 1000.;
+
+    (longitude.rem_euclid(2. * std::f64::consts::PI), latitude, distance)
+}
+
+// Geocentric position of the Moon in KM, ICRF.
+pub fn geocentric_position(t: f64) -> DVec3 {
+    let (longitude, latitude, distance) = geocentric_ecliptic_of_date(t);
+    let of_date = DVec3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+    ) * distance;
+
+    // Ecliptic of date to the equator of date, then back to J2000.
+    precession(t).inverse() * (DQuat::from_rotation_x(mean_obliquity(t)) * of_date)
+}
+
+// Geocentric position (KM) and velocity (KM/s) of the Moon, ICRF. The Moon moves about
+// half a degree an hour, a central difference over a minute is plenty for the velocity.
+pub fn geocentric_state(t: f64) -> (DVec3, DVec3) {
+    let position = geocentric_position(t);
+    let velocity = (geocentric_position(t + 60.) - geocentric_position(t - 60.)) / 120.;
+
+    (position, velocity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time_scale::J2000_JD;
+
+    #[test]
+    fn matches_meeus_example_47_a() {
+        // 1992 April 12, 0h TD.
+        let t = (2448724.5 - J2000_JD) * 86400.;
+        let (longitude, latitude, distance) = geocentric_ecliptic_of_date(t);
+
+        assert!((longitude.to_degrees() - 133.162655).abs() < 1e-6);
+        assert!((latitude.to_degrees() + 3.229126).abs() < 1e-6);
+        assert!((distance - 368409.7).abs() < 0.1);
+    }
+
+    #[test]
+    fn velocity_is_about_a_kilometre_a_second() {
+        let (position, velocity) = geocentric_state(7.5e8);
+        assert!((356000. ..407000.).contains(&position.length()));
+        assert!((0.95..1.1).contains(&velocity.length()));
+        assert!(position.normalize().dot(velocity.normalize()).abs() < 0.1);
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+// Time scales and the conversions between them. Times are seconds past J2000 (JD 2451545.0,
+// 2000-01-01 12:00) read on the given scale's own clock, so the same instant is a slightly
+// different number in each scale.
+//
+// UTC seconds are counted the way Julian Dates are, every day has 86400 of them and a leap
+// second repeats the last second of the day. Before 1972 UTC is taken to be TAI - 10 s.
+
+pub const J2000_JD: f64 = 2451545.0;
+
+const SECONDS_PER_DAY: f64 = 86400.;
+const MJD_OFFSET: f64 = 2400000.5;
+const TT_MINUS_TAI: f64 = 32.184;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_reflect::Reflect))]
+pub enum TimeScale {
+    Utc,
+    Tai,
+    Tt,
+    #[default]
+    Tdb,
+}
+
+impl TimeScale {
+    pub fn label(self) -> &'static str {
+        match self {
+            TimeScale::Utc => "UTC",
+            TimeScale::Tai => "TAI",
+            TimeScale::Tt => "TT",
+            TimeScale::Tdb => "TDB",
+        }
+    }
+}
+
+// TAI - UTC in seconds from the start of each UTC day (MJD) onwards, from the IERS
+// Bulletin C. Needs a new line whenever a leap second is announced.
+const LEAP_SECONDS: [(f64, f64); 28] = [
+    (41317., 10.), // 1972-01-01
+    (41499., 11.), // 1972-07-01
+    (41683., 12.), // 1973-01-01
+    (42048., 13.), // 1974-01-01
+    (42413., 14.), // 1975-01-01
+    (42778., 15.), // 1976-01-01
+    (43144., 16.), // 1977-01-01
+    (43509., 17.), // 1978-01-01
+    (43874., 18.), // 1979-01-01
+    (44239., 19.), // 1980-01-01
+    (44786., 20.), // 1981-07-01
+    (45151., 21.), // 1982-07-01
+    (45516., 22.), // 1983-07-01
+    (46247., 23.), // 1985-07-01
+    (47161., 24.), // 1988-01-01
+    (47892., 25.), // 1990-01-01
+    (48257., 26.), // 1991-01-01
+    (48804., 27.), // 1992-07-01
+    (49169., 28.), // 1993-07-01
+    (49534., 29.), // 1994-07-01
+    (50083., 30.), // 1996-01-01
+    (50630., 31.), // 1997-07-01
+    (51179., 32.), // 1999-01-01
+    (53736., 33.), // 2006-01-01
+    (54832., 34.), // 2009-01-01
+    (56109., 35.), // 2012-07-01
+    (57204., 36.), // 2015-07-01
+    (57754., 37.), // 2017-01-01
+];
+
+fn mjd_to_j2000_seconds(mjd: f64) -> f64 {
+    (mjd + MJD_OFFSET - J2000_JD) * SECONDS_PER_DAY
+}
+
+// TAI - UTC at a UTC time.
+pub fn leap_seconds(utc: f64) -> f64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(mjd, _)| utc >= mjd_to_j2000_seconds(*mjd))
+        .map_or(LEAP_SECONDS[0].1, |(_, offset)| *offset)
+}
+
+fn utc_to_tai(utc: f64) -> f64 {
+    utc + leap_seconds(utc)
+}
+
+fn tai_to_utc(tai: f64) -> f64 {
+    // A new offset starts once TAI reaches the UTC midnight it applies from.
+    let offset = LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(mjd, offset)| tai >= mjd_to_j2000_seconds(*mjd) + offset)
+        .map_or(LEAP_SECONDS[0].1, |(_, offset)| *offset);
+
+    tai - offset
+}
+
+// TDB - TT, the periodic terms from the Earth's orbit, good to about 30 microseconds.
+fn tdb_minus_tt(tt: f64) -> f64 {
+    let g = (357.53 + 0.98560028 * tt / SECONDS_PER_DAY).to_radians();
+    0.001657 * g.sin() + 0.000014 * (2. * g).sin()
+}
+
+// Converts seconds past J2000 from one scale to another, going through TAI.
+pub fn convert(seconds: f64, from: TimeScale, to: TimeScale) -> f64 {
+    if from == to {
+        return seconds;
+    }
+
+    let tai = match from {
+        TimeScale::Utc => utc_to_tai(seconds),
+        TimeScale::Tai => seconds,
+        TimeScale::Tt => seconds - TT_MINUS_TAI,
+        // The difference moves by nanoseconds over the 1.7 ms it's off by, so using TDB
+        // in place of TT for the argument is fine.
+        TimeScale::Tdb => seconds - tdb_minus_tt(seconds) - TT_MINUS_TAI,
+    };
+
+    match to {
+        TimeScale::Utc => tai_to_utc(tai),
+        TimeScale::Tai => tai,
+        TimeScale::Tt => tai + TT_MINUS_TAI,
+        TimeScale::Tdb => {
+            let tt = tai + TT_MINUS_TAI;
+            tt + tdb_minus_tt(tt)
+        }
+    }
+}
+
+pub fn julian_date(seconds: f64) -> f64 {
+    J2000_JD + seconds / SECONDS_PER_DAY
+}
+
+pub fn modified_julian_date(seconds: f64) -> f64 {
+    julian_date(seconds) - MJD_OFFSET
+}
+
+// Calendar date and time on the scale's own clock, e.g. "2000-01-01T11:58:55.816 UTC".
+// Clocks past the years chrono can name are printed as a Julian Date instead.
+pub fn iso_string(seconds: f64, scale: TimeScale) -> String {
+    match calendar_date(seconds) {
+        Some(date) => format!("{} {}", date.format("%Y-%m-%dT%H:%M:%S%.3f"), scale.label()),
+        None => format!("JD {:.5} {}", julian_date(seconds), scale.label()),
+    }
+}
+
+// None outside the roughly ±262000 years chrono covers.
+pub fn calendar_date(seconds: f64) -> Option<NaiveDateTime> {
+    let milliseconds = (seconds * 1000.).round();
+    // Keeps the cast and `Duration::milliseconds` clear of their own limits.
+    if !milliseconds.is_finite() || milliseconds.abs() > 1e16 {
+        return None;
+    }
+
+    j2000().checked_add_signed(Duration::milliseconds(milliseconds as i64))
+}
+
+fn j2000() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2000, 1, 1)
+        .and_then(|date| date.and_hms_opt(12, 0, 0))
+        .unwrap()
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateParseError {
+    Unreadable(String),
+    // Readable, but too far off for `calendar_date` to name.
+    OutOfRange(String),
+}
+
+impl std::fmt::Display for DateParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateParseError::Unreadable(text) => write!(
+                f,
+                "can't read \"{}\" as a date, expected \"now\", an ISO-8601 date or \"JD <number>\"",
+                text
+            ),
+            DateParseError::OutOfRange(text) => {
+                write!(f, "\"{}\" is outside the calendar the clock can show", text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DateParseError {}
+
+// Reads a date typed by a person and returns it as seconds past J2000 in `scale`.
+//
+//   now                              the system clock
+//   2024-04-08T18:17:00Z             ISO-8601, UTC unless it has an offset
+//   2024-04-08 18:17:00 TDB          or a trailing UTC, TAI, TT or TDB
+//   2024-04-08                       midnight
+//   JD 2460409.26 / 2460409.26       Julian Date, TDB unless a scale follows
+pub fn parse_date(text: &str, scale: TimeScale) -> Result<f64, DateParseError> {
+    let seconds = parse_seconds(text, scale)?;
+
+    // A day either side leaves room for the other scales' offsets.
+    if calendar_date(seconds - SECONDS_PER_DAY).is_none()
+        || calendar_date(seconds + SECONDS_PER_DAY).is_none()
+    {
+        return Err(DateParseError::OutOfRange(text.to_string()));
+    }
+
+    Ok(seconds)
+}
+
+fn parse_seconds(text: &str, scale: TimeScale) -> Result<f64, DateParseError> {
+    let error = || DateParseError::Unreadable(text.to_string());
+    let trimmed = text.trim();
+
+    if trimmed.eq_ignore_ascii_case("now") {
+        let now = chrono::Utc::now().naive_utc();
+        return Ok(convert(calendar_seconds(now), TimeScale::Utc, scale));
+    }
+
+    // An optional time scale at the end.
+    let (body, given_scale) = match trimmed.rsplit_once(' ') {
+        Some((body, suffix)) => match parse_scale(suffix) {
+            Some(given) => (body.trim(), Some(given)),
+            None => (trimmed, None),
+        },
+        None => (trimmed, None),
+    };
+
+    let julian = body
+        .strip_prefix("JD")
+        .or(body.strip_prefix("jd"))
+        .unwrap_or(body)
+        .trim();
+    if let Ok(jd) = julian.parse::<f64>() {
+        let seconds = (jd - J2000_JD) * SECONDS_PER_DAY;
+        return Ok(convert(seconds, given_scale.unwrap_or(TimeScale::Tdb), scale));
+    }
+
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(body) {
+        let utc = calendar_seconds(date.naive_utc());
+        return Ok(convert(utc, TimeScale::Utc, scale));
+    }
+
+    let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(body, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(body, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(error)?;
+
+    Ok(convert(
+        calendar_seconds(naive),
+        given_scale.unwrap_or(TimeScale::Utc),
+        scale,
+    ))
+}
+
+fn parse_scale(text: &str) -> Option<TimeScale> {
+    match text.to_uppercase().as_str() {
+        "UTC" | "Z" => Some(TimeScale::Utc),
+        "TAI" => Some(TimeScale::Tai),
+        "TT" => Some(TimeScale::Tt),
+        "TDB" => Some(TimeScale::Tdb),
+        _ => None,
+    }
+}
+
+// Seconds past J2000 for a calendar date and time, the inverse of `calendar_date`.
+pub fn calendar_seconds(date: NaiveDateTime) -> f64 {
+    (date - j2000()).num_milliseconds() as f64 / 1000.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALES: [TimeScale; 4] = [TimeScale::Utc, TimeScale::Tai, TimeScale::Tt, TimeScale::Tdb];
+
+    #[test]
+    fn conversions_round_trip() {
+        for &t in &[-1e9, -86400., 0., 3.1e8, 7.5e8, 2e9] {
+            for &from in &SCALES {
+                for &to in &SCALES {
+                    let back = convert(convert(t, from, to), to, from);
+                    assert!((back - t).abs() < 1e-6, "{:?} -> {:?} at {}", from, to, t);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn offsets_at_j2000() {
+        // TT - UTC was 64.184 s, TDB - TT is under 2 ms.
+        assert!((convert(0., TimeScale::Tt, TimeScale::Utc) + 64.184).abs() < 1e-9);
+        assert!((convert(0., TimeScale::Tt, TimeScale::Tai) + 32.184).abs() < 1e-9);
+        assert!(convert(0., TimeScale::Tt, TimeScale::Tdb).abs() < 0.002);
+    }
+
+    #[test]
+    fn leap_seconds_follow_the_table() {
+        let date = |text: &str| parse_date(text, TimeScale::Utc).unwrap();
+        assert_eq!(leap_seconds(date("1999-06-01")), 32.);
+        assert_eq!(leap_seconds(date("2016-12-31T23:59:59")), 36.);
+        assert_eq!(leap_seconds(date("2017-01-01")), 37.);
+    }
+
+    #[test]
+    fn dates_parse_and_print() {
+        assert_eq!(calendar_date(0.).unwrap().to_string(), "2000-01-01 12:00:00");
+        assert_eq!(parse_date("JD 2451545.0 TT", TimeScale::Tt).unwrap(), 0.);
+        assert!((julian_date(parse_date("2000-01-01T12:00:00 TDB", TimeScale::Tdb).unwrap()) - J2000_JD).abs() < 1e-12);
+        assert_eq!(iso_string(0., TimeScale::Utc), "2000-01-01T12:00:00.000 UTC");
+        assert!((modified_julian_date(0.) - 51544.5).abs() < 1e-9);
+        assert!(parse_date("not a date", TimeScale::Utc).is_err());
+    }
+
+    #[test]
+    fn far_off_clocks_print_as_julian_dates() {
+        let seconds = (1e12 - J2000_JD) * SECONDS_PER_DAY;
+        assert_eq!(calendar_date(seconds), None);
+        assert_eq!(calendar_date(f64::NAN), None);
+        assert_eq!(iso_string(seconds, TimeScale::Tdb), "JD 1000000000000.00000 TDB");
+        assert!(iso_string(f64::INFINITY, TimeScale::Utc).starts_with("JD inf"));
+    }
+
+    #[test]
+    fn dates_past_the_calendar_are_rejected() {
+        let out_of_range = |text: &str| {
+            parse_date(text, TimeScale::Tdb) == Err(DateParseError::OutOfRange(text.to_string()))
+        };
+        assert!(out_of_range("JD 1e12"));
+        assert!(out_of_range("JD -1e12"));
+        assert!(out_of_range("JD NaN"));
+        assert!(out_of_range("inf"));
+        assert!(parse_date("JD 5e7", TimeScale::Tdb).is_ok());
+        assert!(calendar_date(parse_date("JD 5e7", TimeScale::Tdb).unwrap()).is_some());
+    }
+}
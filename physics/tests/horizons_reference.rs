@@ -0,0 +1,509 @@
+use glam::DVec3;
+use std::f64::consts::PI;
+
+use orbiter_physics::elements::{lunar_orbit, OrbitalParameters};
+use orbiter_physics::frames::ecliptic_to_icrf;
+use orbiter_physics::horizons::{
+    jd_to_j2000_seconds, ElementRecord, HorizonsElements, HorizonsVectors,
+};
+use orbiter_physics::lunar_theory::geocentric_state;
+
+// Checks against JPL Horizons, in three parts.
+//
+// Consistency: every ELEMENTS export in tests/fixtures/ is read, and each record's own
+// numbers are the reference. Horizons gives the mean and true anomaly, time of periapsis,
+// periapsis and apoapsis distance and period for the same osculating ellipse, so the
+// elements have to reproduce all of them. This catches unit and convention mistakes, not
+// propagation errors, since everything is at the record's own epoch.
+//
+// Propagation: the two-body math carried over several orbits, against an independent
+// numerical integration of the same two-body problem, and the lunar theory against the
+// DE441 state in the Moon's export.
+//
+// Ephemeris: every VECTORS export in tests/fixtures/ is the truth at its epochs, and both
+// lunar models are held to it. Export from Horizons with target 301, center 500@399,
+// VECTORS table, KM-S units, ecliptic or ICRF reference plane, a few months at a daily or
+// so step starting 2000-01-01, and save the text file next to the others.
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+// Horizons prints 16 significant digits, these leave room for the rounding and for
+// propagating over a fraction of an orbit.
+const ANGLE_TOLERANCE: f64 = 1e-9; // Radians
+const DISTANCE_TOLERANCE: f64 = 1e-9; // Relative
+const SPEED_TOLERANCE: f64 = 1e-9; // Relative
+
+// The lunar theory is good to about 10" in longitude and a few KM in distance, the
+// osculating ellipse only for a few days before the Sun pulls the Moon off it.
+const MEEUS_POSITION_TOLERANCE: f64 = 30.; // KM
+const MEEUS_DISTANCE_TOLERANCE: f64 = 15.; // KM
+const ELLIPSE_TOLERANCES: [(f64, f64, f64); 2] = [
+    // Up to so many days after the elements' epoch, position and distance in KM.
+    (1., 400., 100.),
+    (3., 1000., 600.),
+];
+
+// Name and text of every export whose "Output type" line contains `kind`.
+fn exports(kind: &str) -> Vec<(String, String)> {
+    let mut paths: Vec<_> = std::fs::read_dir(FIXTURES)
+        .expect("fixtures folder")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let text = std::fs::read_to_string(&path).unwrap();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            (name, text)
+        })
+        .filter(|(_, text)| {
+            text.lines()
+                .find(|line| line.trim_start().starts_with("Output type"))
+                .is_some_and(|line| line.contains(kind))
+        })
+        .collect()
+}
+
+fn fixtures() -> Vec<(String, HorizonsElements)> {
+    let fixtures: Vec<_> = exports("osculating elements")
+        .into_iter()
+        .map(|(name, text)| {
+            let elements = HorizonsElements::parse(&text)
+                .unwrap_or_else(|err| panic!("{}: {}", name, err));
+            (name, elements)
+        })
+        .collect();
+
+    assert!(!fixtures.is_empty(), "no ELEMENTS exports in {}", FIXTURES);
+    fixtures
+}
+
+fn vector_fixtures() -> Vec<(String, HorizonsVectors)> {
+    exports("cartesian states")
+        .into_iter()
+        .map(|(name, text)| {
+            let vectors = HorizonsVectors::parse(&text)
+                .unwrap_or_else(|err| panic!("{}: {}", name, err));
+            (name, vectors)
+        })
+        .collect()
+}
+
+// Every record of every fixture, with the elements it turns into.
+fn cases() -> Vec<(String, ElementRecord, OrbitalParameters)> {
+    fixtures()
+        .into_iter()
+        .flat_map(|(name, elements)| {
+            elements
+                .records
+                .iter()
+                .map(|record| {
+                    let orbit = OrbitalParameters {
+                        frame: elements.frame,
+                        ..record.orbital_parameters(elements.grav_parameter)
+                    };
+                    (format!("{} at JD {}", name, record.jd_tdb), *record, orbit)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn angle_difference(a: f64, b: f64) -> f64 {
+    ((a - b + PI).rem_euclid(2. * PI) - PI).abs()
+}
+
+fn relative_difference(a: f64, b: f64) -> f64 {
+    ((a - b) / b).abs()
+}
+
+// Position and velocity from the record's own true anomaly, written out in the closed form
+// rather than through the perifocal rotation `OrbitalParameters` uses.
+fn reference_state(record: &ElementRecord, mu: f64) -> (DVec3, DVec3) {
+    let e = record.eccentricity;
+    let p = record.periapsis_distance * (1. + e);
+    let nu = record.true_anomaly.to_radians();
+    let (node, periapsis, inclination) = (
+        record.longitude_asc_node.to_radians(),
+        record.arg_of_periapsis.to_radians(),
+        record.inclination.to_radians(),
+    );
+
+    let distance = p / (1. + e * nu.cos());
+    let u = periapsis + nu;
+    let direction = |u: f64| {
+        DVec3::new(
+            node.cos() * u.cos() - node.sin() * u.sin() * inclination.cos(),
+            node.sin() * u.cos() + node.cos() * u.sin() * inclination.cos(),
+            u.sin() * inclination.sin(),
+        )
+    };
+
+    let radial_speed = (mu / p).sqrt() * e * nu.sin();
+    let transverse_speed = (mu / p).sqrt() * (1. + e * nu.cos());
+
+    (
+        direction(u) * distance,
+        direction(u) * radial_speed + direction(u + PI / 2.) * transverse_speed,
+    )
+}
+
+#[test]
+fn fixtures_parse() {
+    for (name, elements) in fixtures() {
+        assert!(!elements.records.is_empty(), "{}", name);
+        assert!(elements.grav_parameter.is_some(), "{} has no Keplerian GM line", name);
+    }
+}
+
+#[test]
+fn mean_anomaly() {
+    for (case, record, orbit) in cases() {
+        let epoch = jd_to_j2000_seconds(record.jd_tdb);
+        let periapsis = jd_to_j2000_seconds(record.time_of_periapsis);
+
+        let at_epoch = orbit.mean_anomaly(epoch);
+        assert!(
+            angle_difference(at_epoch, record.mean_anomaly.to_radians()) < ANGLE_TOLERANCE,
+            "{}: mean anomaly {} against {}",
+            case,
+            at_epoch.to_degrees(),
+            record.mean_anomaly
+        );
+
+        // Horizons' time of periapsis has to come out of the mean motion too.
+        let at_periapsis = orbit.mean_anomaly(periapsis);
+        assert!(
+            angle_difference(at_periapsis, 0.) < ANGLE_TOLERANCE * 100.,
+            "{}: mean anomaly {} at periapsis",
+            case,
+            at_periapsis.to_degrees()
+        );
+
+        assert!(
+            relative_difference(orbit.mean_motion(), record.mean_motion.to_radians()) < 1e-12,
+            "{}: mean motion",
+            case
+        );
+    }
+}
+
+#[test]
+fn eccentric_anomaly() {
+    for (case, record, orbit) in cases() {
+        let mean_anomaly = record.mean_anomaly.to_radians();
+        let e = record.eccentricity;
+        let anomaly = orbit.anomaly(mean_anomaly).unwrap();
+
+        // The eccentric anomaly that goes with Horizons' true anomaly.
+        let nu = record.true_anomaly.to_radians();
+        let expected = 2. * (((1. - e) / (1. + e)).sqrt() * (nu / 2.).tan()).atan();
+
+        assert!(
+            angle_difference(anomaly, expected) < ANGLE_TOLERANCE,
+            "{}: eccentric anomaly {} against {}",
+            case,
+            anomaly.to_degrees(),
+            expected.to_degrees()
+        );
+        assert!((anomaly - e * anomaly.sin() - mean_anomaly).abs() < 1e-12, "{}", case);
+    }
+}
+
+#[test]
+fn true_anomaly() {
+    for (case, record, orbit) in cases() {
+        let anomaly = orbit.anomaly(record.mean_anomaly.to_radians()).unwrap();
+        let nu = orbit.true_anomaly(anomaly);
+
+        assert!(
+            angle_difference(nu, record.true_anomaly.to_radians()) < ANGLE_TOLERANCE,
+            "{}: true anomaly {} against {}",
+            case,
+            nu.to_degrees(),
+            record.true_anomaly
+        );
+    }
+}
+
+#[test]
+fn distance() {
+    for (case, record, orbit) in cases() {
+        let epoch = jd_to_j2000_seconds(record.jd_tdb);
+        let periapsis = jd_to_j2000_seconds(record.time_of_periapsis);
+        let (expected, _) = reference_state(&record, orbit.grav_parameter);
+
+        let anomaly = orbit.anomaly(orbit.mean_anomaly(epoch)).unwrap();
+        assert!(
+            relative_difference(orbit.distance(anomaly), expected.length()) < DISTANCE_TOLERANCE,
+            "{}: distance {} against {}",
+            case,
+            orbit.distance(anomaly),
+            expected.length()
+        );
+
+        let at_periapsis = orbit.position(periapsis).unwrap().length();
+        assert!(
+            relative_difference(at_periapsis, record.periapsis_distance) < DISTANCE_TOLERANCE,
+            "{}: periapsis distance {} against {}",
+            case,
+            at_periapsis,
+            record.periapsis_distance
+        );
+
+        if record.eccentricity < 1. {
+            let at_apoapsis = orbit.position(periapsis + record.period / 2.).unwrap().length();
+            assert!(
+                relative_difference(at_apoapsis, record.apoapsis_distance) < DISTANCE_TOLERANCE,
+                "{}: apoapsis distance {} against {}",
+                case,
+                at_apoapsis,
+                record.apoapsis_distance
+            );
+        }
+    }
+}
+
+#[test]
+fn position_and_velocity() {
+    for (case, record, orbit) in cases() {
+        let epoch = jd_to_j2000_seconds(record.jd_tdb);
+        let (expected_position, expected_velocity) = reference_state(&record, orbit.grav_parameter);
+        let (position, velocity) = orbit.state_vector(epoch).unwrap();
+
+        assert!(
+            (position - expected_position).length() < DISTANCE_TOLERANCE * expected_position.length(),
+            "{}: position {:?} against {:?}",
+            case,
+            position,
+            expected_position
+        );
+        assert!(
+            (velocity - expected_velocity).length() < SPEED_TOLERANCE * expected_velocity.length(),
+            "{}: velocity {:?} against {:?}",
+            case,
+            velocity,
+            expected_velocity
+        );
+
+        // And carried over into the frame the simulation runs in.
+        let (icrf, _) = orbit.simulation_state(epoch).unwrap();
+        let expected_icrf = match orbit.frame {
+            orbiter_physics::ReferenceFrame::Ecliptic => ecliptic_to_icrf(expected_position),
+            _ => expected_position,
+        };
+        assert!(
+            (icrf - expected_icrf).length() < DISTANCE_TOLERANCE * expected_icrf.length(),
+            "{}: ICRF position",
+            case
+        );
+    }
+}
+
+#[test]
+fn elements_round_trip_through_state_vectors() {
+    for (case, record, orbit) in cases() {
+        let epoch = jd_to_j2000_seconds(record.jd_tdb);
+        let (position, velocity) = orbit.state_vector(epoch).unwrap();
+        let back = OrbitalParameters::from_state_vector(position, velocity, orbit.grav_parameter, epoch);
+
+        assert!(relative_difference(back.semimajor_axis, record.semimajor_axis) < DISTANCE_TOLERANCE, "{}", case);
+        assert!((back.eccentricity - record.eccentricity).abs() < 1e-12, "{}", case);
+        for (angle, expected) in [
+            (back.inclination, record.inclination),
+            (back.longitude_asc_node, record.longitude_asc_node),
+            (back.arg_of_periapsis, record.arg_of_periapsis),
+            (back.mean_anomaly_at_epoch, record.mean_anomaly),
+        ] {
+            assert!(angle_difference(angle, expected.to_radians()) < ANGLE_TOLERANCE, "{}", case);
+        }
+    }
+}
+
+// The elements the app starts the Moon on are typed in from the bundled export.
+#[test]
+fn built_in_lunar_elements_match_the_export() {
+    let (_, elements) = fixtures()
+        .into_iter()
+        .find(|(_, elements)| elements.target == "Moon")
+        .expect("a Moon fixture");
+    let record = elements.records[0];
+    let moon = lunar_orbit();
+
+    assert_eq!(moon.frame, elements.frame);
+    assert_eq!(moon.epoch, jd_to_j2000_seconds(record.jd_tdb));
+    assert!(relative_difference(moon.semimajor_axis, record.semimajor_axis) < 1e-12);
+    assert!((moon.eccentricity - record.eccentricity).abs() < 1e-12);
+
+    // Transcribed to 8 or 9 digits.
+    for (angle, expected) in [
+        (moon.inclination, record.inclination),
+        (moon.longitude_asc_node, record.longitude_asc_node),
+        (moon.arg_of_periapsis, record.arg_of_periapsis),
+        (moon.mean_anomaly_at_epoch, record.mean_anomaly),
+    ] {
+        assert!(angle_difference(angle, expected.to_radians()) < 1e-8, "{} against {}", angle, expected);
+    }
+}
+
+// Two-body acceleration stepped with classic RK4, nothing shared with the Kepler solver.
+fn integrate_two_body(position: DVec3, velocity: DVec3, mu: f64, duration: f64) -> (DVec3, DVec3) {
+    let acceleration = |r: DVec3| -mu * r / r.length().powi(3);
+    let steps = (duration / 10.).ceil() as usize;
+    let h = duration / steps as f64;
+    let (mut r, mut v) = (position, velocity);
+
+    for _ in 0..steps {
+        let (k1r, k1v) = (v, acceleration(r));
+        let (k2r, k2v) = (v + k1v * h / 2., acceleration(r + k1r * h / 2.));
+        let (k3r, k3v) = (v + k2v * h / 2., acceleration(r + k2r * h / 2.));
+        let (k4r, k4v) = (v + k3v * h, acceleration(r + k3r * h));
+        r += (k1r + 2. * k2r + 2. * k3r + k4r) * h / 6.;
+        v += (k1v + 2. * k2v + 2. * k3v + k4v) * h / 6.;
+    }
+
+    (r, v)
+}
+
+#[test]
+fn propagation_matches_numerical_integration() {
+    for (case, record, orbit) in cases() {
+        if record.eccentricity >= 1. {
+            continue;
+        }
+
+        // Without the Moon's secular rates, so both sides solve the same problem.
+        let orbit = OrbitalParameters {
+            longitude_asc_node_rate: 0.,
+            arg_of_periapsis_rate: 0.,
+            ..orbit
+        };
+        let epoch = jd_to_j2000_seconds(record.jd_tdb);
+        let (position, velocity) = orbit.state_vector(epoch).unwrap();
+
+        for orbits in [0.1, 0.5, 1.3, 3.7] {
+            let duration = orbits * record.period;
+            let (expected_position, expected_velocity) =
+                integrate_two_body(position, velocity, orbit.grav_parameter, duration);
+            let (propagated, propagated_velocity) = orbit.state_vector(epoch + duration).unwrap();
+
+            assert!(
+                (propagated - expected_position).length() < 1e-6 * expected_position.length(),
+                "{}: {} orbits on, {:?} against {:?}",
+                case,
+                orbits,
+                propagated,
+                expected_position
+            );
+            assert!(
+                (propagated_velocity - expected_velocity).length() < 1e-6 * expected_velocity.length(),
+                "{}: {} orbits on, velocity",
+                case,
+                orbits
+            );
+        }
+    }
+}
+
+// DE441's Moon at the export's epoch, which the lunar theory knows nothing about.
+#[test]
+fn lunar_theory_matches_the_export_epoch() {
+    let (name, elements) = fixtures()
+        .into_iter()
+        .find(|(_, elements)| elements.target == "Moon" && elements.center == "Earth")
+        .expect("a Moon fixture");
+
+    for record in &elements.records {
+        let epoch = jd_to_j2000_seconds(record.jd_tdb);
+        let orbit = OrbitalParameters {
+            frame: elements.frame,
+            ..record.orbital_parameters(elements.grav_parameter)
+        };
+        let (expected, _) = orbit.simulation_state(epoch).unwrap();
+        let (position, _) = geocentric_state(epoch);
+
+        assert!(
+            (position - expected).length() < MEEUS_POSITION_TOLERANCE,
+            "{} at JD {}: {:?} against {:?}",
+            name,
+            record.jd_tdb,
+            position,
+            expected
+        );
+        assert!((position.length() - expected.length()).abs() < MEEUS_DISTANCE_TOLERANCE);
+    }
+}
+
+// Needs a VECTORS export of the Moon, see the top of the file. Horizons can't be reached
+// from every machine the tests run on, so it stays out of the default run until one is in.
+// From the physics folder, this saves the one it expects (DE441, 2000-01-01 to 2000-04-01
+// daily, ICRF), after which the #[ignore] can go:
+//
+//     curl -o tests/fixtures/moon_vectors_2000.txt "https://ssd.jpl.nasa.gov/api/horizons.api?\
+//     format=text&COMMAND='301'&CENTER='500@399'&MAKE_EPHEM=YES&EPHEM_TYPE=VECTORS&\
+//     START_TIME='2000-01-01'&STOP_TIME='2000-04-01'&STEP_SIZE='1d'&TIME_TYPE=TDB&\
+//     REF_PLANE=FRAME&REF_SYSTEM=ICRF&OUT_UNITS=KM-S&VEC_TABLE=2&CSV_FORMAT=NO"
+#[test]
+#[ignore = "needs a Horizons VECTORS export of the Moon in tests/fixtures"]
+fn lunar_models_match_horizons_vectors() {
+    let exports: Vec<_> = vector_fixtures()
+        .into_iter()
+        .filter(|(_, vectors)| vectors.target == "Moon" && vectors.center == "Earth")
+        .collect();
+    assert!(!exports.is_empty(), "no VECTORS export of the Moon in {}", FIXTURES);
+
+    let moon = lunar_orbit();
+    let mut later = 0;
+
+    for (name, vectors) in exports {
+        for record in &vectors.records {
+            let t = jd_to_j2000_seconds(record.jd_tdb);
+            let expected = vectors.frame.to_icrf(record.position, t);
+            let days = (t - moon.epoch) / 86400.;
+            later += (days >= 1.) as usize;
+
+            let (meeus, _) = geocentric_state(t);
+            assert!(
+                (meeus - expected).length() < MEEUS_POSITION_TOLERANCE,
+                "{} at JD {}: lunar theory {:?} against {:?}",
+                name,
+                record.jd_tdb,
+                meeus,
+                expected
+            );
+            assert!(
+                (meeus.length() - expected.length()).abs() < MEEUS_DISTANCE_TOLERANCE,
+                "{} at JD {}: lunar theory distance",
+                name,
+                record.jd_tdb
+            );
+
+            let Some(&(_, position_tolerance, distance_tolerance)) = ELLIPSE_TOLERANCES
+                .iter()
+                .find(|(up_to, _, _)| days.abs() <= *up_to)
+            else {
+                continue;
+            };
+            let (ellipse, _) = moon.simulation_state(t).unwrap();
+            assert!(
+                (ellipse - expected).length() < position_tolerance,
+                "{} at JD {}: elements {:?} against {:?}",
+                name,
+                record.jd_tdb,
+                ellipse,
+                expected
+            );
+            assert!(
+                (ellipse.length() - expected.length()).abs() < distance_tolerance,
+                "{} at JD {}: elements distance",
+                name,
+                record.jd_tdb
+            );
+        }
+    }
+
+    assert!(later > 0, "the exports need epochs after the elements' own");
+}
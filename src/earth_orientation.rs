@@ -0,0 +1,55 @@
+use bevy::math::{DQuat, DVec3};
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+
+use crate::frames::to_scene_rotation;
+use crate::orbit::{EarthBody, OrbitSet};
+use crate::time::PhysicsTime;
+use orbiter_physics::earth_orientation::earth_orientation;
+use orbiter_physics::time_scale::TimeScale;
+
+// Turns the Earth to its orientation at the clock's time every frame, see
+// orbiter_physics::earth_orientation for the models.
+pub struct EarthOrientationPlugin;
+
+impl Plugin for EarthOrientationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EarthOrientationSettings::default())
+            .add_systems(Update, orient_earth.in_set(OrbitSet::Place))
+            .register_type::<EarthOrientationSettings>();
+    }
+}
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct EarthOrientationSettings {
+    pub precession_nutation: bool,
+    pub prime_meridian_offset: f64, // Radians, lines the model's texture up with Greenwich
+}
+
+impl Default for EarthOrientationSettings {
+    fn default() -> Self {
+        EarthOrientationSettings {
+            precession_nutation: true,
+            prime_meridian_offset: 0.,
+        }
+    }
+}
+
+pub fn orient_earth(
+    settings: Res<EarthOrientationSettings>,
+    mut query: Query<&mut Transform, With<EarthBody>>,
+    physics_time_q: Query<&PhysicsTime>,
+) {
+    let physics_time = physics_time_q.single();
+
+    let orientation = earth_orientation(
+        physics_time.seconds(TimeScale::Utc),
+        settings.precession_nutation,
+    )
+        * DQuat::from_axis_angle(DVec3::Z, settings.prime_meridian_offset);
+
+    for mut transform in &mut query {
+        transform.rotation = to_scene_rotation(orientation);
+    }
+}
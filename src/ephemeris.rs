@@ -0,0 +1,605 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+use std::fmt;
+
+use crate::orbit::{propagate_orbits, OrbitSet, RelativePosition, RelativeVelocity};
+use crate::sun::AU;
+use crate::time::{PhysicsStep, PhysicsTime};
+use orbiter_physics::frames::ecliptic_to_icrf;
+use orbiter_physics::kepler::{self, KeplerError};
+use orbiter_physics::lunar_theory;
+use orbiter_physics::time_scale::TimeScale;
+
+// Heliocentric positions of the planets. VSOP87 series files (version A, heliocentric
+// rectangular coordinates on the J2000 ecliptic, as distributed by the IMCCE) dropped into
+// assets/vsop87/ are used when they're there. Otherwise positions come from JPL's
+// Keplerian elements for approximate positions of the major planets (Standish), good to
+// a few arcminutes between 1800 and 2050.
+//
+// A `CelestialBody` with an `EphemerisBody` is placed from the ephemeris rather than its
+// `OrbitalParameters`, relative to whichever body its `center` names. The scenario gives
+// its bodies one with `OrbitSource::Ephemeris`.
+pub struct EphemerisPlugin;
+
+impl Plugin for EphemerisPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Vsop87Series>()
+            .init_asset_loader::<Vsop87Loader>()
+            .insert_resource(Ephemeris::default())
+            .add_systems(Startup, setup)
+            .add_systems(Update, collect_vsop87_series)
+            .add_systems(
+                PhysicsStep,
+                propagate_ephemeris_bodies
+                    .in_set(OrbitSet::Propagate)
+                    .after(propagate_orbits),
+            )
+            .register_type::<Planet>()
+            .register_type::<EphemerisBody>();
+    }
+}
+
+const SECONDS_PER_CENTURY: f64 = 36525. * 86400.;
+const SECONDS_PER_MILLENNIUM: f64 = 10. * SECONDS_PER_CENTURY;
+// Earth's share of the Earth-Moon barycentre offset, GM Moon / (GM Earth + GM Moon).
+const MOON_MASS_FRACTION: f64 = 4902.800066 / (398600.4418 + 4902.800066);
+
+#[derive(Reflect, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Planet {
+    Sun, // Origin of the heliocentric positions, so always at zero
+    Mercury,
+    Venus,
+    Earth,
+    Mars,
+    Jupiter,
+    Saturn,
+    Uranus,
+    Neptune,
+}
+
+impl Planet {
+    pub const PLANETS: [Planet; 8] = [
+        Planet::Mercury,
+        Planet::Venus,
+        Planet::Earth,
+        Planet::Mars,
+        Planet::Jupiter,
+        Planet::Saturn,
+        Planet::Uranus,
+        Planet::Neptune,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Planet::Sun => "Sun",
+            Planet::Mercury => "Mercury",
+            Planet::Venus => "Venus",
+            Planet::Earth => "Earth",
+            Planet::Mars => "Mars",
+            Planet::Jupiter => "Jupiter",
+            Planet::Saturn => "Saturn",
+            Planet::Uranus => "Uranus",
+            Planet::Neptune => "Neptune",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Planet> {
+        [Planet::Sun]
+            .into_iter()
+            .chain(Planet::PLANETS)
+            .find(|planet| planet.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// Places a body from the ephemeris: its position relative to its parent is that of `body`
+// less that of `center`, which should be the planet (or Sun) the parent is.
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct EphemerisBody {
+    pub body: Planet,
+    pub center: Planet,
+}
+
+impl Default for EphemerisBody {
+    fn default() -> Self {
+        EphemerisBody {
+            body: Planet::Earth,
+            center: Planet::Sun,
+        }
+    }
+}
+
+// Semimajor axis (AU), eccentricity, inclination, mean longitude, longitude of perihelion
+// and longitude of the ascending node (degrees) at J2000, each followed by its rate per
+// Julian century. Table 1 of Standish, referred to the J2000 ecliptic. The Earth row is
+// the Earth-Moon barycentre.
+#[rustfmt::skip]
+const APPROXIMATE_ELEMENTS: [(Planet, [f64; 6], [f64; 6]); 8] = [
+    (Planet::Mercury,
+        [0.38709927, 0.20563593, 7.00497902, 252.25032350, 77.45779628, 48.33076593],
+        [0.00000037, 0.00001906, -0.00594749, 149472.67411175, 0.16047689, -0.12534081]),
+    (Planet::Venus,
+        [0.72333566, 0.00677672, 3.39467605, 181.97909950, 131.60246718, 76.67984255],
+        [0.00000390, -0.00004107, -0.00078890, 58517.81538729, 0.00268329, -0.27769418]),
+    (Planet::Earth,
+        [1.00000261, 0.01671123, -0.00001531, 100.46457166, 102.93768193, 0.0],
+        [0.00000562, -0.00004392, -0.01294668, 35999.37244981, 0.32327364, 0.0]),
+    (Planet::Mars,
+        [1.52371034, 0.09339410, 1.84969142, -4.55343205, -23.94362959, 49.55953891],
+        [0.00001847, 0.00007882, -0.00813131, 19140.30268499, 0.44441088, -0.29257343]),
+    (Planet::Jupiter,
+        [5.20288700, 0.04838624, 1.30439695, 34.39644051, 14.72847983, 100.47390909],
+        [-0.00011607, -0.00013253, -0.00183714, 3034.74612775, 0.21252668, 0.20469106]),
+    (Planet::Saturn,
+        [9.53667594, 0.05386179, 2.48599187, 49.95424423, 92.59887831, 113.66242448],
+        [-0.00125060, -0.00050991, 0.00193609, 1222.49362201, -0.41897216, -0.28867794]),
+    (Planet::Uranus,
+        [19.18916464, 0.04725744, 0.77263783, 313.23810451, 170.95427630, 74.01692503],
+        [-0.00196176, -0.00004397, -0.00242939, 428.48202785, 0.40805281, 0.04240589]),
+    (Planet::Neptune,
+        [30.06992276, 0.00859048, 1.77004347, -55.12002969, 44.96476227, 131.78422574],
+        [0.00026291, 0.00005105, 0.00035372, 218.45945325, -0.32241464, -0.01262724]),
+];
+
+// Heliocentric position in KM, ICRF, from the approximate elements, `tdb` seconds past
+// J2000.
+pub fn approximate_position(body: Planet, tdb: f64) -> Result<DVec3, KeplerError> {
+    let Some((_, elements, rates)) = APPROXIMATE_ELEMENTS.iter().find(|(planet, _, _)| *planet == body)
+    else {
+        return Ok(DVec3::ZERO);
+    };
+
+    let centuries = tdb / SECONDS_PER_CENTURY;
+    let at = |idx: usize| elements[idx] + rates[idx] * centuries;
+
+    let (semimajor_axis, eccentricity) = (at(0), at(1));
+    let inclination = at(2).to_radians();
+    let perihelion = at(4).to_radians();
+    let node = at(5).to_radians();
+    let mean_anomaly = (at(3).to_radians() - perihelion).rem_euclid(2. * std::f64::consts::PI);
+    let arg_of_perihelion = perihelion - node;
+
+    let eccentric_anomaly = kepler::eccentric_anomaly(mean_anomaly, eccentricity)?;
+    let x = semimajor_axis * (eccentric_anomaly.cos() - eccentricity);
+    let y = semimajor_axis * (1. - eccentricity * eccentricity).sqrt() * eccentric_anomaly.sin();
+
+    let (sin_w, cos_w) = arg_of_perihelion.sin_cos();
+    let (sin_node, cos_node) = node.sin_cos();
+    let (sin_i, cos_i) = inclination.sin_cos();
+
+    let ecliptic = DVec3::new(
+        (cos_w * cos_node - sin_w * sin_node * cos_i) * x
+            + (-sin_w * cos_node - cos_w * sin_node * cos_i) * y,
+        (cos_w * sin_node + sin_w * cos_node * cos_i) * x
+            + (-sin_w * sin_node + cos_w * cos_node * cos_i) * y,
+        sin_w * sin_i * x + cos_w * sin_i * y,
+    );
+    let mut position = ecliptic_to_icrf(ecliptic) * AU;
+
+    // The table has the Earth-Moon barycentre, Earth sits on the far side of it from the
+    // Moon.
+    if body == Planet::Earth {
+        position -= lunar_theory::geocentric_position(tdb) * MOON_MASS_FRACTION;
+    }
+
+    Ok(position)
+}
+
+// One term of a series, A cos(B + C T) with T in Julian millennia.
+#[derive(Debug, Clone, Copy)]
+pub struct Vsop87Term {
+    pub amplitude: f64, // AU
+    pub phase: f64,     // Radians
+    pub frequency: f64, // Radians per millennium
+}
+
+// A VSOP87A file: for each of X, Y and Z, the series multiplying each power of T.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct Vsop87Series {
+    pub body: Planet,
+    pub coordinates: [Vec<Vec<Vsop87Term>>; 3],
+}
+
+#[derive(Debug)]
+pub enum Vsop87Error {
+    Io(std::io::Error),
+    UnsupportedVersion(String), // Only version A, rectangular on the J2000 ecliptic
+    UnknownBody(String),
+    InvalidHeader(String),
+    InvalidTerm(String),
+    Empty,
+}
+
+impl fmt::Display for Vsop87Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Vsop87Error::Io(err) => write!(f, "could not read VSOP87 file: {}", err),
+            Vsop87Error::UnsupportedVersion(version) => {
+                write!(f, "VSOP87 version {} isn't supported, expected version A", version)
+            }
+            Vsop87Error::UnknownBody(body) => write!(f, "unknown VSOP87 body {}", body),
+            Vsop87Error::InvalidHeader(line) => write!(f, "invalid VSOP87 header: {}", line),
+            Vsop87Error::InvalidTerm(line) => write!(f, "invalid VSOP87 term: {}", line),
+            Vsop87Error::Empty => write!(f, "VSOP87 file has no series"),
+        }
+    }
+}
+
+impl std::error::Error for Vsop87Error {}
+
+impl From<std::io::Error> for Vsop87Error {
+    fn from(err: std::io::Error) -> Self {
+        Vsop87Error::Io(err)
+    }
+}
+
+impl Vsop87Series {
+    // Each series starts with a header like
+    //   VSOP87 VERSION A1    MERCURY   VARIABLE 1 (XYZ)       *T**0   1449 TERMS ...
+    // followed by its terms, the last three numbers on a term line being A, B and C.
+    pub fn parse(text: &str) -> Result<Vsop87Series, Vsop87Error> {
+        let mut body = None;
+        let mut coordinates: [Vec<Vec<Vsop87Term>>; 3] = Default::default();
+        let mut current: Option<(usize, usize)> = None;
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            if tokens[0] == "VSOP87" {
+                let invalid = || Vsop87Error::InvalidHeader(line.trim().to_string());
+
+                let version = tokens.get(2).ok_or_else(invalid)?;
+                if !version.starts_with('A') {
+                    return Err(Vsop87Error::UnsupportedVersion(version.to_string()));
+                }
+
+                let name = tokens.get(3).ok_or_else(invalid)?;
+                let planet = Planet::from_name(name)
+                    .filter(|planet| *planet != Planet::Sun)
+                    .ok_or_else(|| Vsop87Error::UnknownBody(name.to_string()))?;
+                body = Some(planet);
+
+                let variable = tokens
+                    .get(5)
+                    .and_then(|token| token.parse::<usize>().ok())
+                    .filter(|variable| (1..=3).contains(variable))
+                    .ok_or_else(invalid)?;
+                let power = tokens
+                    .iter()
+                    .find_map(|token| token.strip_prefix("*T**"))
+                    .and_then(|power| power.parse::<usize>().ok())
+                    .ok_or_else(invalid)?;
+
+                let series = &mut coordinates[variable - 1];
+                if series.len() <= power {
+                    series.resize(power + 1, Vec::new());
+                }
+                current = Some((variable - 1, power));
+                continue;
+            }
+
+            let Some((variable, power)) = current else {
+                return Err(Vsop87Error::InvalidTerm(line.trim().to_string()));
+            };
+
+            let numbers: Option<Vec<f64>> = tokens[tokens.len().saturating_sub(3)..]
+                .iter()
+                .map(|token| token.parse::<f64>().ok())
+                .collect();
+            let Some(&[amplitude, phase, frequency]) = numbers.as_deref() else {
+                return Err(Vsop87Error::InvalidTerm(line.trim().to_string()));
+            };
+
+            coordinates[variable][power].push(Vsop87Term {
+                amplitude,
+                phase,
+                frequency,
+            });
+        }
+
+        Ok(Vsop87Series {
+            body: body.ok_or(Vsop87Error::Empty)?,
+            coordinates,
+        })
+    }
+
+    // Heliocentric position (KM) and velocity (KM/s) in ICRF, `tdb` seconds past J2000.
+    pub fn state(&self, tdb: f64) -> (DVec3, DVec3) {
+        let millennia = tdb / SECONDS_PER_MILLENNIUM;
+        let mut position = [0.; 3];
+        let mut rate = [0.; 3]; // AU per millennium
+
+        for (coordinate, series) in self.coordinates.iter().enumerate() {
+            for (power, terms) in series.iter().enumerate() {
+                let t_power = millennia.powi(power as i32);
+                let t_power_rate = if power == 0 {
+                    0.
+                } else {
+                    power as f64 * millennia.powi(power as i32 - 1)
+                };
+
+                for term in terms {
+                    let (sin, cos) = (term.phase + term.frequency * millennia).sin_cos();
+                    position[coordinate] += t_power * term.amplitude * cos;
+                    rate[coordinate] += term.amplitude
+                        * (t_power_rate * cos - t_power * term.frequency * sin);
+                }
+            }
+        }
+
+        (
+            vsop87_to_icrf(DVec3::from(position)) * AU,
+            vsop87_to_icrf(DVec3::from(rate)) * AU / SECONDS_PER_MILLENNIUM,
+        )
+    }
+}
+
+// From VSOP87's dynamical ecliptic and equinox of J2000 to ICRF (Bretagnon & Francou).
+fn vsop87_to_icrf(vector: DVec3) -> DVec3 {
+    DVec3::new(
+        vector.x + 0.000000440360 * vector.y - 0.000000190919 * vector.z,
+        -0.000000479966 * vector.x + 0.917482137087 * vector.y - 0.397776982902 * vector.z,
+        0.397776982902 * vector.y + 0.917482137087 * vector.z,
+    )
+}
+
+#[derive(Default)]
+pub struct Vsop87Loader;
+
+impl AssetLoader for Vsop87Loader {
+    type Asset = Vsop87Series;
+    type Settings = ();
+    type Error = Vsop87Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Vsop87Series, Vsop87Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Vsop87Series::parse(&String::from_utf8_lossy(&bytes))
+        })
+    }
+
+    // The IMCCE's names, VSOP87A.mer and so on.
+    fn extensions(&self) -> &[&str] {
+        &["mer", "ven", "ear", "mar", "jup", "sat", "ura", "nep"]
+    }
+}
+
+// Keeps the loaded folder, and with it every series file, alive.
+#[derive(Resource)]
+pub struct Vsop87Folder(pub Handle<bevy::asset::LoadedFolder>);
+
+// Every VSOP87 series loaded so far. Planets without one fall back to the approximate
+// elements.
+#[derive(Resource, Default)]
+pub struct Ephemeris {
+    pub vsop87: HashMap<Planet, Vsop87Series>,
+}
+
+impl Ephemeris {
+    // Heliocentric position of `body` in KM, ICRF, `tdb` seconds past J2000.
+    pub fn position_at(&self, body: Planet, tdb: f64) -> Result<DVec3, KeplerError> {
+        match self.vsop87.get(&body) {
+            Some(series) => Ok(series.state(tdb).0),
+            None => approximate_position(body, tdb),
+        }
+    }
+
+    // Heliocentric position (KM) and velocity (KM/s), ICRF.
+    pub fn state_at(&self, body: Planet, tdb: f64) -> Result<(DVec3, DVec3), KeplerError> {
+        if body == Planet::Sun {
+            return Ok((DVec3::ZERO, DVec3::ZERO));
+        }
+
+        if let Some(series) = self.vsop87.get(&body) {
+            return Ok(series.state(tdb));
+        }
+
+        // Planets move at most a few degrees a day, a central difference over a minute is
+        // plenty.
+        let posn = approximate_position(body, tdb)?;
+        let vel = (approximate_position(body, tdb + 60.)? - approximate_position(body, tdb - 60.)?)
+            / 120.;
+        Ok((posn, vel))
+    }
+}
+
+pub fn setup(mut commands: Commands, ass: Res<AssetServer>) {
+    commands.insert_resource(Vsop87Folder(ass.load_folder("vsop87")));
+}
+
+pub fn collect_vsop87_series(
+    mut events: EventReader<AssetEvent<Vsop87Series>>,
+    series: Res<Assets<Vsop87Series>>,
+    mut ephemeris: ResMut<Ephemeris>,
+) {
+    for event in events.read() {
+        let id = match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => *id,
+            _ => continue,
+        };
+
+        if let Some(series) = series.get(id) {
+            println!("Loaded VSOP87 series for {}.", series.body.name());
+            ephemeris.vsop87.insert(series.body, series.clone());
+        }
+    }
+}
+
+pub fn propagate_ephemeris_bodies(
+    mut commands: Commands,
+    ephemeris: Res<Ephemeris>,
+    mut body_query: Query<(
+        Entity,
+        &EphemerisBody,
+        Option<&mut RelativePosition>,
+        Option<&mut RelativeVelocity>,
+    )>,
+    physics_time_q: Query<&PhysicsTime>,
+) {
+    let t = physics_time_q.single().seconds(TimeScale::Tdb);
+
+    for (entity, binding, relative, velocity) in &mut body_query {
+        let (Ok((body_posn, body_vel)), Ok((center_posn, center_vel))) = (
+            ephemeris.state_at(binding.body, t),
+            ephemeris.state_at(binding.center, t),
+        ) else {
+            continue;
+        };
+
+        let (posn, vel) = (body_posn - center_posn, body_vel - center_vel);
+
+        match (relative, velocity) {
+            (Some(mut relative), Some(mut velocity)) => {
+                relative.0 = posn;
+                velocity.0 = vel;
+            }
+            _ => {
+                commands
+                    .entity(entity)
+                    .insert((RelativePosition(posn), RelativeVelocity(vel)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orbiter_physics::frames::icrf_to_ecliptic;
+
+    // The leading term of Earth's X and Y series from VSOP87A.ear, everything else dropped.
+    // Without the eccentricity terms it's a circle, good to about 0.017 AU and a degree.
+    const EARTH_EXCERPT: &str = "
+ VSOP87 VERSION A1    EARTH     VARIABLE 1 (XYZ)       *T**0      1 TERMS    HELIOCENTRIC DYNAMICAL ECLIPTIC AND EQUINOX J2000
+ 1311    1  0  0  0  0  0  0  0  0  0  0  0  0 0.00000000000 0.99982928844 0.99982928844 1.75348568475     6283.07584999140
+ VSOP87 VERSION A1    EARTH     VARIABLE 2 (XYZ)       *T**0      1 TERMS    HELIOCENTRIC DYNAMICAL ECLIPTIC AND EQUINOX J2000
+ 1321    1  0  0  0  0  0  0  0  0  0  0  0  0 0.00000000000 0.99989211030 0.99989211030 0.18265890456     6283.07584999140
+";
+
+    // Meeus, Astronomical Algorithms, examples 25.b and 33.a: heliocentric longitude,
+    // latitude (degrees, ecliptic and equinox of date) and distance (AU) at a JDE.
+    const EARTH_REFERENCE: (f64, f64, f64, f64) = (2448908.5, 19.907372, -0.000179, 0.99760775);
+    const VENUS_REFERENCE: (f64, f64, f64, f64) = (2448976.5, 26.11428, -2.62070, 0.724603);
+
+    fn seconds(jde: f64) -> f64 {
+        (jde - 2451545.) * 86400.
+    }
+
+    // Longitude and latitude in degrees on the J2000 ecliptic, and distance in AU.
+    fn spherical(position: DVec3) -> (f64, f64, f64) {
+        let ecliptic = icrf_to_ecliptic(position);
+        let r = ecliptic.length();
+        (
+            ecliptic.y.atan2(ecliptic.x).to_degrees().rem_euclid(360.),
+            (ecliptic.z / r).asin().to_degrees(),
+            r / AU,
+        )
+    }
+
+    // Brings a longitude of date back to J2000, general precession of 5029.0966" a century.
+    fn longitude_at_j2000(longitude: f64, jde: f64) -> f64 {
+        longitude - 5029.0966 / 3600. * (jde - 2451545.) / 36525.
+    }
+
+    #[test]
+    fn vsop87_files_parse() {
+        let series = Vsop87Series::parse(EARTH_EXCERPT).unwrap();
+
+        assert_eq!(series.body, Planet::Earth);
+        assert_eq!(series.coordinates[0].len(), 1);
+        assert_eq!(series.coordinates[1].len(), 1);
+        assert!(series.coordinates[2].is_empty());
+
+        let term = series.coordinates[1][0][0];
+        assert_eq!(term.amplitude, 0.99989211030);
+        assert_eq!(term.phase, 0.18265890456);
+        assert_eq!(term.frequency, 6283.07584999140);
+
+        assert!(matches!(
+            Vsop87Series::parse(&EARTH_EXCERPT.replace("VERSION A1", "VERSION B1")),
+            Err(Vsop87Error::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            Vsop87Series::parse(&EARTH_EXCERPT.replace("EARTH", "PLUTO")),
+            Err(Vsop87Error::UnknownBody(_))
+        ));
+        assert!(matches!(
+            Vsop87Series::parse("1311 1 0 0.1 0.2 0.3"),
+            Err(Vsop87Error::InvalidTerm(_))
+        ));
+        assert!(matches!(Vsop87Series::parse("\n"), Err(Vsop87Error::Empty)));
+    }
+
+    #[test]
+    fn vsop87_excerpt_places_the_earth() {
+        let series = Vsop87Series::parse(EARTH_EXCERPT).unwrap();
+        let (jde, longitude, latitude, distance) = EARTH_REFERENCE;
+
+        let (l, b, r) = spherical(series.state(seconds(jde)).0);
+
+        assert!((l - longitude_at_j2000(longitude, jde)).abs() < 2., "{l}");
+        assert!((b - latitude).abs() < 1e-3, "{b}");
+        assert!((r - distance).abs() < 0.02, "{r}");
+    }
+
+    #[test]
+    fn vsop87_velocity_is_the_derivative_of_position() {
+        let term = |amplitude, phase, frequency| Vsop87Term {
+            amplitude,
+            phase,
+            frequency,
+        };
+        // Made up, but with a term on each power so the T^n factors get differentiated too.
+        let series = Vsop87Series {
+            body: Planet::Mars,
+            coordinates: [
+                vec![vec![term(1.5, 0.3, 3340.6)], vec![term(0.01, 1.1, 6681.2)], vec![term(1e-4, 0., 0.)]],
+                vec![vec![term(1.5, 1.9, 3340.6)], vec![term(0.02, 0.4, 0.)]],
+                vec![vec![term(0.05, 2.2, 3340.6)], vec![], vec![term(3e-4, 0.7, 10.)]],
+            ],
+        };
+
+        for tdb in [0., 1e9, -2e9] {
+            let (_, velocity) = series.state(tdb);
+            let h = 60.;
+            let difference = (series.state(tdb + h).0 - series.state(tdb - h).0) / (2. * h);
+
+            assert!(
+                (velocity - difference).length() < 1e-6 * velocity.length(),
+                "{velocity} against {difference}"
+            );
+        }
+    }
+
+    #[test]
+    fn approximate_elements_are_good_to_arcminutes() {
+        for (planet, (jde, longitude, latitude, distance)) in
+            [(Planet::Earth, EARTH_REFERENCE), (Planet::Venus, VENUS_REFERENCE)]
+        {
+            let (l, b, r) = spherical(approximate_position(planet, seconds(jde)).unwrap());
+            let arcminutes = |degrees: f64| degrees.abs() * 60.;
+
+            assert!(arcminutes(l - longitude_at_j2000(longitude, jde)) < 5., "{planet:?} {l}");
+            assert!(arcminutes(b - latitude) < 5., "{planet:?} {b}");
+            assert!((r - distance).abs() < 1e-4, "{planet:?} {r}");
+        }
+    }
+
+    #[test]
+    fn the_sun_stays_at_the_origin() {
+        let ephemeris = Ephemeris::default();
+
+        assert_eq!(ephemeris.state_at(Planet::Sun, 1e8).unwrap(), (DVec3::ZERO, DVec3::ZERO));
+        assert_eq!(ephemeris.position_at(Planet::Sun, 1e8).unwrap(), DVec3::ZERO);
+    }
+}
@@ -0,0 +1,89 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+
+use crate::frames::to_scene;
+use crate::orbit::{BodyRegistry, CelestialBody, OrbitSet, SimulationPosition};
+
+// Render transforms are f32 and lose precision far from the origin, so instead of
+// centering the scene on Earth every frame re-centers it on the body the active camera
+// is attached to. Positions stay in f64 `SimulationPosition`s, only the difference from
+// the origin is ever converted to a Transform.
+pub struct FloatingOriginPlugin;
+
+impl Plugin for FloatingOriginPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FloatingOrigin::default())
+            .add_systems(
+                Update,
+                (update_floating_origin, apply_floating_origin)
+                    .chain()
+                    .in_set(OrbitSet::Render),
+            )
+            .add_systems(Update, follow_camera.after(OrbitSet::Render))
+            .register_type::<FloatingOrigin>();
+    }
+}
+
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource)]
+pub struct FloatingOrigin {
+    pub anchor: Option<Entity>,
+    pub position: DVec3, // KM in the simulation frame
+}
+
+// Kept centered on the active camera, e.g. the skybox.
+#[derive(Component)]
+pub struct FollowCamera;
+
+pub fn update_floating_origin(
+    mut origin: ResMut<FloatingOrigin>,
+    registry: Res<BodyRegistry>,
+    camera_query: Query<(Entity, &Camera), With<Camera3d>>,
+    parent_query: Query<&Parent>,
+    body_query: Query<&SimulationPosition, With<CelestialBody>>,
+) {
+    // The body the active camera hangs off, or the first body in focus order if the
+    // camera is free.
+    let anchor = camera_query
+        .iter()
+        .find(|(_, camera)| camera.is_active)
+        .and_then(|(camera, _)| {
+            parent_query
+                .iter_ancestors(camera)
+                .find(|ancestor| body_query.contains(*ancestor))
+        })
+        .or_else(|| registry.iter().next().map(|body| body.entity));
+
+    let Some(anchor) = anchor else {
+        return;
+    };
+
+    let Ok(position) = body_query.get(anchor) else {
+        return;
+    };
+
+    origin.anchor = Some(anchor);
+    origin.position = position.0;
+}
+
+pub fn apply_floating_origin(
+    origin: Res<FloatingOrigin>,
+    mut body_query: Query<(&SimulationPosition, &mut Transform), With<CelestialBody>>,
+) {
+    for (position, mut transform) in &mut body_query {
+        transform.translation = to_scene(position.0 - origin.position);
+    }
+}
+
+pub fn follow_camera(
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut follower_query: Query<&mut Transform, With<FollowCamera>>,
+) {
+    let Some((_, camera)) = camera_query.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    for mut transform in &mut follower_query {
+        transform.translation = camera.translation();
+    }
+}
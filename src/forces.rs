@@ -0,0 +1,335 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+
+use crate::earth_orientation::EarthOrientationSettings;
+use crate::orbit::{EarthBody, OrbitSet};
+use crate::time::{PhysicsStep, PhysicsTime};
+use orbiter_physics::earth_orientation::earth_orientation;
+use orbiter_physics::time_scale::TimeScale;
+
+// Forces on numerically propagated bodies beyond point mass gravity. What a central body
+// offers is set by its components (`ZonalHarmonics`, `ExponentialAtmosphere`), which of
+// them act on a body is set by its `ForceModels`, and the accelerations are summed.
+pub struct ForcesPlugin;
+
+impl Plugin for ForcesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PhysicsStep, track_earth_pole.before(OrbitSet::Propagate))
+            .register_type::<ForceModels>()
+            .register_type::<ZonalHarmonics>()
+            .register_type::<ExponentialAtmosphere>();
+    }
+}
+
+// Earth's rotation rate, radians per second.
+pub const EARTH_ROTATION_RATE: f64 = 7.292115e-5;
+
+// Which forces act on a body. Gravity of the parent always does.
+#[derive(Component, Reflect, InspectorOptions, Clone, Copy, Debug)]
+#[reflect(Component, InspectorOptions)]
+pub struct ForceModels {
+    pub j2: bool,
+    pub j3: bool,
+    pub third_body: bool,           // Every other body with a GravParameter, i.e. the Sun and Moon
+    pub drag: bool,
+    pub ballistic_coefficient: f64, // m^2/kg, drag coefficient * area / mass
+}
+
+impl Default for ForceModels {
+    fn default() -> Self {
+        ForceModels {
+            j2: true,
+            j3: true,
+            third_body: true,
+            drag: true,
+            ballistic_coefficient: 2.2 * 0.01,
+        }
+    }
+}
+
+// Zonal harmonics of a body's gravity field, on a body the ones being propagated orbit.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ZonalHarmonics {
+    pub radius: f64, // KM, reference radius the coefficients go with
+    pub j2: f64,
+    pub j3: f64,
+    pub pole: DVec3, // Unit vector along the rotation axis, ICRF
+}
+
+impl Default for ZonalHarmonics {
+    fn default() -> Self {
+        ZonalHarmonics::earth()
+    }
+}
+
+impl ZonalHarmonics {
+    // EGM2008.
+    pub fn earth() -> Self {
+        ZonalHarmonics {
+            radius: 6378.1363,
+            j2: 1.0826358e-3,
+            j3: -2.5324e-6,
+            pole: DVec3::Z,
+        }
+    }
+}
+
+// Drag atmosphere, with the same falloff as the rendered one: density drops off as
+// exp(-height01 * falloff) and goes to nothing at the top. Heights are measured from
+// `base_altitude` rather than the surface, a single exponential can't match the air at
+// the ground and in low orbit both.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ExponentialAtmosphere {
+    pub radius: f64,        // KM, surface
+    pub base_altitude: f64, // KM above the surface
+    pub base_density: f64,  // kg/m^3 at base_altitude
+    pub height: f64,        // KM from base_altitude to the top
+    pub falloff: f64,
+    pub rotation_rate: f64, // Radians per second, the air turns with the body
+}
+
+impl Default for ExponentialAtmosphere {
+    fn default() -> Self {
+        ExponentialAtmosphere::earth()
+    }
+}
+
+impl ExponentialAtmosphere {
+    // Close to the mean of the usual tables from 200 to 600 km.
+    pub fn earth() -> Self {
+        ExponentialAtmosphere {
+            radius: 6378.1363,
+            base_altitude: 200.,
+            base_density: 2.5e-10,
+            height: 800.,
+            falloff: 16.,
+            rotation_rate: EARTH_ROTATION_RATE,
+        }
+    }
+
+    // kg/m^3 at a distance from the centre.
+    pub fn density(&self, distance: f64) -> f64 {
+        let height01 = (distance - self.radius - self.base_altitude) / self.height;
+
+        if height01 >= 1. {
+            return 0.;
+        }
+
+        self.base_density * (-height01 * self.falloff).exp() * (1. - height01)
+    }
+}
+
+// J2 and J3 terms at `position` relative to the body's centre.
+pub fn zonal_acceleration(
+    position: DVec3,
+    mu: f64,
+    harmonics: &ZonalHarmonics,
+    j2: bool,
+    j3: bool,
+) -> DVec3 {
+    let r2 = position.length_squared();
+    let r = r2.sqrt();
+    let z = position.dot(harmonics.pole);
+    let z2_r2 = z * z / r2;
+    let mut acceleration = DVec3::ZERO;
+
+    if j2 {
+        let k = -1.5 * harmonics.j2 * mu * harmonics.radius.powi(2) / r.powi(5);
+        acceleration += k * ((1. - 5. * z2_r2) * position + 2. * z * harmonics.pole);
+    }
+
+    if j3 {
+        let k = -2.5 * harmonics.j3 * mu * harmonics.radius.powi(3) / r.powi(7);
+        acceleration += k
+            * ((3. * z - 7. * z * z2_r2) * position + (3. * z * z - 0.6 * r2) * harmonics.pole);
+    }
+
+    acceleration
+}
+
+// Drag against air turning with the body about `pole`.
+pub fn drag_acceleration(
+    position: DVec3,
+    velocity: DVec3,
+    pole: DVec3,
+    atmosphere: &ExponentialAtmosphere,
+    ballistic_coefficient: f64,
+) -> DVec3 {
+    let density = atmosphere.density(position.length());
+
+    if density <= 0. {
+        return DVec3::ZERO;
+    }
+
+    let relative = velocity - (pole * atmosphere.rotation_rate).cross(position);
+    // Density is per m^3 and speeds are in KM/s, 1000 turns the result back into KM/s^2.
+    -0.5 * density * ballistic_coefficient * 1000. * relative.length() * relative
+}
+
+// Keeps Earth's pole where precession and nutation have it for the coming sub-step.
+pub fn track_earth_pole(
+    settings: Res<EarthOrientationSettings>,
+    physics_time_q: Query<&PhysicsTime>,
+    mut earth_query: Query<&mut ZonalHarmonics, With<EarthBody>>,
+) {
+    let physics_time = physics_time_q.single();
+    let orientation = earth_orientation(
+        physics_time.seconds(TimeScale::Utc),
+        settings.precession_nutation,
+    );
+
+    for mut harmonics in &mut earth_query {
+        harmonics.pole = orientation * DVec3::Z;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrators::{rk4_step, State};
+    use crate::nbody::gravity;
+    use std::f64::consts::PI;
+
+    const MU: f64 = 398600.4418;
+
+    // Zonal part of the potential, J2 and J3 terms only.
+    fn zonal_potential(position: DVec3, harmonics: &ZonalHarmonics) -> f64 {
+        let r = position.length();
+        let s = position.dot(harmonics.pole) / r;
+        let p2 = 0.5 * (3. * s * s - 1.);
+        let p3 = 0.5 * (5. * s * s * s - 3. * s);
+        let ratio = harmonics.radius / r;
+
+        -MU / r * (harmonics.j2 * ratio.powi(2) * p2 + harmonics.j3 * ratio.powi(3) * p3)
+    }
+
+    #[test]
+    fn zonal_terms_are_the_gradient_of_the_potential() {
+        let harmonics = ZonalHarmonics {
+            pole: DVec3::new(0.1, -0.2, 1.).normalize(),
+            ..ZonalHarmonics::earth()
+        };
+        let dx = 1e-3;
+
+        for position in [
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(4000., -3000., 5000.),
+            DVec3::new(-1000., 2000., -8000.),
+        ] {
+            let gradient = DVec3::new(
+                zonal_potential(position + DVec3::X * dx, &harmonics)
+                    - zonal_potential(position - DVec3::X * dx, &harmonics),
+                zonal_potential(position + DVec3::Y * dx, &harmonics)
+                    - zonal_potential(position - DVec3::Y * dx, &harmonics),
+                zonal_potential(position + DVec3::Z * dx, &harmonics)
+                    - zonal_potential(position - DVec3::Z * dx, &harmonics),
+            ) / (2. * dx);
+            let acceleration = zonal_acceleration(position, MU, &harmonics, true, true);
+
+            assert!(
+                (acceleration - gradient).length() < 1e-6 * gradient.length(),
+                "{position}: {acceleration} against {gradient}"
+            );
+        }
+    }
+
+    #[test]
+    fn j2_regresses_the_node() {
+        let harmonics = ZonalHarmonics::earth();
+        let (a, inclination) = (7000f64, 50f64.to_radians());
+        let n = (MU / a.powi(3)).sqrt();
+        let period = 2. * PI / n;
+
+        // Circular, starting at the ascending node.
+        let mut state = State {
+            position: DVec3::new(a, 0., 0.),
+            velocity: a * n * DVec3::new(0., inclination.cos(), inclination.sin()),
+        };
+        let acceleration = |position: DVec3, _velocity: DVec3| {
+            gravity(position, MU, &[]) + zonal_acceleration(position, MU, &harmonics, true, false)
+        };
+
+        // Whole orbits, so the node is looked at where it was at the start and the short
+        // periodic terms mostly drop out.
+        let orbits = 15.;
+        let steps = 15_000;
+        let h = orbits * period / steps as f64;
+        for _ in 0..steps {
+            state = rk4_step(state, h, acceleration);
+        }
+
+        let momentum = state.position.cross(state.velocity);
+        let node = momentum.x.atan2(-momentum.y);
+        let rate = node / (orbits * period);
+        let expected = -1.5 * n * harmonics.j2 * (harmonics.radius / a).powi(2) * inclination.cos();
+
+        assert!(
+            (rate - expected).abs() < 0.02 * expected.abs(),
+            "{rate} rad/s against {expected}"
+        );
+    }
+
+    #[test]
+    fn distant_third_bodies_pull_tidally() {
+        let position = DVec3::new(7000., 1000., -500.);
+        let (third_mu, third_position) = (4902.8, DVec3::new(0., 384400., 0.));
+        let tidal = gravity(position, MU, &[(third_mu, third_position)]) - gravity(position, MU, &[]);
+
+        let d = third_position.length();
+        let direction = third_position / d;
+        let expected = third_mu / d.powi(3) * (3. * position.dot(direction) * direction - position);
+
+        assert!(
+            (tidal - expected).length() < 0.05 * expected.length(),
+            "{tidal} against {expected}"
+        );
+    }
+
+    #[test]
+    fn drag_opposes_the_air_relative_velocity() {
+        let atmosphere = ExponentialAtmosphere::earth();
+        let position = DVec3::new(6378.1363 + 300., 0., 0.);
+        let velocity = DVec3::new(0., 7.7, 0.3);
+        let relative = velocity - (DVec3::Z * atmosphere.rotation_rate).cross(position);
+
+        let drag = drag_acceleration(position, velocity, DVec3::Z, &atmosphere, 0.022);
+
+        assert!(drag.length() > 0.);
+        assert!(drag.normalize().dot(-relative.normalize()) > 1. - 1e-12);
+        // Not simply against the inertial velocity, the air turns with the Earth.
+        assert!(drag.normalize().dot(-velocity.normalize()) < 1. - 1e-6);
+    }
+
+    #[test]
+    fn drag_scales_with_density() {
+        let atmosphere = ExponentialAtmosphere::earth();
+        let denser = ExponentialAtmosphere {
+            base_density: 2. * atmosphere.base_density,
+            ..atmosphere
+        };
+        let position = DVec3::new(0., 6378.1363 + 400., 0.);
+        let velocity = DVec3::new(-7.6, 0., 0.);
+
+        let drag = drag_acceleration(position, velocity, DVec3::Z, &atmosphere, 0.022);
+        let double = drag_acceleration(position, velocity, DVec3::Z, &denser, 0.022);
+
+        assert!((double - 2. * drag).length() < 1e-12 * drag.length());
+
+        // And with density down the profile. Over the pole, where the air doesn't move.
+        let polar = DVec3::new(0., 0., 6378.1363 + 400.);
+        let high = drag_acceleration(polar, velocity, DVec3::Z, &atmosphere, 0.022);
+        let low = drag_acceleration(0.99 * polar, velocity, DVec3::Z, &atmosphere, 0.022);
+        let ratio = atmosphere.density(0.99 * polar.length()) / atmosphere.density(polar.length());
+        assert!((low.length() / high.length() - ratio).abs() < 1e-12 * ratio);
+
+        // Nothing above the top.
+        let above = DVec3::new(6378.1363 + 1100., 0., 0.);
+        assert_eq!(
+            drag_acceleration(above, velocity, DVec3::Z, &atmosphere, 0.022),
+            DVec3::ZERO
+        );
+    }
+}
@@ -0,0 +1,15 @@
+use bevy::math::{DQuat, DVec3};
+use bevy::prelude::*;
+
+// The frames themselves and the conversions between them are in orbiter_physics::frames,
+// these hand the scene mapping to Bevy in single precision.
+
+// Converts a position in ICRF (KM) to world/scene coordinates.
+pub fn to_scene(position: DVec3) -> Vec3 {
+    orbiter_physics::frames::to_scene(position).as_vec3()
+}
+
+// Converts a rotation of ICRF axes to a scene rotation.
+pub fn to_scene_rotation(rotation: DQuat) -> Quat {
+    orbiter_physics::frames::to_scene_rotation(rotation).as_f32()
+}
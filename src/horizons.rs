@@ -0,0 +1,149 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashMap};
+
+use crate::forces::ForceModels;
+use crate::nbody::Propagation;
+use crate::orbit::{BodyRegistry, CelestialBody, OrbitalParameters, ParentBody};
+use orbiter_physics::horizons::{HorizonsElements, HorizonsError};
+
+// Loads JPL Horizons ELEMENTS exports saved under assets/horizons/. Every file adds
+// (or updates) the body named on its "Target body name" line, orbiting the body named
+// on its "Center body name" line. The format is read by orbiter_physics::horizons.
+pub struct HorizonsPlugin;
+
+impl Plugin for HorizonsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<HorizonsFile>()
+            .init_asset_loader::<HorizonsLoader>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, apply_horizons_elements);
+    }
+}
+
+// Keeps the loaded folder, and with it every elements file, alive.
+#[derive(Resource)]
+pub struct HorizonsFolder(pub Handle<bevy::asset::LoadedFolder>);
+
+// On a body whose elements came from a file, so reloading the scenario leaves them be.
+#[derive(Component)]
+pub struct FromHorizons;
+
+// One elements file.
+#[derive(Asset, TypePath, Debug, Clone, Deref)]
+pub struct HorizonsFile(pub HorizonsElements);
+
+#[derive(Default)]
+pub struct HorizonsLoader;
+
+impl AssetLoader for HorizonsLoader {
+    type Asset = HorizonsFile;
+    type Settings = ();
+    type Error = HorizonsError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<HorizonsFile, HorizonsError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            HorizonsElements::parse(&String::from_utf8_lossy(&bytes)).map(HorizonsFile)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["txt"]
+    }
+}
+
+pub fn setup(mut commands: Commands, ass: Res<AssetServer>) {
+    commands.insert_resource(HorizonsFolder(ass.load_folder("horizons")));
+}
+
+pub fn apply_horizons_elements(
+    mut events: EventReader<AssetEvent<HorizonsFile>>,
+    elements: Res<Assets<HorizonsFile>>,
+    registry: Res<BodyRegistry>,
+    body_query: Query<&OrbitalParameters>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Bodies spawned this frame aren't in the registry yet.
+    let mut spawned: HashMap<String, Entity> = HashMap::new();
+    let mut next_focus_idx = registry.iter().map(|body| body.focus_idx + 1).max().unwrap_or(0);
+
+    for event in events.read() {
+        let id = match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => *id,
+            _ => continue,
+        };
+
+        let Some(elements) = elements.get(id) else {
+            continue;
+        };
+
+        let mut orbit = elements.orbital_parameters();
+        let target = elements.target.to_lowercase();
+
+        let body = match registry.by_name(&target).or(spawned.get(&target).copied()) {
+            Some(body) => {
+                // Elements don't say anything about spin or how the orbit drifts, keep what
+                // the body already had.
+                if let Ok(existing) = body_query.get(body) {
+                    orbit.rotational_period = existing.rotational_period;
+                    orbit.longitude_asc_node_rate = existing.longitude_asc_node_rate;
+                    orbit.arg_of_periapsis_rate = existing.arg_of_periapsis_rate;
+                    orbit.inclination_rate = existing.inclination_rate;
+                    orbit.eccentricity_rate = existing.eccentricity_rate;
+                }
+                commands.entity(body).insert((orbit, FromHorizons));
+                body
+            }
+            None => {
+                let body = commands
+                    .spawn((
+                        PbrBundle {
+                            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                                radius: 5.,
+                                ..default()
+                            })),
+                            material: materials.add(Color::rgb(0.8, 0.8, 0.8).into()),
+                            ..default()
+                        },
+                        CelestialBody {
+                            name: elements.target.clone(),
+                            focus_idx: next_focus_idx,
+                            viewport_position: None,
+                        },
+                        orbit,
+                        FromHorizons,
+                        Propagation::default(),
+                        ForceModels::default(),
+                    ))
+                    .insert(Name::new(elements.target.clone()))
+                    .id();
+                next_focus_idx += 1;
+                spawned.insert(target, body);
+                body
+            }
+        };
+
+        let center = elements.center.to_lowercase();
+        match registry.by_name(&center).or(spawned.get(&center).copied()) {
+            Some(parent) => {
+                commands.entity(body).insert(ParentBody(parent));
+            }
+            None => println!(
+                "Center body {} for {} isn't in the scene.",
+                elements.center, elements.target
+            ),
+        }
+
+        println!("Loaded Horizons elements for {}.", elements.target);
+    }
+}
@@ -0,0 +1,295 @@
+use bevy::math::DVec3;
+use std::fmt;
+
+// Fixed and adaptive step integrators for a body's position and velocity. `acceleration`
+// gives the acceleration (KM/s^2) at a position and velocity, steps can be negative to run
+// backwards in time.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct State {
+    pub position: DVec3, // KM
+    pub velocity: DVec3, // KM/s
+}
+
+// Classic fourth order Runge-Kutta.
+pub fn rk4_step(state: State, h: f64, acceleration: impl Fn(DVec3, DVec3) -> DVec3) -> State {
+    let (r, v) = (state.position, state.velocity);
+
+    let k1_r = v;
+    let k1_v = acceleration(r, v);
+    let k2_r = v + k1_v * h / 2.;
+    let k2_v = acceleration(r + k1_r * h / 2., k2_r);
+    let k3_r = v + k2_v * h / 2.;
+    let k3_v = acceleration(r + k2_r * h / 2., k3_r);
+    let k4_r = v + k3_v * h;
+    let k4_v = acceleration(r + k3_r * h, k4_r);
+
+    State {
+        position: r + (k1_r + 2. * k2_r + 2. * k3_r + k4_r) * h / 6.,
+        velocity: v + (k1_v + 2. * k2_v + 2. * k3_v + k4_v) * h / 6.,
+    }
+}
+
+// Kick-drift-kick leapfrog, second order and symplectic for forces that only depend on
+// position.
+pub fn leapfrog_step(state: State, h: f64, acceleration: impl Fn(DVec3, DVec3) -> DVec3) -> State {
+    let half_kick = state.velocity + acceleration(state.position, state.velocity) * h / 2.;
+    let position = state.position + half_kick * h;
+    let velocity = half_kick + acceleration(position, half_kick) * h / 2.;
+
+    State { position, velocity }
+}
+
+// Yoshida's fourth order symplectic integrator, three leapfrog-like stages.
+pub fn yoshida_step(state: State, h: f64, acceleration: impl Fn(DVec3, DVec3) -> DVec3) -> State {
+    let cbrt2 = 2f64.cbrt();
+    let w1 = 1. / (2. - cbrt2);
+    let w0 = -cbrt2 / (2. - cbrt2);
+
+    let drifts = [w1 / 2., (w0 + w1) / 2., (w0 + w1) / 2., w1 / 2.];
+    let kicks = [w1, w0, w1];
+
+    let (mut r, mut v) = (state.position, state.velocity);
+
+    for stage in 0..3 {
+        r += v * drifts[stage] * h;
+        v += acceleration(r, v) * kicks[stage] * h;
+    }
+    r += v * drifts[3] * h;
+
+    State { position: r, velocity: v }
+}
+
+// Dormand-Prince 5(4) coefficients. Gravity doesn't depend on time, so the nodes aren't
+// needed.
+const DP_A: [[f64; 6]; 7] = [
+    [0., 0., 0., 0., 0., 0.],
+    [1. / 5., 0., 0., 0., 0., 0.],
+    [3. / 40., 9. / 40., 0., 0., 0., 0.],
+    [44. / 45., -56. / 15., 32. / 9., 0., 0., 0.],
+    [19372. / 6561., -25360. / 2187., 64448. / 6561., -212. / 729., 0., 0.],
+    [9017. / 3168., -355. / 33., 46732. / 5247., 49. / 176., -5103. / 18656., 0.],
+    [35. / 384., 0., 500. / 1113., 125. / 192., -2187. / 6784., 11. / 84.],
+];
+// Fifth order weights are the last row of DP_A, these are the embedded fourth order ones.
+const DP_B4: [f64; 7] = [
+    5179. / 57600.,
+    0.,
+    7571. / 16695.,
+    393. / 640.,
+    -92097. / 339200.,
+    187. / 2100.,
+    1. / 40.,
+];
+
+const RK45_MAX_STEPS: u32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntegrationError {
+    // RK45 used up its steps with this many seconds still to go.
+    StepLimit { remaining: f64 },
+}
+
+impl fmt::Display for IntegrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrationError::StepLimit { remaining } => write!(
+                f,
+                "RK45 gave up after {} steps with {:.3} s left, the tolerance is too tight",
+                RK45_MAX_STEPS, remaining
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IntegrationError {}
+
+// Adaptive Dormand-Prince over a span of `h` seconds. `step` carries the step size between
+// calls and `tolerance` is the allowed error per step relative to the size of the position
+// and velocity. Returns the state and how many internal steps it took.
+pub fn rk45_integrate(
+    state: State,
+    h: f64,
+    step: &mut f64,
+    tolerance: f64,
+    acceleration: impl Fn(DVec3, DVec3) -> DVec3,
+) -> Result<(State, u32), IntegrationError> {
+    let direction = h.signum();
+    let mut remaining = h.abs();
+    let mut current = state;
+    let mut steps = 0;
+
+    if *step <= 0. || !step.is_finite() {
+        *step = remaining;
+    }
+
+    while remaining > 0. && steps < RK45_MAX_STEPS {
+        let dt = step.min(remaining);
+        let (next, embedded) = dormand_prince_step(current, direction * dt, &acceleration);
+        steps += 1;
+
+        let position_ratio = (next.position - embedded.position).length()
+            / (tolerance * (1. + next.position.length()));
+        let velocity_ratio = (next.velocity - embedded.velocity).length()
+            / (tolerance * (1. + next.velocity.length()));
+        let ratio = position_ratio.max(velocity_ratio);
+
+        if ratio <= 1. {
+            current = next;
+            remaining -= dt;
+        }
+
+        // Usual controller, with a safety factor and limits on how fast the step changes.
+        let factor = if ratio == 0. { 5. } else { (0.9 * ratio.powf(-0.2)).clamp(0.2, 5.) };
+        *step = dt * factor;
+    }
+
+    if remaining > 0. {
+        return Err(IntegrationError::StepLimit { remaining });
+    }
+
+    Ok((current, steps))
+}
+
+// One step, returning the fifth order result and the embedded fourth order one.
+fn dormand_prince_step(state: State, h: f64, acceleration: &impl Fn(DVec3, DVec3) -> DVec3) -> (State, State) {
+    let mut k_r = [DVec3::ZERO; 7];
+    let mut k_v = [DVec3::ZERO; 7];
+
+    for stage in 0..7 {
+        let mut r = state.position;
+        let mut v = state.velocity;
+        for prev in 0..stage {
+            r += k_r[prev] * DP_A[stage][prev] * h;
+            v += k_v[prev] * DP_A[stage][prev] * h;
+        }
+        k_r[stage] = v;
+        k_v[stage] = acceleration(r, v);
+    }
+
+    let mut fifth = state;
+    let mut fourth = state;
+    for stage in 0..7 {
+        let b5 = if stage < 6 { DP_A[6][stage] } else { 0. };
+        fifth.position += k_r[stage] * b5 * h;
+        fifth.velocity += k_v[stage] * b5 * h;
+        fourth.position += k_r[stage] * DP_B4[stage] * h;
+        fourth.velocity += k_v[stage] * DP_B4[stage] * h;
+    }
+
+    (fifth, fourth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orbiter_physics::OrbitalParameters;
+    use std::f64::consts::PI;
+
+    const MU: f64 = 398600.4418;
+
+    type Step = fn(State, f64) -> State;
+
+    fn gravity(position: DVec3, _velocity: DVec3) -> DVec3 {
+        -MU * position / position.length().powi(3)
+    }
+
+    fn energy(state: State) -> f64 {
+        state.velocity.length_squared() / 2. - MU / state.position.length()
+    }
+
+    // 7000 KM circular, a little over 97 minutes round.
+    fn circular() -> (State, f64) {
+        let radius = 7000.;
+        let speed = (MU / radius).sqrt();
+        let state = State {
+            position: DVec3::new(radius, 0., 0.),
+            velocity: DVec3::new(0., speed, 0.),
+        };
+
+        (state, 2. * PI * radius / speed)
+    }
+
+    #[test]
+    fn fixed_step_integrators_close_a_circular_orbit() {
+        let (start, period) = circular();
+        let steps = 600;
+        let h = period / steps as f64;
+
+        let integrators: [(&str, Step, f64, f64); 3] = [
+            ("rk4", |state, h| rk4_step(state, h, gravity), 1e-4, 1e-10),
+            ("leapfrog", |state, h| leapfrog_step(state, h, gravity), 3., 1e-10),
+            ("yoshida", |state, h| yoshida_step(state, h, gravity), 1e-3, 1e-12),
+        ];
+
+        for (name, step, position_tolerance, energy_tolerance) in integrators {
+            let mut state = start;
+            for _ in 0..steps {
+                state = step(state, h);
+            }
+
+            let position_error = (state.position - start.position).length();
+            let energy_error = ((energy(state) - energy(start)) / energy(start)).abs();
+            assert!(position_error < position_tolerance, "{}: {} KM off", name, position_error);
+            assert!(energy_error < energy_tolerance, "{}: energy off by {}", name, energy_error);
+        }
+    }
+
+    #[test]
+    fn rk45_closes_a_circular_orbit() {
+        let (start, period) = circular();
+        let mut step = 0.;
+
+        let (end, steps) = rk45_integrate(start, period, &mut step, 1e-12, gravity).unwrap();
+
+        assert!(steps > 1);
+        assert!((end.position - start.position).length() < 1e-4);
+        assert!(((energy(end) - energy(start)) / energy(start)).abs() < 1e-11);
+    }
+
+    // Against the Kepler solution on an eccentric orbit, forwards and back, at a few
+    // tolerances. The global error grows with the number of steps, a hundred times the
+    // per-step tolerance leaves room for that.
+    #[test]
+    fn rk45_meets_its_tolerance() {
+        let orbit = OrbitalParameters {
+            semimajor_axis: 26600.,
+            eccentricity: 0.7,
+            inclination: 1.1,
+            grav_parameter: MU,
+            ..Default::default()
+        };
+        let (position, velocity) = orbit.state_vector(0.).unwrap();
+        let start = State { position, velocity };
+        let span = 0.8 * 2. * PI * (orbit.semimajor_axis.powi(3) / MU).sqrt();
+
+        for tolerance in [1e-8, 1e-10, 1e-12] {
+            for span in [span, -span] {
+                let mut step = 0.;
+                let (end, steps) = rk45_integrate(start, span, &mut step, tolerance, gravity).unwrap();
+                let (expected, expected_velocity) = orbit.state_vector(span).unwrap();
+
+                let error = (end.position - expected).length() / (1. + expected.length());
+                let velocity_error =
+                    (end.velocity - expected_velocity).length() / (1. + expected_velocity.length());
+                assert!(
+                    error < 100. * tolerance && velocity_error < 100. * tolerance,
+                    "tolerance {}: {} and {} after {} steps",
+                    tolerance,
+                    error,
+                    velocity_error,
+                    steps
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rk45_reports_running_out_of_steps() {
+        let (start, period) = circular();
+        let mut step = 0.;
+
+        let result = rk45_integrate(start, period, &mut step, 1e-30, gravity);
+        assert!(matches!(result, Err(IntegrationError::StepLimit { remaining }) if remaining > 0.));
+    }
+}
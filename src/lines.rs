@@ -11,8 +11,11 @@ use bevy::{
     },
 };
 
-#[derive(Resource, Component)]
-pub struct OrbitalLines;
+// The orbit line strip drawn for `body`.
+#[derive(Component)]
+pub struct OrbitalLines {
+    pub body: Entity,
+}
 
 /// A list of lines with a start and end position
 #[derive(Debug, Clone)]
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+
+use crate::nbody::Propagation;
+use crate::orbit::{propagate_orbits, MoonBody, OrbitSet, RelativePosition, RelativeVelocity};
+use crate::time::{PhysicsStep, PhysicsTime};
+use orbiter_physics::lunar_theory::geocentric_state;
+use orbiter_physics::time_scale::TimeScale;
+
+// Places the Moon from the lunar theory in orbiter_physics::lunar_theory (Meeus,
+// Astronomical Algorithms ch. 47) rather than a single ellipse, which is off by thousands
+// of km within a month.
+//
+// With `LunarModel::Meeus` selected this places the Moon each sub-step instead of its
+// Kepler orbit, in the same frame (ICRF, relative to Earth) that `lunar_orbit` is
+// converted to. propagate_orbits leaves it alone then, and its orbit line is drawn from
+// the osculating elements of the series' state.
+pub struct LunarTheoryPlugin;
+
+impl Plugin for LunarTheoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LunarTheorySettings::default())
+            .add_systems(
+                PhysicsStep,
+                propagate_moon
+                    .in_set(OrbitSet::Propagate)
+                    .after(propagate_orbits),
+            )
+            .register_type::<LunarTheorySettings>();
+    }
+}
+
+#[derive(Reflect, Default, Debug, Clone, Copy, PartialEq)]
+pub enum LunarModel {
+    Elements, // The osculating ellipse, with its secular rates
+    #[default]
+    Meeus,
+}
+
+#[derive(Reflect, Resource, InspectorOptions, Default)]
+#[reflect(Resource, InspectorOptions)]
+pub struct LunarTheorySettings {
+    pub model: LunarModel,
+}
+
+impl LunarTheorySettings {
+    // Whether the series, rather than its elements, places a body. Numerically propagated,
+    // the series only applies to the analytic solution.
+    pub fn places(&self, moon: bool, propagation: Option<&Propagation>) -> bool {
+        self.model == LunarModel::Meeus
+            && moon
+            && !propagation.is_some_and(|propagation| *propagation != Propagation::Analytic)
+    }
+}
+
+pub fn propagate_moon(
+    settings: Res<LunarTheorySettings>,
+    mut commands: Commands,
+    mut moon_query: Query<
+        (
+            Entity,
+            Option<&Propagation>,
+            Option<&mut RelativePosition>,
+            Option<&mut RelativeVelocity>,
+        ),
+        With<MoonBody>,
+    >,
+    physics_time_q: Query<&PhysicsTime>,
+) {
+    if settings.model != LunarModel::Meeus {
+        return;
+    }
+
+    let t = physics_time_q.single().seconds(TimeScale::Tdb);
+
+    let (posn, vel) = geocentric_state(t);
+
+    for (entity, propagation, relative, velocity) in &mut moon_query {
+        if !settings.places(true, propagation) {
+            continue;
+        }
+
+        match (relative, velocity) {
+            (Some(mut relative), Some(mut velocity)) => {
+                relative.0 = posn;
+                velocity.0 = vel;
+            }
+            _ => {
+                commands
+                    .entity(entity)
+                    .insert((RelativePosition(posn), RelativeVelocity(vel)));
+            }
+        }
+    }
+}
@@ -1,151 +1,82 @@
 use atmosphere::AtmosphereSettings;
 use bevy::prelude::*;
 
-use bevy::pbr::{CascadeShadowConfigBuilder, NotShadowCaster, NotShadowReceiver};
 use bevy::render::camera::CameraProjection;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use earth_orientation::EarthOrientationPlugin;
+use ephemeris::EphemerisPlugin;
+use floating_origin::FloatingOriginPlugin;
+use forces::ForcesPlugin;
+use horizons::HorizonsPlugin;
+use lunar_theory::LunarTheoryPlugin;
+use moon_orientation::MoonOrientationPlugin;
+use nbody::NBodyPlugin;
 use orbit::OrbitPlugin;
+use satellites::SatellitePlugin;
+use scenario::ScenarioPlugin;
+use snapshot::SnapshotPlugin;
 use sphere_camera::SphericalCameraPlugin;
+use sun::SunPlugin;
 use time::PhysicsTimePlugin;
+use time_controls::TimeControlsPlugin;
 use topocentric_camera::TopoCentricCameraPlugin;
-use bevy::{
-    core_pipeline::prepass::{DepthPrepass},
-};
 
+mod earth_orientation;
+mod ephemeris;
+mod floating_origin;
+mod forces;
+mod frames;
+mod horizons;
+mod integrators;
 mod lines;
+mod lunar_theory;
+mod moon_orientation;
+mod nbody;
 mod orbit;
+mod satellites;
+mod scenario;
+mod sgp4;
+mod snapshot;
 mod sphere_camera;
+mod sun;
 mod topocentric_camera;
 mod atmosphere;
 mod time;
+mod time_controls;
 
 fn main() {
     App::new()
         .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
+        .insert_resource(time::start_epoch_from_args())
         .add_plugins(DefaultPlugins) 
-        .add_systems(Startup, setup)
         .add_systems(Update, sync_data_to_atmosphere_settings)
         .add_plugins(TopoCentricCameraPlugin)
         .add_plugins((WorldInspectorPlugin::new(), SphericalCameraPlugin))
         .add_plugins(OrbitPlugin)
+        .add_plugins(ScenarioPlugin)
+        .add_plugins(FloatingOriginPlugin)
+        .add_plugins(NBodyPlugin)
+        .add_plugins(ForcesPlugin)
+        .add_plugins(EarthOrientationPlugin)
+        .add_plugins(HorizonsPlugin)
+        .add_plugins(SatellitePlugin)
+        .add_plugins(SunPlugin)
+        .add_plugins(LunarTheoryPlugin)
+        .add_plugins(MoonOrientationPlugin)
+        .add_plugins(EphemerisPlugin)
         .add_plugins(PhysicsTimePlugin)
+        .add_plugins(TimeControlsPlugin)
+        .add_plugins(SnapshotPlugin)
         .add_plugins(atmosphere::PostProcessPlugin)
         .register_type::<atmosphere::AtmosphereSettings>()
         .run();
 }
 
-fn setup(mut commands: Commands, ass: Res<AssetServer>) {
-    let earth_handle = ass.load("earth_updated.glb#Scene0");
-    let skybox_handle = ass.load("sky_actual_constel.glb#Scene0");
-    let moon_handle = ass.load("moon.glb#Scene0");
-
-    // Earth
-    commands.spawn((
-        SceneBundle {
-            scene: earth_handle,
-            transform: Transform::from_xyz(0.0, 0.0, 0.0),
-            ..Default::default()
-        },
-        orbit::EarthBody,
-    ));
-
-    // Sphere Camera
-    commands.spawn(sphere_camera::SphereCamera {
-        radius: 600.,
-        ..Default::default()
-    });
-
-    // Altitude Azimuth Camera Controls
-    commands.spawn(topocentric_camera::AltitudeAzimuthCamera {
-        altitude: 0.,
-        azimuth: 0.,
-        roll: 0.,
-    });
-
-    // 3D Camera
-    commands.spawn((
-        Camera3dBundle {
-            transform: Transform::from_xyz(0., 20., 44.).looking_at(Vec3::Y, Vec3::Y),
-            ..default()
-        },
-        NotShadowCaster,
-        // Add the setting to the camera.
-        // This component is also used to determine on which camera to run the post processing effect.
-        atmosphere::AtmosphereSettings {
-            planetPosition: Vec3::new(0.,0.,0.),
-            planetRadius: 1000.,
-            atmosphereRadius: 10.,
-            falloffFactor: 15.,
-            sunIntensity: 15.,
-            scatteringStrength: 1.,
-            densityModifier: 10.,
-            redWaveLength: 700.,
-            greenWaveLength: 530.,
-            blueWaveLength: 440.,
-            sunPosition: Vec3::new(5000.0, 4.0, 0.0),
-            cameraPosition: Vec3::new(1000.,0.,0.),
-            inverseProjection: Mat4::IDENTITY,
-            inverseView: Mat4::IDENTITY,
-            cameraFar: 0.,
-            cameraNear: 0.,
-        },
-        // To enable the prepass you need to add the components associated with the ones you need
-        // This will write the depth buffer to a texture that you can use in the main pass
-        DepthPrepass,
-        // This will generate a texture containing world normals (with normal maps applied)
-        //NormalPrepass,
-        // This will generate a texture containing screen space pixel motion vectors
-        //MotionVectorPrepass,
-    ));
-
-    commands.spawn(PointLightBundle {
-        // transform: Transform::from_xyz(5.0, 8.0, 2.0),
-        transform: Transform::from_xyz(50000.0, 0.0, 0.0),
-        point_light: PointLight {
-            range: 100000.,
-            intensity: 999999995904.0, // lumens - roughly a 100W non-halogen incandescent bulb
-            color: Color::WHITE,
-            shadows_enabled: true,
-            ..default()
-        },
-        ..default()
-    });
-
-    // Skybox
-    commands
-        .spawn((
-            SceneBundle {
-                scene: skybox_handle,
-                transform: Transform::from_xyz(0., 0., 0.).with_scale(Vec3::new(400.,400.,400.)),
-                ..default()
-            },
-            NotShadowCaster,
-        ))
-        .insert(Name::new("Sky"));
-
-    // Moon
-    commands
-        .spawn((
-            SceneBundle {
-                scene: moon_handle.clone(),
-                transform: Transform::from_xyz(0.0, orbit::REAL_TO_WORLD * 384472.282, 0.0)
-                    .with_rotation(Quat::from_rotation_x(std::f32::consts::PI / 2.)),
-                ..Default::default()
-            },
-            orbit::CelestialBody {
-                name: "Moon".to_string(),
-                focus_idx: 1,
-                viewport_position: None,
-            },
-            orbit::MoonBody,
-        ))
-        .insert(Name::new("Moon"));
-}
-
 pub fn sync_data_to_atmosphere_settings(
     mut camera_q: Query<&mut GlobalTransform, With<Camera3d>>,
     mut projection_q: Query<&mut Projection>,
     mut atmosphere_q: Query<&mut AtmosphereSettings>,
+    earth_q: Query<&GlobalTransform, (With<orbit::EarthBody>, Without<Camera3d>)>,
 ) {
     let mut atmosphere = match atmosphere_q.get_single_mut() {
         Ok(atmosphere) => atmosphere,
@@ -167,4 +98,9 @@ pub fn sync_data_to_atmosphere_settings(
     atmosphere.inverseProjection = projection.get_projection_matrix().inverse();
     atmosphere.inverseView = camera.compute_matrix().inverse();
     atmosphere.cameraPosition = camera.translation();
+
+    // Earth is only at the origin while the camera is anchored to it.
+    if let Ok(earth) = earth_q.get_single() {
+        atmosphere.planetPosition = earth.translation();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,174 @@
+use bevy::math::{DQuat, DVec3};
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+
+use crate::frames::to_scene_rotation;
+use crate::orbit::{MoonBody, OrbitSet};
+use crate::time::PhysicsTime;
+use orbiter_physics::time_scale::TimeScale;
+
+// The Moon's orientation from the IAU rotation model (Archinal et al. 2011, the WGCCRE
+// report): the direction of its pole and the angle of its prime meridian, worked out from
+// the clock every frame. The prime meridian is the mean sub-Earth point, so the near side
+// faces Earth, and since the Moon spins evenly while it moves round an ellipse the optical
+// libration shows up on its own. The periodic terms add the physical libration.
+pub struct MoonOrientationPlugin;
+
+impl Plugin for MoonOrientationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MoonOrientationSettings::default())
+            .add_systems(Update, orient_moon.in_set(OrbitSet::Place))
+            .register_type::<MoonOrientationSettings>();
+    }
+}
+
+const SECONDS_PER_DAY: f64 = 86400.;
+const DAYS_PER_CENTURY: f64 = 36525.;
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct MoonOrientationSettings {
+    pub physical_libration: bool,   // Off leaves the mean pole and an even spin
+    pub prime_meridian_offset: f64, // Radians, lines the model's texture up with the near side
+}
+
+impl Default for MoonOrientationSettings {
+    fn default() -> Self {
+        MoonOrientationSettings {
+            physical_libration: true,
+            prime_meridian_offset: 0.,
+        }
+    }
+}
+
+// Right ascension and declination of the north pole and the prime meridian angle W, in
+// radians, `t` TDB seconds past J2000.
+pub fn pole_and_prime_meridian(t: f64, physical_libration: bool) -> (f64, f64, f64) {
+    let d = t / SECONDS_PER_DAY;
+    let c = d / DAYS_PER_CENTURY;
+
+    let mut right_ascension = 269.9949 + 0.0031 * c;
+    let mut declination = 66.5392 + 0.0130 * c;
+    let mut prime_meridian = 38.3213 + 13.17635815 * d - 1.4e-12 * d * d;
+
+    if physical_libration {
+        let e = |base: f64, rate: f64| (base + rate * d).to_radians();
+        let e1 = e(125.045, -0.0529921);
+        let e2 = e(250.089, -0.1059842);
+        let e3 = e(260.008, 13.0120009);
+        let e4 = e(176.625, 13.3407154);
+        let e5 = e(357.529, 0.9856003);
+        let e6 = e(311.589, 26.4057084);
+        let e7 = e(134.963, 13.0649930);
+        let e8 = e(276.617, 0.3287146);
+        let e9 = e(34.226, 1.7484877);
+        let e10 = e(15.134, -0.1589763);
+        let e11 = e(119.743, 0.0036096);
+        let e12 = e(239.961, 0.1643573);
+        let e13 = e(25.053, 12.9590088);
+
+        right_ascension += -3.8787 * e1.sin() - 0.1204 * e2.sin() + 0.0700 * e3.sin()
+            - 0.0172 * e4.sin()
+            + 0.0072 * e6.sin()
+            - 0.0052 * e10.sin()
+            + 0.0043 * e13.sin();
+        declination += 1.5419 * e1.cos() + 0.0239 * e2.cos() - 0.0278 * e3.cos()
+            + 0.0068 * e4.cos()
+            - 0.0029 * e6.cos()
+            + 0.0009 * e7.cos()
+            + 0.0008 * e10.cos()
+            - 0.0009 * e13.cos();
+        prime_meridian += 3.5610 * e1.sin() + 0.1208 * e2.sin() - 0.0642 * e3.sin()
+            + 0.0158 * e4.sin()
+            + 0.0252 * e5.sin()
+            - 0.0066 * e6.sin()
+            - 0.0047 * e7.sin()
+            - 0.0046 * e8.sin()
+            + 0.0028 * e9.sin()
+            + 0.0052 * e10.sin()
+            + 0.0040 * e11.sin()
+            + 0.0019 * e12.sin()
+            - 0.0044 * e13.sin();
+    }
+
+    (
+        right_ascension.to_radians(),
+        declination.to_radians(),
+        prime_meridian.rem_euclid(360.).to_radians(),
+    )
+}
+
+// Rotation from the Moon's body-fixed frame (x through the prime meridian, z through the
+// north pole) to ICRF.
+pub fn moon_orientation(t: f64, physical_libration: bool) -> DQuat {
+    let (right_ascension, declination, prime_meridian) =
+        pole_and_prime_meridian(t, physical_libration);
+
+    DQuat::from_rotation_z(right_ascension + std::f64::consts::FRAC_PI_2)
+        * DQuat::from_rotation_x(std::f64::consts::FRAC_PI_2 - declination)
+        * DQuat::from_rotation_z(prime_meridian)
+}
+
+pub fn orient_moon(
+    settings: Res<MoonOrientationSettings>,
+    mut query: Query<&mut Transform, With<MoonBody>>,
+    physics_time_q: Query<&PhysicsTime>,
+) {
+    let physics_time = physics_time_q.single();
+
+    let orientation = moon_orientation(
+        physics_time.seconds(TimeScale::Tdb),
+        settings.physical_libration,
+    ) * DQuat::from_axis_angle(DVec3::Z, settings.prime_meridian_offset);
+
+    for mut transform in &mut query {
+        transform.rotation = to_scene_rotation(orientation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orbiter_physics::lunar_theory::geocentric_position;
+
+    #[test]
+    fn matches_the_wgccre_model_at_j2000() {
+        // Archinal et al. 2011, table 2, evaluated at d = 0.
+        let (right_ascension, declination, prime_meridian) = pole_and_prime_meridian(0., false);
+        assert!((right_ascension.to_degrees() - 269.9949).abs() < 1e-9);
+        assert!((declination.to_degrees() - 66.5392).abs() < 1e-9);
+        assert!((prime_meridian.to_degrees() - 38.3213).abs() < 1e-9);
+
+        // With the periodic terms, the table's series summed separately at d = 0.
+        let (right_ascension, declination, prime_meridian) = pole_and_prime_meridian(0., true);
+        assert!((right_ascension.to_degrees() - 266.857733).abs() < 1e-5);
+        assert!((declination.to_degrees() - 65.641103).abs() < 1e-5);
+        assert!((prime_meridian.to_degrees() - 41.195264).abs() < 1e-5);
+    }
+
+    #[test]
+    fn pole_is_where_the_model_puts_it() {
+        let t = 765_000_000.;
+        let (right_ascension, declination, _) = pole_and_prime_meridian(t, true);
+        let pole = moon_orientation(t, true) * DVec3::Z;
+
+        assert!((pole.z.asin() - declination).abs() < 1e-12);
+        let pole_right_ascension = pole.y.atan2(pole.x).rem_euclid(2. * std::f64::consts::PI);
+        assert!((pole_right_ascension - right_ascension).abs() < 1e-12);
+    }
+
+    #[test]
+    fn near_side_faces_earth() {
+        // Every six hours over a year. Libration swings the sub-Earth point about the body's
+        // +x axis by up to 7.9° in longitude and 6.9° in latitude.
+        for step in 0..4 * 365 {
+            let t = 765_000_000. + step as f64 * 6. * 3600.;
+            let to_earth = moon_orientation(t, true).inverse() * -geocentric_position(t).normalize();
+
+            let longitude = to_earth.y.atan2(to_earth.x).to_degrees();
+            let latitude = to_earth.z.asin().to_degrees();
+            assert!(longitude.abs() < 8.2, "{}° of longitude at {}", longitude, t);
+            assert!(latitude.abs() < 7.2, "{}° of latitude at {}", latitude, t);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+
+use crate::forces::{
+    drag_acceleration, zonal_acceleration, ExponentialAtmosphere, ForceModels, ZonalHarmonics,
+};
+use crate::integrators::{self, State};
+use crate::orbit::{
+    BodyRegistry, GravParameter, OrbitSet, OrbitalParameters, ParentBody, PropagationError,
+    PropagationFailed, RelativePosition, RelativeVelocity, SimulationPosition,
+};
+use crate::satellites::Satellite;
+use crate::time::{PhysicsStep, PhysicsTime};
+use orbiter_physics::time_scale::TimeScale;
+
+// Numerical propagation as an alternative to the analytic solution, the Kepler orbit or
+// SGP4 for satellites. A body with a `Propagation` other than Analytic has its state
+// relative to its parent integrated every physics sub-step, under its parent's gravity
+// plus whatever its `ForceModels` turn on (see forces.rs). Other bodies pulling on it are
+// held where they were at the start of the sub-step.
+//
+// `NumericalState` keeps the energy drift and the distance from the analytic solution, so
+// the two can be compared in the inspector and in the label at the bottom left. With only
+// gravity on the drift shows integrator error, J2 and drag make it real.
+pub struct NBodyPlugin;
+
+impl Plugin for NBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(NBodySettings::default())
+            .add_systems(Startup, setup)
+            .add_systems(PhysicsStep, integrate_bodies.in_set(OrbitSet::Propagate))
+            .add_systems(Update, draw_integrator_diagnostics)
+            .register_type::<Propagation>()
+            .register_type::<NumericalState>()
+            .register_type::<NBodySettings>();
+    }
+}
+
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub enum Propagation {
+    #[default]
+    Analytic,
+    Rk4,
+    Rk45,
+    Leapfrog,
+    Yoshida,
+}
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct NBodySettings {
+    pub max_step_seconds: f64, // Longest fixed-step integrator step inside a sub-step
+    pub rk45_tolerance: f64,   // Relative error per RK45 step
+}
+
+impl Default for NBodySettings {
+    fn default() -> Self {
+        NBodySettings {
+            max_step_seconds: 60.,
+            rk45_tolerance: 1e-12,
+        }
+    }
+}
+
+// The integrated state of a body relative to its parent, in ICRF.
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct NumericalState {
+    pub position: DVec3,         // KM
+    pub velocity: DVec3,         // KM/s
+    pub time: f64,               // TDB seconds past J2000 the state is at
+    pub initial_energy: f64,     // KM^2/s^2, two-body specific orbital energy at the start
+    pub energy_drift: f64,       // (E - E0) / |E0|
+    pub analytic_deviation: f64, // KM from where the analytic solution has the body
+    pub rk45_step: f64,          // Seconds, carried between sub-steps
+}
+
+#[derive(Component)]
+pub struct IntegratorDiagnosticsLabel;
+
+pub fn specific_energy(position: DVec3, velocity: DVec3, mu: f64) -> f64 {
+    velocity.length_squared() / 2. - mu / position.length()
+}
+
+// Point mass gravity of the parent plus the tidal pull of other bodies. `third_bodies` are
+// (GM, position relative to the parent) pairs.
+pub fn gravity(position: DVec3, mu: f64, third_bodies: &[(f64, DVec3)]) -> DVec3 {
+    let mut acceleration = -mu * position / position.length().powi(3);
+
+    for (third_mu, third_position) in third_bodies {
+        let to_third = *third_position - position;
+        acceleration += *third_mu
+            * (to_third / to_third.length().powi(3) - *third_position / third_position.length().powi(3));
+    }
+
+    acceleration
+}
+
+pub fn setup(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        IntegratorDiagnosticsLabel,
+    ));
+}
+
+pub fn integrate_bodies(
+    mut commands: Commands,
+    settings: Res<NBodySettings>,
+    physics_time_q: Query<&PhysicsTime>,
+    mut body_query: Query<(
+        Entity,
+        &Propagation,
+        &ParentBody,
+        Option<&ForceModels>,
+        Option<&OrbitalParameters>,
+        Option<&Satellite>,
+        Option<&mut NumericalState>,
+        Option<&mut RelativePosition>,
+        Option<&mut RelativeVelocity>,
+    )>,
+    attractor_query: Query<(Entity, &GravParameter, &SimulationPosition)>,
+    central_query: Query<(Option<&ZonalHarmonics>, Option<&ExponentialAtmosphere>)>,
+    mut failures: EventWriter<PropagationFailed>,
+) {
+    let physics_time = physics_time_q.single();
+    let t = physics_time.seconds(TimeScale::Tdb);
+    let h = physics_time.delta_seconds;
+
+    for (entity, propagation, parent, forces, orbit, satellite, numerical, relative, velocity) in
+        &mut body_query
+    {
+        if *propagation == Propagation::Analytic {
+            if numerical.is_some() {
+                commands.entity(entity).remove::<NumericalState>();
+            }
+            continue;
+        }
+
+        let Some(mu) = orbit.map(|orbit| orbit.grav_parameter).or_else(|| {
+            attractor_query
+                .get(parent.0)
+                .ok()
+                .map(|(_, mu, _)| mu.0)
+        }) else {
+            continue;
+        };
+
+        // Start from the analytic state, and again whenever the clock has been moved by
+        // something other than stepping, e.g. a jump to another date.
+        let mut state = match numerical {
+            Some(state) if (state.time + h - t).abs() <= 1e-6 * (1. + h.abs()) => state,
+            existing => {
+                let Some((posn, vel)) = analytic_state(orbit, satellite, physics_time) else {
+                    continue;
+                };
+                let fresh = NumericalState {
+                    position: posn,
+                    velocity: vel,
+                    time: t,
+                    initial_energy: specific_energy(posn, vel, mu),
+                    ..default()
+                };
+
+                match existing {
+                    Some(mut existing) => {
+                        *existing = fresh;
+                        existing
+                    }
+                    None => {
+                        commands.entity(entity).insert(fresh);
+                        write_state(&mut commands, entity, relative, velocity, posn, vel);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if state.time != t {
+            let forces = forces.copied().unwrap_or_default();
+
+            let third_bodies: Vec<(f64, DVec3)> = if forces.third_body {
+                let parent_position = attractor_query
+                    .iter()
+                    .find(|(attractor, _, _)| *attractor == parent.0)
+                    .map(|(_, _, position)| position.0);
+
+                match parent_position {
+                    Some(parent_position) => attractor_query
+                        .iter()
+                        .filter(|(attractor, _, _)| *attractor != entity && *attractor != parent.0)
+                        .map(|(_, mu, position)| (mu.0, position.0 - parent_position))
+                        .collect(),
+                    None => Vec::new(),
+                }
+            } else {
+                Vec::new()
+            };
+
+            let (harmonics, atmosphere) = central_query.get(parent.0).unwrap_or((None, None));
+            let harmonics = harmonics.filter(|_| forces.j2 || forces.j3);
+            let atmosphere = atmosphere.filter(|_| forces.drag);
+            let pole = harmonics.map_or(DVec3::Z, |harmonics| harmonics.pole);
+
+            let acceleration = |position: DVec3, velocity: DVec3| {
+                let mut total = gravity(position, mu, &third_bodies);
+
+                if let Some(harmonics) = harmonics {
+                    total += zonal_acceleration(position, mu, harmonics, forces.j2, forces.j3);
+                }
+
+                if let Some(atmosphere) = atmosphere {
+                    total += drag_acceleration(
+                        position,
+                        velocity,
+                        pole,
+                        atmosphere,
+                        forces.ballistic_coefficient,
+                    );
+                }
+
+                total
+            };
+            let start = State {
+                position: state.position,
+                velocity: state.velocity,
+            };
+
+            let end = match *propagation {
+                Propagation::Rk45 => match integrators::rk45_integrate(
+                    start,
+                    h,
+                    &mut state.rk45_step,
+                    settings.rk45_tolerance,
+                    acceleration,
+                ) {
+                    Ok((end, _)) => end,
+                    // Left where it was, at the time it got to last. The clock has moved
+                    // on, so the next sub-step starts over from the analytic state.
+                    Err(err) => {
+                        failures.send(PropagationFailed {
+                            body: entity,
+                            error: PropagationError::Integration(err),
+                        });
+                        continue;
+                    }
+                },
+                fixed => {
+                    // Sub-steps can be hours long at high warp, split them to keep the
+                    // fixed-step integrators stable.
+                    let steps = (h.abs() / settings.max_step_seconds.max(1e-3)).ceil().max(1.);
+                    let step = h / steps;
+                    let mut current = start;
+
+                    for _ in 0..steps as u32 {
+                        current = match fixed {
+                            Propagation::Rk4 => integrators::rk4_step(current, step, acceleration),
+                            Propagation::Leapfrog => integrators::leapfrog_step(current, step, acceleration),
+                            _ => integrators::yoshida_step(current, step, acceleration),
+                        };
+                    }
+
+                    current
+                }
+            };
+
+            state.position = end.position;
+            state.velocity = end.velocity;
+            state.time = t;
+        }
+
+        let energy = specific_energy(state.position, state.velocity, mu);
+        state.energy_drift = (energy - state.initial_energy) / state.initial_energy.abs();
+        state.analytic_deviation = analytic_state(orbit, satellite, physics_time)
+            .map_or(f64::NAN, |(posn, _)| (posn - state.position).length());
+
+        let (posn, vel) = (state.position, state.velocity);
+        write_state(&mut commands, entity, relative, velocity, posn, vel);
+    }
+}
+
+// The Kepler orbit, or SGP4 for a satellite, that integration starts from and is compared
+// against.
+fn analytic_state(
+    orbit: Option<&OrbitalParameters>,
+    satellite: Option<&Satellite>,
+    physics_time: &PhysicsTime,
+) -> Option<(DVec3, DVec3)> {
+    if let Some(orbit) = orbit {
+        return orbit.simulation_state(physics_time.seconds(TimeScale::Tdb)).ok();
+    }
+
+    satellite?
+        .icrf_state(physics_time.seconds(TimeScale::Utc))
+        .ok()
+}
+
+fn write_state(
+    commands: &mut Commands,
+    entity: Entity,
+    relative: Option<Mut<RelativePosition>>,
+    velocity: Option<Mut<RelativeVelocity>>,
+    posn: DVec3,
+    vel: DVec3,
+) {
+    match (relative, velocity) {
+        (Some(mut relative), Some(mut velocity)) => {
+            relative.0 = posn;
+            velocity.0 = vel;
+        }
+        _ => {
+            commands
+                .entity(entity)
+                .insert((RelativePosition(posn), RelativeVelocity(vel)));
+        }
+    }
+}
+
+pub fn draw_integrator_diagnostics(
+    registry: Res<BodyRegistry>,
+    body_query: Query<(&Propagation, &NumericalState)>,
+    mut text_query: Query<&mut Text, With<IntegratorDiagnosticsLabel>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let lines: Vec<String> = registry
+        .iter()
+        .filter_map(|body| {
+            let (propagation, state) = body_query.get(body.entity).ok()?;
+            Some(format!(
+                "{} ({:?}): dE/E {:.2e}, {:.1} km from analytic",
+                body.name, propagation, state.energy_drift, state.analytic_deviation
+            ))
+        })
+        .collect();
+
+    text.sections[0].value = lines.join("\n");
+}
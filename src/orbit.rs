@@ -1,85 +1,102 @@
+use crate::frames::to_scene;
 use crate::lines;
-use crate::time::{PhysicsTime, PhysicsTimeMode};
+use crate::lunar_theory::LunarTheorySettings;
+use crate::nbody::Propagation;
+use crate::integrators::IntegrationError;
+use crate::sgp4::Sgp4Error;
+use crate::time::{PhysicsStep, PhysicsTime};
+use bevy::math::DVec3;
 use bevy::pbr::{NotShadowCaster, NotShadowReceiver};
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use bevy_inspector_egui::prelude::*;
-use ndarray::{arr1, arr2};
+use orbiter_physics::kepler::KeplerError;
+use orbiter_physics::lunar_theory::geocentric_state;
+use orbiter_physics::time_scale::TimeScale;
 use std::str;
+
+pub use orbiter_physics::elements::{lunar_orbit, OrbitalParameters, G};
+
 pub struct OrbitPlugin;
 
+/// Orbits are propagated relative to their parent body first, in the `PhysicsStep`
+/// schedule, then every frame each body gets its simulation position by walking up its
+/// chain of parents, and finally the render transforms are written relative to the
+/// floating origin.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OrbitSet {
+    Propagate,
+    Place,
+    Render,
+}
+
 impl Plugin for OrbitPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, moon_orbit)
-            .add_systems(Update, rotate_moon)
-            .add_systems(Update, rotate_earth)
-            .add_systems(Update, draw_lunar_orbit_lines)
-            //.add_systems(Update, draw_lunar_orbit_lines)
-            .insert_resource(LunarOrbit {
-                ..Default::default()
-            })
+        app.configure_sets(Update, (OrbitSet::Place, OrbitSet::Render).chain())
+            .add_systems(Update, register_bodies.before(OrbitSet::Place))
+            .configure_sets(PhysicsStep, (OrbitSet::Propagate, OrbitSet::Place).chain())
+            .add_systems(PhysicsStep, propagate_orbits.in_set(OrbitSet::Propagate))
+            // Also placed after every sub-step, so bodies that pull on others are where
+            // they should be for the next one.
+            .add_systems(PhysicsStep, place_bodies.in_set(OrbitSet::Place))
+            .add_systems(Update, place_bodies.in_set(OrbitSet::Place))
+            .add_systems(Startup, setup)
+            .add_systems(Update, draw_body_info.after(OrbitSet::Place))
+            .add_systems(Update, draw_propagation_errors.after(OrbitSet::Place))
+            .add_event::<PropagationFailed>()
+            .add_systems(Update, rotate_bodies)
+            .add_systems(
+                Update,
+                (draw_orbit_lines, sync_orbit_lines)
+                    .chain()
+                    .after(OrbitSet::Render),
+            )
+            .insert_resource(BodyRegistry::default())
+            .insert_resource(OrbitLineSettings::default())
             .insert_resource(lines::LineStrip {
                 ..Default::default()
             })
-            .register_type::<LunarOrbit>()
-            .register_type::<OrbitalParameters>();
+            .register_type::<CelestialBody>()
+            .register_type::<ParentBody>()
+            .register_type::<SimulationPosition>()
+            .register_type::<GravParameter>()
+            .register_type::<RelativePosition>()
+            .register_type::<RelativeVelocity>()
+            .register_type::<OrbitalParameters>()
+            .register_type::<OrbitLineSettings>();
     }
 }
 
 // Consts
-const G: f64 = 6.67e-20; // In KM!
 use std::f32::consts::PI;
 const PI64: f64 = PI as f64;
-pub const REAL_TO_WORLD: f32 = 500. / 12742.; // 100 in world unit to 12,742 KM (Earth width)
+pub const REAL_TO_WORLD: f32 = orbiter_physics::frames::REAL_TO_WORLD as f32;
 pub const WORLD_TO_REAL: f32 = 12742. / 500.; // 12742 KM to 100 world units
 
-#[derive(Reflect, Resource, InspectorOptions, Component)]
+// Parent bodies are followed at most this many levels up when placing a body.
+const MAX_HIERARCHY_DEPTH: usize = 16;
+
+// How much of an open (parabolic or hyperbolic) trajectory gets drawn.
+#[derive(Reflect, Resource, InspectorOptions)]
 #[reflect(Resource, InspectorOptions)]
-pub struct LunarOrbit {
-    pub orbit: OrbitalParameters,
+pub struct OrbitLineSettings {
+    pub open_time_window: f64,  // Seconds either side of periapsis
+    pub open_max_distance: f64, // KM from the parent
+    pub redraw_change: f64,     // Radians an orbit with element rates turns before it's redrawn
 }
 
-
-// EC= 6.476694128611285E-02 QR= 3.565283199467715E+05 IN= 5.240010829674768E+00
-// OM= 1.239837028145578E+02 W = 3.081359034620368E+02 Tp=  2451533.965359285008
-// N = 1.546268358955514E-04 MA= 1.407402571142365E+02 TA= 1.451550311169052E+02
-// A = 3.812186883524646E+05 AD= 4.059090567581577E+05 PR= 2.328185776517964E+06
-
-// Symbol meaning:
-
-// JDTDB    Julian Day Number, Barycentric Dynamical Time
-//   EC     Eccentricity, e
-//   QR     Periapsis distance, q (km)
-//   IN     Inclination w.r.t X-Y plane, i (degrees)
-//   OM     Longitude of Ascending Node, OMEGA, (degrees)
-//   W      Argument of Perifocus, w (degrees)
-//   Tp     Time of periapsis (Julian Day Number)
-//   N      Mean motion, n (degrees/sec)
-//   MA     Mean anomaly, M (degrees)
-//   TA     True anomaly, nu (degrees)
-//   A      Semi-major axis, a (km)
-//   AD     Apoapsis distance (km)
-//   PR     Sidereal orbit period (sec)
-
-impl Default for LunarOrbit {
+impl Default for OrbitLineSettings {
     fn default() -> Self {
-        return LunarOrbit {
-            orbit: OrbitalParameters::new(
-                2.45638088,
-                3.812186883524646E+05, 
-                6.476694128611285E-02, 
-                0.3361502582,
-                5.37798606, 
-                2.16392383, 
-                5.9722e+24,
-                2360584.6848,
-                2360592.,
-            ),
-        };
+        OrbitLineSettings {
+            open_time_window: 30. * 86400.,
+            open_max_distance: 2_000_000.,
+            redraw_change: 1e-3,
+        }
     }
 }
 
 // Components
-// A marker component for our shapes so we can query them separately from the ground plane
+// Every body the registry knows about. `name` and `focus_idx` are unique across the scene.
 #[derive(Component, Reflect)]
 pub struct CelestialBody {
     pub focus_idx: i32,
@@ -87,278 +104,497 @@ pub struct CelestialBody {
     pub viewport_position: Option<Vec2>,
 }
 
+// The body this one orbits. Its `OrbitalParameters` are relative to the parent.
+#[derive(Component, Reflect)]
+pub struct ParentBody(pub Entity);
+
+// Position in KM in the simulation frame. This is the source of truth for where a body is,
+// its Transform is only a render position relative to the floating origin. Bodies without
+// a parent keep whatever is set here, every other body has it written by `place_bodies`.
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct SimulationPosition(pub DVec3);
+
+// The body's own gravitational parameter in KM^3s^-2. Bodies with one pull on numerically
+// propagated bodies other than their own children.
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct GravParameter(pub f64);
+
+// Position relative to the parent body in KM, written by the propagation systems.
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct RelativePosition(pub DVec3);
+
+// Velocity relative to the parent body in KM/s, written alongside `RelativePosition`.
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct RelativeVelocity(pub DVec3);
+
+// A body's rotation at J2000, before any spin is applied. Taken from its Transform the
+// first time it's rotated.
 #[derive(Component)]
-pub struct EarthBody;
+pub struct RestRotation(pub Quat);
 
 #[derive(Component)]
-pub struct MoonBody;
+pub struct BodyInfoLabel;
 
-#[derive(Reflect, Resource, InspectorOptions, Clone, Copy)]
-#[reflect(Resource, InspectorOptions)]
-pub struct OrbitalParameters {
-    pub semimajor_axis: f64,     // KM
-    pub longitude_asc_node: f64, // Radians
-    pub arg_of_periapsis: f64,   // Radians
-    pub inclination: f64,        // Radians
-    pub eccentricity: f64,       // Unitless
-    pub mass_of_parent: f64,     // KG
-    pub grav_parameter: f64,     // KM^3s^-2
-    pub period: f64,             // Seconds
-    pub rotational_period: f64,  // Seconds
-    pub mean_anomaly_at_epoch: f64, // Angles
+#[derive(Component)]
+pub struct PropagationErrorLabel;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropagationError {
+    Kepler(KeplerError),
+    Sgp4(Sgp4Error),
+    Integration(IntegrationError),
 }
 
-impl Default for OrbitalParameters {
-    fn default() -> Self {
-        OrbitalParameters {
-            semimajor_axis: 0.,
-            longitude_asc_node: 0.,
-            arg_of_periapsis: 0.,
-            inclination: 0.,
-            eccentricity: 0.,
-            mass_of_parent: 0.,
-            grav_parameter: 0.,
-            period: 0.,
-            rotational_period: 0.,
-            mean_anomaly_at_epoch: 0.,
+impl std::fmt::Display for PropagationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropagationError::Kepler(err) => err.fmt(f),
+            PropagationError::Sgp4(err) => err.fmt(f),
+            PropagationError::Integration(err) => err.fmt(f),
         }
     }
 }
 
-impl OrbitalParameters {
-    pub fn new(
-        mean_anomaly_at_epoch: f64,
-        semimajor_axis: f64,
-        eccentricity: f64,
-        inclination: f64,
-        arg_of_periapsis: f64,
-        longitude_asc_node: f64,
-        mass_of_parent: f64,
-        period: f64,
-        rotational_period: f64,
-    ) -> OrbitalParameters {
-        let mu = G * mass_of_parent;
-        OrbitalParameters {
-            mean_anomaly_at_epoch,
-            semimajor_axis,
-            eccentricity,
-            inclination,
-            arg_of_periapsis,
-            longitude_asc_node,
-            mass_of_parent,
-            grav_parameter: mu,
-            period: period,
-            rotational_period,
-        }
+// Sent every frame a body's position couldn't be computed.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PropagationFailed {
+    pub body: Entity,
+    pub error: PropagationError,
+}
+
+pub struct RegisteredBody {
+    pub entity: Entity,
+    pub name: String,
+    pub focus_idx: i32,
+}
+
+// All celestial bodies in the scene, ordered by focus_idx.
+#[derive(Resource, Default)]
+pub struct BodyRegistry {
+    bodies: Vec<RegisteredBody>,
+}
+
+impl BodyRegistry {
+    pub fn by_name(&self, name: &str) -> Option<Entity> {
+        self.bodies
+            .iter()
+            .find(|body| body.name.eq_ignore_ascii_case(name))
+            .map(|body| body.entity)
     }
 
-    pub fn position(mut self, t: f64) -> Vec3 {
-        self.period = 2. * PI64 * (self.semimajor_axis.powf(3.) / self.grav_parameter).sqrt();
-
-        let mean_anomaly = self.mean_anomaly(t % self.period);
-        let eccentric_anomaly = self.eccentric_anomaly(mean_anomaly);
-        let true_anomaly = self.true_anomaly(eccentric_anomaly);
-        let distance = self.distance(eccentric_anomaly);
-
-        let x = distance * true_anomaly.cos();
-        let y = distance * true_anomaly.sin();
-        let z = 0.;
-
-        let coords = arr1(&[x, y, z]);
-
-        // cos Ω cos ω − sin Ω sin ω cos i 
-        let i_1_1 = self.longitude_asc_node.cos() * self.arg_of_periapsis.cos() - self.longitude_asc_node.sin() * self.arg_of_periapsis.sin() * self.inclination.cos();
-        // − cos Ω sin ω − sin Ω cos ω cos i
-        let i_1_2  = - self.longitude_asc_node.cos() * self.arg_of_periapsis.sin() - self.longitude_asc_node.sin() * self.arg_of_periapsis.cos() * self.inclination.cos();
-        // sin Ω sin i
-        let i_1_3 = self.longitude_asc_node.sin() * self.inclination.sin();
-
-        // sin Ω cos ω + cos Ω sin ω cos i
-        let i_2_1 = self.longitude_asc_node.sin() * self.arg_of_periapsis.cos() + self.longitude_asc_node.cos() * self.arg_of_periapsis.sin() * self.inclination.cos();
-        // − sin Ω sin ω + cos Ω cos ω cos i
-        let i_2_2 = - self.longitude_asc_node.sin() * self.arg_of_periapsis.sin() + self.longitude_asc_node.cos() * self.arg_of_periapsis.cos() * self.inclination.cos();
-        // − cos Ω sin i
-        let i_2_3 = - self.longitude_asc_node.cos() * self.inclination.sin();
-
-        // sin ω sin i
-        let i_3_1 = self.arg_of_periapsis.sin() * self.inclination.sin();
-        // cos ω sin i
-        let i_3_2 = self.arg_of_periapsis.cos() * self.inclination.sin();
-        // cos i
-        let i_3_3 = self.inclination.cos();
-
-        let trans = arr2(&[
-            [i_1_1, i_1_2, i_1_3],
-            [i_2_1, i_2_2, i_2_3],
-            [i_3_1, i_3_2, i_3_3],
-        ]);
-
-        let final_coords = trans.dot(&coords);
-
-        return Vec3::new(
-            final_coords[0] as f32,
-            final_coords[1] as f32,
-            final_coords[2] as f32,
-        );
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredBody> {
+        self.bodies.iter()
     }
 
-    pub fn mean_anomaly(&self, t: f64) -> f64 {
-        // println!("Expected mean anomaly = {}",2.45638088);
-        // println!("Actual mean anomaly = {}", self.mean_anomaly_at_epoch + self.mean_motion() * t);
-        (self.mean_anomaly_at_epoch + self.mean_motion() * t) % (2. * std::f64::consts::PI) 
+    pub fn by_focus_idx(&self, focus_idx: i32) -> Option<Entity> {
+        self.bodies
+            .iter()
+            .find(|body| body.focus_idx == focus_idx)
+            .map(|body| body.entity)
     }
+}
 
-    pub fn eccentric_anomaly(&self, mean_anomaly: f64) -> f64 {
-        let eta = 1e-15_f64;
-        let e_naught;
+#[derive(Component)]
+pub struct EarthBody;
 
-        if (-1. * PI64 < mean_anomaly && mean_anomaly < 0.) || (mean_anomaly > PI64) {
-            e_naught = mean_anomaly - self.eccentricity;
-        } else {
-            e_naught = mean_anomaly + self.eccentricity;
-        }
+#[derive(Component)]
+pub struct MoonBody;
 
-        let mut e_n = e_naught;
-        let mut delta = eta + 1.;
-        let mut count = 0;
-        let mut e_np1 = 0.;
-
-        while delta > eta {
-            e_np1 = e_n
-                + (mean_anomaly - e_n + self.eccentricity * e_n.sin())
-                    / (1. - self.eccentricity * e_n.cos());
-            delta = (e_np1 - e_n).abs();
-            e_n = e_np1;
-            count += 1;
-
-            if count > 20 {
-                println!("Something bad happened, couldn't converge for eccentric anomaly.");
-                return 0. as f64;
-            }
-        }
-        
-        e_np1
+pub fn compute_orbit_lines(orbit: &OrbitalParameters, num_lines: i32, settings: &OrbitLineSettings) -> Vec<Vec3> {
+    if !orbit.is_closed() {
+        return compute_open_orbit_lines(orbit, num_lines, settings);
     }
 
-    pub fn mean_motion(&self) -> f64 {
-        (2. * std::f64::consts::PI) / self.period
-    }
+    let mut lines: Vec<Vec3> = Vec::<Vec3>::new();
+    let period = 2. * PI64 * (orbit.semimajor_axis.powf(3.) / orbit.grav_parameter).sqrt();
+
+    let time_increment = period / (num_lines as f64);
 
-    pub fn true_anomaly(&self, eccentric_anomaly: f64) -> f64 {
-        let t_a = 2. * (((1. + self.eccentricity) / (1. - self.eccentricity)).sqrt()
-            * (eccentric_anomaly / 2.).tan())
-        .atan();
+    let mut t: f64 = 0.;
 
-        // println!("True anomaly = {}, expected = {}", t_a, 2.53343322);
-        t_a
+    while t <= period {
+        // Points the solver can't find are left out, the failure is reported by propagate_orbits.
+        if let Ok((posn, _)) = orbit.simulation_state(t) {
+            lines.push(to_scene(posn));
+        }
+        t = t + time_increment;
     }
 
-    pub fn distance(&self, eccentric_anomaly: f64) -> f64 {
-        self.semimajor_axis * (1. - self.eccentricity * eccentric_anomaly.cos())
+    if lines.is_empty() {
+        return lines;
     }
 
-    pub fn compute_orbit_lines(&self, num_lines: i32) -> Vec<Vec3> {
-        let mut lines: Vec<Vec3> = Vec::<Vec3>::new();
-        let period = 2. * PI64 * (self.semimajor_axis.powf(3.) / self.grav_parameter).sqrt();
+    lines.push(lines[lines.len() - 1 as usize]);
+    lines.push(lines[0 as usize]);
 
-        let time_increment = period / (num_lines as f64);
+    lines
+}
 
-        let mut t: f64 = 0.;
+// Escape trajectories never close, so only the arc around periapsis that fits inside
+// both the time and the distance window is drawn.
+fn compute_open_orbit_lines(orbit: &OrbitalParameters, num_lines: i32, settings: &OrbitLineSettings) -> Vec<Vec3> {
+    // Stay just short of the asymptote, where the distance goes to infinity.
+    let asymptote = if orbit.is_parabolic() {
+        PI64
+    } else {
+        (-1. / orbit.eccentricity).acos()
+    };
+    let mut max_true_anomaly = asymptote * 0.999;
+
+    if let Ok(window_anomaly) = orbit.anomaly(orbit.mean_motion() * settings.open_time_window) {
+        max_true_anomaly = max_true_anomaly.min(orbit.true_anomaly(window_anomaly).abs());
+    }
 
-        while t <= period {
-            let vec = REAL_TO_WORLD * self.position(t);
-            lines.push(Vec3::new(-vec.x, -vec.z, vec.y));
-            t = t + time_increment;
-        }
+    let p = orbit.semi_latus_rectum();
+    let cos_at_max_distance = (p / settings.open_max_distance - 1.) / orbit.eccentricity;
+    if cos_at_max_distance.abs() <= 1. {
+        max_true_anomaly = max_true_anomaly.min(cos_at_max_distance.acos());
+    }
 
-        lines.push(lines[lines.len() - 1 as usize]);
-        lines.push(lines[0 as usize]);
+    (0..=num_lines)
+        .map(|i| {
+            let true_anomaly = -max_true_anomaly + 2. * max_true_anomaly * (i as f64) / (num_lines as f64);
+            to_scene(orbit.frame.to_icrf(orbit.state_at_true_anomaly(true_anomaly).0, orbit.epoch))
+        })
+        .collect()
+}
+
+pub fn register_bodies(
+    mut registry: ResMut<BodyRegistry>,
+    bodies: Query<(Entity, &CelestialBody)>,
+    changed: Query<(), Changed<CelestialBody>>,
+    mut removed: RemovedComponents<CelestialBody>,
+) {
+    if changed.is_empty() && removed.read().count() == 0 {
+        return;
+    }
 
-        lines
+    registry.bodies = bodies
+        .iter()
+        .map(|(entity, body)| RegisteredBody {
+            entity,
+            name: body.name.clone(),
+            focus_idx: body.focus_idx,
+        })
+        .collect();
+    registry.bodies.sort_by_key(|body| body.focus_idx);
+
+    for pair in registry.bodies.windows(2) {
+        if pair[0].focus_idx == pair[1].focus_idx {
+            println!(
+                "Bodies {} and {} share focus_idx {}",
+                pair[0].name, pair[1].name, pair[0].focus_idx
+            );
+        }
     }
 }
 
-pub fn rotate_earth(
-    mut query: Query<&mut Transform, With<EarthBody>>,
+pub fn propagate_orbits(
+    mut commands: Commands,
+    mut body_query: Query<(
+        Entity,
+        &OrbitalParameters,
+        Option<&mut RelativePosition>,
+        Option<&mut RelativeVelocity>,
+        Option<&Propagation>,
+        Option<&MoonBody>,
+    )>,
     physics_time_q: Query<&PhysicsTime>,
+    lunar_theory: Option<Res<LunarTheorySettings>>,
+    mut failures: EventWriter<PropagationFailed>,
 ) {
     let physics_time = physics_time_q.single();
 
-    if physics_time.mode == PhysicsTimeMode::StopTick {
-        return;
-    }
+    for (entity, orbit, relative, velocity, propagation, moon) in &mut body_query {
+        // Numerically propagated bodies are moved by nbody.rs.
+        if propagation.is_some_and(|propagation| *propagation != Propagation::Analytic) {
+            continue;
+        }
+
+        // And the Moon by lunar_theory.rs, unless it's set to use its elements.
+        if lunar_theory.as_ref().is_some_and(|lunar| lunar.places(moon.is_some(), propagation)) {
+            continue;
+        }
 
-    for mut transform in &mut query {
-        let val = (physics_time.delta_seconds / 86400.0) * 2. * std::f64::consts::PI;
-        transform.rotate_y(val as f32);
+        // A body that can't be propagated stays where it was last placed.
+        let (posn, vel) = match orbit.simulation_state(physics_time.seconds(TimeScale::Tdb)) {
+            Ok(state) => state,
+            Err(err) => {
+                failures.send(PropagationFailed {
+                    body: entity,
+                    error: PropagationError::Kepler(err),
+                });
+                continue;
+            }
+        };
+
+        match (relative, velocity) {
+            (Some(mut relative), Some(mut velocity)) => {
+                relative.0 = posn;
+                velocity.0 = vel;
+            }
+            _ => {
+                commands
+                    .entity(entity)
+                    .insert((RelativePosition(posn), RelativeVelocity(vel)));
+            }
+        }
     }
 }
 
-pub fn rotate_moon(
-    mut query: Query<&mut Transform, With<MoonBody>>,
-    physics_time_q: Query<&mut PhysicsTime>,
-    lunar_orbit: ResMut<LunarOrbit>,
+pub fn setup(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            right: Val::Px(12.0),
+            ..default()
+        }),
+        BodyInfoLabel,
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::rgb(1., 0.3, 0.3),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        PropagationErrorLabel,
+    ));
+}
+
+// Lists every body that failed to propagate this frame.
+pub fn draw_propagation_errors(
+    mut failures: EventReader<PropagationFailed>,
+    mut recent: Local<HashMap<Entity, (PropagationError, f64)>>,
+    time: Res<Time>,
+    body_query: Query<&CelestialBody>,
+    mut text_query: Query<&mut Text, With<PropagationErrorLabel>>,
 ) {
-    let physics_time = physics_time_q.single();
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+
+    // Physics steps don't line up with frames, so a failure stays up for a moment rather
+    // than flickering on the frames without a step.
+    const SHOW_FOR_SECONDS: f64 = 0.5;
+    let now = time.elapsed_seconds_f64();
 
-    if physics_time.mode == PhysicsTimeMode::StopTick {
+    for failure in failures.read() {
+        recent.insert(failure.body, (failure.error, now));
+    }
+    recent.retain(|_, (_, seen)| now - *seen < SHOW_FOR_SECONDS);
+
+    let lines: Vec<String> = recent
+        .iter()
+        .map(|(body, (error, _))| {
+            let name = body_query
+                .get(*body)
+                .map_or("Unknown body", |body| body.name.as_str());
+            format!("{}: {}", name, error)
+        })
+        .collect();
+
+    text.sections[0].value = lines.join("\n");
+}
+
+// Distance and speed of every propagated body relative to its parent.
+pub fn draw_body_info(
+    registry: Res<BodyRegistry>,
+    body_query: Query<(&RelativePosition, &RelativeVelocity)>,
+    mut text_query: Query<&mut Text, With<BodyInfoLabel>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
         return;
+    };
+
+    let lines: Vec<String> = registry
+        .iter()
+        .filter_map(|body| {
+            let (posn, vel) = body_query.get(body.entity).ok()?;
+            Some(format!(
+                "{}: {:.0} km, {:.3} km/s",
+                body.name,
+                posn.0.length(),
+                vel.0.length()
+            ))
+        })
+        .collect();
+
+    text.sections[0].value = lines.join("\n");
+}
+
+pub fn place_bodies(
+    mut commands: Commands,
+    mut body_query: Query<
+        (
+            Entity,
+            Option<&mut SimulationPosition>,
+            Option<&RelativePosition>,
+            Option<&ParentBody>,
+        ),
+        With<CelestialBody>,
+    >,
+) {
+    // Each body's offset from its parent in KM. Bodies without a parent are offset from
+    // the simulation frame's origin by their own simulation position.
+    let mut offsets: HashMap<Entity, (Option<Entity>, DVec3)> = HashMap::new();
+
+    for (entity, simulation, relative, parent) in &body_query {
+        let offset = match (relative, parent) {
+            (Some(relative), Some(parent)) => (Some(parent.0), relative.0),
+            _ => (None, simulation.map_or(DVec3::ZERO, |simulation| simulation.0)),
+        };
+        offsets.insert(entity, offset);
     }
 
-    for mut transform in &mut query {
-        transform.rotate_y(
-            ((physics_time.delta_seconds
-                / lunar_orbit.orbit.rotational_period)
-                * 2.
-                * std::f64::consts::PI) as f32,
-        );
+    for (entity, simulation, _, _) in &mut body_query {
+        let Some(position) = simulation_position(entity, &offsets) else {
+            continue;
+        };
+
+        match simulation {
+            Some(mut simulation) => {
+                if simulation.0 != position {
+                    simulation.0 = position;
+                }
+            }
+            None => {
+                commands.entity(entity).insert(SimulationPosition(position));
+            }
+        }
     }
 }
 
-pub fn moon_orbit(
-    mut body_query: Query<&mut Transform, With<MoonBody>>,
-    orbit: Res<LunarOrbit>,
+fn simulation_position(entity: Entity, offsets: &HashMap<Entity, (Option<Entity>, DVec3)>) -> Option<DVec3> {
+    let mut position = DVec3::ZERO;
+    let mut current = entity;
+
+    for _ in 0..MAX_HIERARCHY_DEPTH {
+        let (parent, offset) = offsets.get(&current)?;
+        position += *offset;
+
+        match parent {
+            Some(parent) => current = *parent,
+            None => return Some(position),
+        }
+    }
+
+    None
+}
+
+// Spins every body with a rotational period about its own Y axis. The angle comes straight
+// from the clock, so jumping to another date leaves bodies facing the right way.
+pub fn rotate_bodies(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &OrbitalParameters, Option<&RestRotation>)>,
     physics_time_q: Query<&PhysicsTime>,
 ) {
     let physics_time = physics_time_q.single();
+    let t = physics_time.seconds(TimeScale::Tdb);
 
-    let mut posn = orbit
-        .orbit
-        .position(physics_time.clock_seconds);
+    for (entity, mut transform, orbit, rest) in &mut query {
+        if orbit.rotational_period == 0. {
+            continue;
+        }
 
-    // Convert from physical coordinates to world/scene coordinates
-    posn.x *= REAL_TO_WORLD;
-    posn.y *= REAL_TO_WORLD;
-    posn.z *= REAL_TO_WORLD;
+        let rest = match rest {
+            Some(rest) => rest.0,
+            None => {
+                commands.entity(entity).insert(RestRotation(transform.rotation));
+                transform.rotation
+            }
+        };
 
-    for mut transform in &mut body_query {
-        transform.translation = Vec3::new(-posn.x, -posn.z, posn.y);
+        let angle = (t / orbit.rotational_period).fract() * 2. * PI64;
+        transform.rotation = rest * Quat::from_rotation_y(angle as f32);
     }
 }
 
-pub fn draw_lunar_orbit_lines(
-    orbit: Res<LunarOrbit>,
+pub fn draw_orbit_lines(
+    orbit_query: Query<(Entity, Ref<OrbitalParameters>, Option<&Propagation>, Option<&MoonBody>)>,
+    settings: Res<OrbitLineSettings>,
+    physics_time_q: Query<&PhysicsTime>,
+    lunar_theory: Option<Res<LunarTheorySettings>>,
+    mut drawn: Local<HashMap<Entity, OrbitalParameters>>,
+    mut removed: RemovedComponents<OrbitalParameters>,
     mut commands: Commands,
-    mut mesh_query: Query<Entity, With<lines::OrbitalLines>>,
+    mesh_query: Query<(Entity, &lines::OrbitalLines)>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    // Only despawn original lines if the orbit has changed.
-    if orbit.is_changed() {
-        let orbit_lines = orbit.orbit.compute_orbit_lines(1000);
+    let t = physics_time_q.single().seconds(TimeScale::Tdb);
+
+    // Lines of bodies that lost their elements or were despawned, e.g. by a scenario
+    // reload. Elements put straight back are redrawn below.
+    for body in removed.read() {
+        if orbit_query.contains(body) {
+            continue;
+        }
+
+        drawn.remove(&body);
+        for (entity, lines) in &mesh_query {
+            if lines.body == body {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+
+    // Only despawn a body's original lines if its orbit (or the line window) has changed,
+    // or its elements have drifted far enough from the ellipse that was drawn.
+    for (body, orbit, propagation, moon) in &orbit_query {
+        // The Moon placed by the lunar theory gets the ellipse it's on at the moment.
+        let meeus = lunar_theory.as_ref().is_some_and(|lunar| lunar.places(moon.is_some(), propagation));
+        let ellipse = if meeus {
+            let (posn, vel) = geocentric_state(t);
+            OrbitalParameters::from_state_vector(posn, vel, orbit.grav_parameter, t)
+        } else {
+            orbit.osculating(t)
+        };
+        let drifted = drawn
+            .get(&body)
+            .map_or(true, |last| last.element_change(&ellipse) > settings.redraw_change);
+
+        let model_changed = lunar_theory.as_ref().is_some_and(|lunar| lunar.is_changed());
+        if !orbit.is_changed() && !settings.is_changed() && !model_changed && !drifted {
+            continue;
+        }
 
-        for entity in &mut mesh_query {
-            commands.entity(entity).despawn_recursive();
+        drawn.insert(body, ellipse);
+        let orbit_lines = compute_orbit_lines(&ellipse, 1000, &settings);
+
+        for (entity, lines) in &mesh_query {
+            if lines.body == body {
+                commands.entity(entity).despawn_recursive();
+            }
         }
 
-        // Draw moon lines
         // Spawn a line strip that goes from point to point
         commands.spawn((
             MaterialMeshBundle {
                 mesh: meshes.add(Mesh::from(lines::LineStrip {
                     points: orbit_lines,
                 })),
-                transform: Transform::from_xyz(0.5, 0.0, 0.0),
                 material: materials.add(StandardMaterial {
                     base_color: Color::rgba(1., 0.0, 0.0, 1.),
                     emissive: Color::rgba(1., 0., 0., 1.),
@@ -367,10 +603,73 @@ pub fn draw_lunar_orbit_lines(
                 }),
                 ..default()
             },
-            lines::OrbitalLines,
+            lines::OrbitalLines { body },
             NotShadowCaster,
             NotShadowReceiver,
         ));
-        println!("Orbit changed.");
+
+        if orbit.is_changed() {
+            println!("Orbit changed.");
+        }
+    }
+}
+
+// Orbit lines are drawn relative to the parent, so keep them centered on it.
+pub fn sync_orbit_lines(
+    mut lines_query: Query<(&lines::OrbitalLines, &mut Transform)>,
+    parent_query: Query<&ParentBody>,
+    transform_query: Query<&Transform, Without<lines::OrbitalLines>>,
+) {
+    for (lines, mut transform) in &mut lines_query {
+        let Ok(parent) = parent_query.get(lines.body) else {
+            continue;
+        };
+
+        if let Ok(parent_transform) = transform_query.get(parent.0) {
+            transform.translation = parent_transform.translation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_go_with_their_bodies() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<StandardMaterial>>();
+        world.insert_resource(OrbitLineSettings::default());
+        world.spawn(PhysicsTime::default());
+        let mut schedule = Schedule::default();
+        schedule.add_systems(draw_orbit_lines);
+
+        let moon = world.spawn(lunar_orbit()).id();
+        let other = world.spawn(lunar_orbit()).id();
+        schedule.run(&mut world);
+
+        let drawn_for = |world: &mut World| {
+            let mut bodies: Vec<Entity> = world
+                .query::<&lines::OrbitalLines>()
+                .iter(world)
+                .map(|lines| lines.body)
+                .collect();
+            bodies.sort();
+            bodies
+        };
+        assert_eq!(drawn_for(&mut world), vec![moon, other]);
+
+        world.despawn(moon);
+        world.entity_mut(other).remove::<OrbitalParameters>();
+        schedule.run(&mut world);
+        assert!(drawn_for(&mut world).is_empty());
+
+        // Elements taken off and put straight back keep one line.
+        world.entity_mut(other).insert(lunar_orbit());
+        schedule.run(&mut world);
+        world.entity_mut(other).remove::<OrbitalParameters>().insert(lunar_orbit());
+        schedule.run(&mut world);
+        assert_eq!(drawn_for(&mut world), vec![other]);
     }
 }
@@ -0,0 +1,272 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashSet};
+
+use crate::nbody::Propagation;
+use crate::orbit::{
+    CelestialBody, OrbitSet, PropagationError, PropagationFailed, RelativePosition,
+    RelativeVelocity,
+};
+use crate::sgp4::{Sgp4, Sgp4Error, TleError, TwoLineElements};
+use crate::time::{PhysicsStep, PhysicsTime};
+use orbiter_physics::frames::ReferenceFrame;
+use orbiter_physics::time_scale::TimeScale;
+
+// Earth satellites propagated with SGP4. The scenario names them, each along with the TLE
+// catalogue under assets/ its element set is in (`OrbitSource::Tle`).
+pub struct SatellitePlugin;
+
+impl Plugin for SatellitePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TleCatalogue>()
+            .init_asset_loader::<TleCatalogueLoader>()
+            .add_systems(Update, attach_satellites.before(OrbitSet::Place))
+            .add_systems(PhysicsStep, propagate_satellites.in_set(OrbitSet::Propagate));
+    }
+}
+
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct TleCatalogue {
+    pub satellites: Vec<TwoLineElements>,
+}
+
+// The catalogue a body's element set is looked up in, by the body's name.
+#[derive(Component)]
+pub struct TleSource(pub Handle<TleCatalogue>);
+
+#[derive(Component)]
+pub struct Satellite {
+    pub propagator: Sgp4,
+}
+
+impl Satellite {
+    // Position and velocity relative to Earth in ICRF at a UTC time.
+    pub fn icrf_state(&self, utc: f64) -> Result<(DVec3, DVec3), Sgp4Error> {
+        let (posn, vel) = self.propagator.state_at(utc)?;
+        Ok((
+            ReferenceFrame::Teme.to_icrf(posn, utc),
+            ReferenceFrame::Teme.to_icrf(vel, utc),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum TleCatalogueError {
+    Io(std::io::Error),
+    Tle(TleError),
+}
+
+impl std::fmt::Display for TleCatalogueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TleCatalogueError::Io(err) => write!(f, "could not read TLE catalogue: {}", err),
+            TleCatalogueError::Tle(err) => write!(f, "invalid TLE: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TleCatalogueError {}
+
+impl From<std::io::Error> for TleCatalogueError {
+    fn from(err: std::io::Error) -> Self {
+        TleCatalogueError::Io(err)
+    }
+}
+
+#[derive(Default)]
+pub struct TleCatalogueLoader;
+
+impl AssetLoader for TleCatalogueLoader {
+    type Asset = TleCatalogue;
+    type Settings = ();
+    type Error = TleCatalogueError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<TleCatalogue, TleCatalogueError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let satellites = TwoLineElements::parse_catalogue(&String::from_utf8_lossy(&bytes))
+                .map_err(TleCatalogueError::Tle)?;
+            Ok(TleCatalogue { satellites })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tle"]
+    }
+}
+
+// Gives each body with a `TleSource` the propagator for the element set with its name,
+// once the catalogue has loaded, which can be before or after the body is spawned, and
+// again when the file changes.
+pub fn attach_satellites(
+    mut events: EventReader<AssetEvent<TleCatalogue>>,
+    catalogues: Res<Assets<TleCatalogue>>,
+    body_query: Query<(Entity, &CelestialBody, &TleSource, Option<&Satellite>)>,
+    mut commands: Commands,
+    mut reported: Local<HashSet<Entity>>,
+) {
+    let modified: Vec<AssetId<TleCatalogue>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, body, source, satellite) in &body_query {
+        if satellite.is_some() && !modified.contains(&source.0.id()) {
+            continue;
+        }
+
+        // Not loaded yet.
+        let Some(catalogue) = catalogues.get(&source.0) else {
+            continue;
+        };
+
+        let propagator = catalogue
+            .satellites
+            .iter()
+            .find(|elements| elements.name == body.name)
+            .ok_or_else(|| "not in its catalogue".to_string())
+            .and_then(|elements| Sgp4::new(elements.clone()).map_err(|err| err.to_string()));
+
+        match propagator {
+            Ok(propagator) => {
+                commands.entity(entity).insert(Satellite { propagator });
+                reported.remove(&entity);
+            }
+            Err(err) => {
+                if satellite.is_some() {
+                    commands.entity(entity).remove::<Satellite>();
+                }
+                if reported.insert(entity) {
+                    println!("No SGP4 propagator for {}: {}", body.name, err);
+                }
+            }
+        }
+    }
+}
+
+pub fn propagate_satellites(
+    mut commands: Commands,
+    mut satellite_query: Query<(
+        Entity,
+        &Satellite,
+        &mut Visibility,
+        Option<&mut RelativePosition>,
+        Option<&mut RelativeVelocity>,
+        Option<&Propagation>,
+    )>,
+    physics_time_q: Query<&PhysicsTime>,
+    mut failures: EventWriter<PropagationFailed>,
+) {
+    let physics_time = physics_time_q.single();
+
+    for (entity, satellite, mut visibility, relative, velocity, propagation) in &mut satellite_query {
+        // Numerically propagated satellites are moved by nbody.rs.
+        if propagation.is_some_and(|propagation| *propagation != Propagation::Analytic) {
+            continue;
+        }
+
+        let (posn, vel) = match satellite.icrf_state(physics_time.seconds(TimeScale::Utc)) {
+            Ok(state) => state,
+            Err(err) => {
+                // Outside the span the element set is good for, e.g. before launch or after decay.
+                *visibility = Visibility::Hidden;
+                failures.send(PropagationFailed {
+                    body: entity,
+                    error: PropagationError::Sgp4(err),
+                });
+                continue;
+            }
+        };
+
+        *visibility = Visibility::Inherited;
+
+        match (relative, velocity) {
+            (Some(mut relative), Some(mut velocity)) => {
+                relative.0 = posn;
+                velocity.0 = vel;
+            }
+            _ => {
+                commands
+                    .entity(entity)
+                    .insert((RelativePosition(posn), RelativeVelocity(vel)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOGUE: &str = "VANGUARD 1
+1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753
+2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667
+";
+
+    fn world() -> (World, Schedule) {
+        let mut world = World::new();
+        world.init_resource::<Assets<TleCatalogue>>();
+        world.init_resource::<Events<AssetEvent<TleCatalogue>>>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(attach_satellites);
+        (world, schedule)
+    }
+
+    fn body(name: &str) -> CelestialBody {
+        CelestialBody {
+            name: name.to_string(),
+            focus_idx: 0,
+            viewport_position: None,
+        }
+    }
+
+    fn catalogue() -> TleCatalogue {
+        TleCatalogue {
+            satellites: TwoLineElements::parse_catalogue(CATALOGUE).unwrap(),
+        }
+    }
+
+    #[test]
+    fn catalogue_loaded_before_the_body_waits_for_it() {
+        let (mut world, mut schedule) = world();
+        let handle = world.resource_mut::<Assets<TleCatalogue>>().add(catalogue());
+        world.send_event(AssetEvent::LoadedWithDependencies { id: handle.id() });
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+
+        let satellite = world.spawn((body("VANGUARD 1"), TleSource(handle.clone()))).id();
+        let unknown = world.spawn((body("ISS (ZARYA)"), TleSource(handle))).id();
+        schedule.run(&mut world);
+
+        assert!(world.get::<Satellite>(satellite).is_some());
+        assert!(world.get::<Satellite>(unknown).is_none());
+    }
+
+    #[test]
+    fn body_spawned_before_the_catalogue_loads_waits_for_it() {
+        let (mut world, mut schedule) = world();
+        let handle = world
+            .resource::<Assets<TleCatalogue>>()
+            .get_handle_provider()
+            .reserve_handle()
+            .typed::<TleCatalogue>();
+        let satellite = world.spawn((body("VANGUARD 1"), TleSource(handle.clone()))).id();
+        schedule.run(&mut world);
+        assert!(world.get::<Satellite>(satellite).is_none());
+
+        world.resource_mut::<Assets<TleCatalogue>>().insert(handle.id(), catalogue());
+        schedule.run(&mut world);
+        assert!(world.get::<Satellite>(satellite).is_some());
+    }
+}
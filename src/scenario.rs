@@ -0,0 +1,1048 @@
+use bevy::asset::io::file::FileAssetReader;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::core_pipeline::prepass::DepthPrepass;
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+use std::fmt;
+
+use crate::atmosphere::AtmosphereSettings;
+use crate::ephemeris::{EphemerisBody, Planet};
+use crate::floating_origin::FollowCamera;
+use crate::forces::{ExponentialAtmosphere, ForceModels, ZonalHarmonics};
+use crate::horizons::FromHorizons;
+use crate::nbody::Propagation;
+use crate::satellites::{Satellite, TleSource};
+use crate::orbit::{
+    lunar_orbit, BodyRegistry, CelestialBody, EarthBody, GravParameter, MoonBody,
+    OrbitalParameters, ParentBody, RelativePosition, RelativeVelocity, RestRotation,
+    SimulationPosition, G, REAL_TO_WORLD,
+};
+use crate::sphere_camera::SphereCamera;
+use crate::sun::SunBody;
+use crate::topocentric_camera::AltitudeAzimuthCamera;
+use orbiter_physics::frames::ReferenceFrame;
+use orbiter_physics::time_scale::{self, TimeScale};
+
+// The scene the app starts with, read from a RON file under assets/ instead of being spawned
+// in code: the bodies with their models, orbits and rotation, the sky, the cameras and the
+// atmosphere shader's parameters. assets/default.scenario.ron is used unless another file
+// is given with `--scenario <path>` or ORBITER_SCENARIO, relative to assets/.
+//
+// Saving the file while the app runs applies the changes. Bodies are matched by name, and
+// only the parts of a body that changed are redone, so editing a model leaves its orbit
+// alone. Cameras are only set up at startup, the user has moved them since.
+pub struct ScenarioPlugin;
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Scenario>()
+            .init_asset_loader::<ScenarioLoader>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, reload_scenario);
+    }
+}
+
+pub const DEFAULT_SCENARIO: &str = "default.scenario.ron";
+
+#[derive(Asset, TypePath, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub bodies: Vec<BodyConfig>,
+    #[serde(default)]
+    pub sky: Option<SkyConfig>,
+    #[serde(default)]
+    pub camera: CameraConfig,
+    #[serde(default)]
+    pub atmosphere: AtmosphereConfig,
+}
+
+// A body's focus index is its place in the list.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BodyConfig {
+    pub name: String,
+    #[serde(default)]
+    pub model: ModelConfig,
+    #[serde(default)]
+    pub radius: f64, // KM
+    #[serde(default)]
+    pub grav_parameter: Option<f64>, // KM^3s^-2
+    #[serde(default)]
+    pub parent: Option<String>, // Another body in the file, or one already in the scene
+    #[serde(default)]
+    pub orbit: OrbitSource,
+    #[serde(default)]
+    pub rotation: RotationModel,
+    #[serde(default)]
+    pub light: Option<LightConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum ModelConfig {
+    // A grey sphere the size of `radius`.
+    #[default]
+    Sphere,
+    // A glTF scene, e.g. "moon.glb#Scene0". `rotation` turns the model inside the body,
+    // degrees about X, Y then Z, for models whose axes don't line up with it.
+    Scene {
+        path: String,
+        #[serde(default = "unit_scale")]
+        scale: f32,
+        #[serde(default)]
+        rotation: (f32, f32, f32),
+    },
+    // Nothing drawn, e.g. the Sun, which is just its light.
+    Hidden,
+}
+
+fn unit_scale() -> f32 {
+    1.
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum OrbitSource {
+    // Stays at the origin of the simulation frame.
+    #[default]
+    Fixed,
+    // Placed by the lunar theory, with the built-in osculating elements to fall back on.
+    Moon,
+    // Placed by the solar ephemeris.
+    Sun,
+    // Osculating elements about the parent, whose grav_parameter has to be given. Angles in
+    // degrees, the epoch read like --epoch and J2000 if left out.
+    Elements {
+        semimajor_axis: f64, // KM
+        eccentricity: f64,
+        inclination: f64,
+        longitude_asc_node: f64,
+        arg_of_periapsis: f64,
+        mean_anomaly: f64,
+        #[serde(default)]
+        epoch: Option<String>,
+        #[serde(default)]
+        frame: ReferenceFrame,
+    },
+    // Elements come from the file under assets/horizons/ with the body's name.
+    Horizons,
+    // Placed by the planetary ephemeris about the Sun, which should be the parent.
+    Ephemeris { planet: Planet },
+    // SGP4, from the element set named like the body in a TLE catalogue under assets/.
+    // The parent should be the Earth.
+    Tle { catalogue: String },
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum RotationModel {
+    #[default]
+    Fixed,
+    // Spins about its own axis once every period, only for bodies with elements.
+    Uniform { period: f64 }, // Seconds
+    // Earth orientation (precession, nutation, sidereal time). The body also becomes the
+    // Earth the cameras anchor to, with Earth's gravity field and atmosphere.
+    Earth,
+    // IAU rotation model with physical libration. Also what the lunar theory places.
+    Moon,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct LightConfig {
+    pub illuminance: f32, // Lux
+    #[serde(default)]
+    pub shadows: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SkyConfig {
+    pub model: String,
+    pub scale: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub radius: f32, // Scene units from the focused body
+    pub theta: f32,  // Degrees
+    pub phi: f32,    // Degrees from the pole
+    pub altitude: f32,
+    pub azimuth: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            radius: 600.,
+            theta: 0.,
+            phi: 90.,
+            altitude: 0.,
+            azimuth: 0.,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AtmosphereConfig {
+    pub planet_radius: f32,
+    pub atmosphere_radius: f32,
+    pub falloff: f32,
+    pub sun_intensity: f32,
+    pub scattering_strength: f32,
+    pub density_modifier: f32,
+    pub wavelengths: (f32, f32, f32), // Red, green, blue, nm
+}
+
+impl Default for AtmosphereConfig {
+    fn default() -> Self {
+        AtmosphereConfig {
+            planet_radius: 1000.,
+            atmosphere_radius: 10.,
+            falloff: 15.,
+            sun_intensity: 15.,
+            scattering_strength: 1.,
+            density_modifier: 10.,
+            wavelengths: (700., 530., 440.),
+        }
+    }
+}
+
+impl AtmosphereConfig {
+    // Leaves what the other systems write every frame alone.
+    fn write(&self, settings: &mut AtmosphereSettings) {
+        settings.planetRadius = self.planet_radius;
+        settings.atmosphereRadius = self.atmosphere_radius;
+        settings.falloffFactor = self.falloff;
+        settings.sunIntensity = self.sun_intensity;
+        settings.scatteringStrength = self.scattering_strength;
+        settings.densityModifier = self.density_modifier;
+        (settings.redWaveLength, settings.greenWaveLength, settings.blueWaveLength) = self.wavelengths;
+    }
+}
+
+// The scene from before scenario files, used when the file can't be read.
+impl Default for Scenario {
+    fn default() -> Self {
+        // Grey spheres about the Sun or Earth.
+        let placed = |name: &str, parent: &str, orbit: OrbitSource| BodyConfig {
+            name: name.to_string(),
+            model: ModelConfig::Sphere,
+            radius: 0.,
+            grav_parameter: None,
+            parent: Some(parent.to_string()),
+            orbit,
+            rotation: RotationModel::Fixed,
+            light: None,
+        };
+
+        let mut bodies = vec![
+            BodyConfig {
+                name: "Earth".to_string(),
+                model: ModelConfig::Scene {
+                    path: "earth_updated.glb#Scene0".to_string(),
+                    scale: 1.,
+                    rotation: (0., 0., 0.),
+                },
+                radius: 6378.1366,
+                grav_parameter: Some(398600.4418),
+                parent: None,
+                orbit: OrbitSource::Fixed,
+                rotation: RotationModel::Earth,
+                light: None,
+            },
+            BodyConfig {
+                name: "Moon".to_string(),
+                model: ModelConfig::Scene {
+                    path: "moon.glb#Scene0".to_string(),
+                    scale: 1.,
+                    rotation: (90., 0., 0.),
+                },
+                radius: 1737.4,
+                grav_parameter: Some(4902.800066),
+                parent: Some("Earth".to_string()),
+                orbit: OrbitSource::Moon,
+                rotation: RotationModel::Moon,
+                light: None,
+            },
+            BodyConfig {
+                name: "Sun".to_string(),
+                model: ModelConfig::Hidden,
+                radius: 695700.,
+                grav_parameter: Some(1.32712440018e11),
+                parent: Some("Earth".to_string()),
+                orbit: OrbitSource::Sun,
+                rotation: RotationModel::Fixed,
+                light: Some(LightConfig {
+                    illuminance: 100000.,
+                    shadows: true,
+                }),
+            },
+        ];
+        bodies.extend(
+            Planet::PLANETS
+                .into_iter()
+                .filter(|planet| *planet != Planet::Earth)
+                .map(|planet| placed(planet.name(), "Sun", OrbitSource::Ephemeris { planet })),
+        );
+        bodies.extend(["ISS (ZARYA)", "VANGUARD 1"].map(|name| {
+            let catalogue = "satellites.tle".to_string();
+            placed(name, "Earth", OrbitSource::Tle { catalogue })
+        }));
+
+        Scenario {
+            bodies,
+            sky: Some(SkyConfig {
+                model: "sky_actual_constel.glb#Scene0".to_string(),
+                scale: 400.,
+            }),
+            camera: CameraConfig::default(),
+            atmosphere: AtmosphereConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "could not read scenario: {}", err),
+            ScenarioError::Ron(err) => write!(f, "could not parse scenario: {}", err),
+            ScenarioError::Invalid(reason) => write!(f, "invalid scenario: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(err: std::io::Error) -> Self {
+        ScenarioError::Io(err)
+    }
+}
+
+impl Scenario {
+    pub fn parse(text: &str) -> Result<Scenario, ScenarioError> {
+        let scenario: Scenario = ron::from_str(text).map_err(ScenarioError::Ron)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    // The cameras and Earth orientation need exactly one Earth, and bodies are told apart
+    // by name.
+    fn validate(&self) -> Result<(), ScenarioError> {
+        let earths = self
+            .bodies
+            .iter()
+            .filter(|body| body.rotation == RotationModel::Earth)
+            .count();
+        if earths != 1 {
+            return Err(ScenarioError::Invalid(format!(
+                "needs one body with the Earth rotation model, found {}",
+                earths
+            )));
+        }
+
+        for (idx, body) in self.bodies.iter().enumerate() {
+            if body.name.is_empty() {
+                return Err(ScenarioError::Invalid(format!("body {} has no name", idx)));
+            }
+            if self.bodies[..idx].iter().any(|other| other.name == body.name) {
+                return Err(ScenarioError::Invalid(format!("{} is in the file twice", body.name)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn body(&self, name: &str) -> Option<&BodyConfig> {
+        self.bodies.iter().find(|body| body.name == name)
+    }
+}
+
+#[derive(Default)]
+pub struct ScenarioLoader;
+
+impl AssetLoader for ScenarioLoader {
+    type Asset = Scenario;
+    type Settings = ();
+    type Error = ScenarioError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Scenario, ScenarioError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Scenario::parse(&String::from_utf8_lossy(&bytes))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+// What was applied last, and the entities it spawned.
+#[derive(Resource)]
+pub struct ActiveScenario {
+    pub handle: Handle<Scenario>,
+    pub applied: Scenario,
+    bodies: HashMap<String, SpawnedBody>,
+    sky: Option<Entity>,
+}
+
+#[derive(Clone, Copy)]
+struct SpawnedBody {
+    entity: Entity,
+    model: Option<Entity>, // Child entity, so swapping it leaves the body's children alone
+}
+
+pub fn scenario_path_from_args() -> String {
+    let args: Vec<String> = std::env::args().collect();
+
+    let from_args = args.iter().enumerate().find_map(|(idx, arg)| {
+        if arg == "--scenario" {
+            args.get(idx + 1).cloned()
+        } else {
+            arg.strip_prefix("--scenario=").map(str::to_string)
+        }
+    });
+
+    from_args
+        .or(std::env::var("ORBITER_SCENARIO").ok())
+        .unwrap_or_else(|| DEFAULT_SCENARIO.to_string())
+}
+
+// Read straight from disk rather than through the asset server, so the bodies are there
+// on the first frame.
+fn read_scenario(path: &str) -> Result<Scenario, ScenarioError> {
+    let full_path = FileAssetReader::get_base_path().join("assets").join(path);
+    Scenario::parse(&std::fs::read_to_string(full_path)?)
+}
+
+pub fn setup(
+    mut commands: Commands,
+    ass: Res<AssetServer>,
+    registry: Res<BodyRegistry>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let path = scenario_path_from_args();
+    let scenario = match read_scenario(&path) {
+        Ok(scenario) => scenario,
+        Err(err) => {
+            println!("Using the built-in scenario, {}: {}", path, err);
+            Scenario::default()
+        }
+    };
+
+    spawn_cameras(&mut commands, &scenario);
+
+    // Nothing applied yet, so every body and the sky get spawned.
+    let mut active = ActiveScenario {
+        handle: ass.load(path),
+        applied: Scenario {
+            bodies: Vec::new(),
+            sky: None,
+            ..scenario.clone()
+        },
+        bodies: HashMap::new(),
+        sky: None,
+    };
+    apply_scenario(
+        &mut commands,
+        &ass,
+        &registry,
+        &mut meshes,
+        &mut materials,
+        &mut active,
+        scenario,
+    );
+    commands.insert_resource(active);
+}
+
+fn spawn_cameras(commands: &mut Commands, scenario: &Scenario) {
+    let camera = &scenario.camera;
+
+    commands.spawn(SphereCamera {
+        radius: camera.radius,
+        theta: camera.theta.to_radians(),
+        phi: camera.phi.to_radians(),
+        ..Default::default()
+    });
+
+    commands.spawn(AltitudeAzimuthCamera {
+        altitude: camera.altitude.to_radians(),
+        azimuth: camera.azimuth.to_radians(),
+        roll: 0.,
+    });
+
+    let mut atmosphere = AtmosphereSettings {
+        cameraPosition: Vec3::new(1000., 0., 0.),
+        ..default()
+    };
+    scenario.atmosphere.write(&mut atmosphere);
+
+    commands.spawn((
+        Camera3dBundle {
+            transform: Transform::from_xyz(0., 20., 44.).looking_at(Vec3::Y, Vec3::Y),
+            ..default()
+        },
+        NotShadowCaster,
+        // Also what picks the camera the atmosphere post processing runs on.
+        atmosphere,
+        // The atmosphere shader reads the depth buffer.
+        DepthPrepass,
+    ));
+}
+
+pub fn reload_scenario(
+    mut events: EventReader<AssetEvent<Scenario>>,
+    scenarios: Res<Assets<Scenario>>,
+    mut active: ResMut<ActiveScenario>,
+    mut atmosphere_q: Query<&mut AtmosphereSettings>,
+    mut commands: Commands,
+    ass: Res<AssetServer>,
+    registry: Res<BodyRegistry>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for event in events.read() {
+        let id = match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => *id,
+            _ => continue,
+        };
+
+        // The first load is what setup already read, unless the file changed since.
+        let Some(scenario) = scenarios.get(id).filter(|_| id == active.handle.id()) else {
+            continue;
+        };
+        if *scenario == active.applied {
+            continue;
+        }
+
+        if scenario.atmosphere != active.applied.atmosphere {
+            for mut atmosphere in &mut atmosphere_q {
+                scenario.atmosphere.write(&mut atmosphere);
+            }
+        }
+
+        apply_scenario(
+            &mut commands,
+            &ass,
+            &registry,
+            &mut meshes,
+            &mut materials,
+            &mut active,
+            scenario.clone(),
+        );
+        println!("Reloaded scenario.");
+    }
+}
+
+// Brings the scene from `active.applied` to `scenario`.
+fn apply_scenario(
+    commands: &mut Commands,
+    ass: &AssetServer,
+    registry: &BodyRegistry,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    active: &mut ActiveScenario,
+    scenario: Scenario,
+) {
+    let ActiveScenario {
+        applied,
+        bodies,
+        sky,
+        ..
+    } = active;
+
+    bodies.retain(|name, body| {
+        let keep = scenario.body(name).is_some();
+        if !keep {
+            // Children other than the model, like a camera locked to the body, are let go.
+            if let Some(model) = body.model {
+                commands.entity(model).despawn_recursive();
+            }
+            commands.entity(body.entity).clear_children().despawn();
+            println!("Removed {} from the scene.", name);
+        }
+        keep
+    });
+
+    // Spawned up front so bodies can name parents further down the list.
+    let mut spawned: Vec<&str> = Vec::new();
+    for config in &scenario.bodies {
+        bodies.entry(config.name.clone()).or_insert_with(|| {
+            spawned.push(&config.name);
+            SpawnedBody {
+                entity: commands.spawn(SpatialBundle::default()).id(),
+                model: None,
+            }
+        });
+    }
+
+    for (focus_idx, config) in scenario.bodies.iter().enumerate() {
+        let body = bodies.get_mut(&config.name).unwrap();
+        let old_idx = applied.bodies.iter().position(|old| old.name == config.name);
+        let old = old_idx.map(|idx| &applied.bodies[idx]);
+        let entity = body.entity;
+
+        if old_idx != Some(focus_idx) {
+            commands.entity(entity).insert((
+                CelestialBody {
+                    name: config.name.clone(),
+                    focus_idx: focus_idx as i32,
+                    viewport_position: None,
+                },
+                Name::new(config.name.clone()),
+            ));
+        }
+
+        if old.map(|old| (&old.model, old.radius)) != Some((&config.model, config.radius)) {
+            if let Some(model) = body.model.take() {
+                commands.entity(model).despawn_recursive();
+            }
+            body.model = spawn_model(commands, ass, meshes, materials, config);
+            if let Some(model) = body.model {
+                commands.entity(entity).add_child(model);
+            }
+        }
+
+        if old.map(|old| old.grav_parameter) != Some(config.grav_parameter) {
+            match config.grav_parameter {
+                Some(mu) => commands.entity(entity).insert(GravParameter(mu)),
+                None => commands.entity(entity).remove::<GravParameter>(),
+            };
+        }
+
+        if old.map(|old| &old.light) != Some(&config.light) {
+            match &config.light {
+                Some(light) => commands.entity(entity).insert(DirectionalLightBundle {
+                    directional_light: DirectionalLight {
+                        illuminance: light.illuminance,
+                        color: Color::WHITE,
+                        shadows_enabled: light.shadows,
+                        ..default()
+                    },
+                    ..default()
+                }),
+                None => commands.entity(entity).remove::<DirectionalLight>(),
+            };
+        }
+
+        // Elements are about the parent, so they're redone when its mass changes too.
+        let parent_mu = |scenario: &Scenario, config: &BodyConfig| {
+            config
+                .parent
+                .as_ref()
+                .and_then(|parent| scenario.body(parent))
+                .and_then(|parent| parent.grav_parameter)
+        };
+        // A parent taken out and put back is a new entity.
+        let unchanged = old.is_some_and(|old| {
+            (&old.orbit, &old.rotation, &old.parent) == (&config.orbit, &config.rotation, &config.parent)
+                && parent_mu(applied, old) == parent_mu(&scenario, config)
+                && !config.parent.as_deref().is_some_and(|parent| spawned.contains(&parent))
+        });
+
+        if !unchanged {
+            let parent = config.parent.as_ref().and_then(|parent| {
+                let found = scenario
+                    .body(parent)
+                    .and_then(|_| bodies.get(parent).map(|body| body.entity))
+                    .or_else(|| registry.by_name(parent));
+                if found.is_none() {
+                    println!("Parent body {} for {} isn't in the scene.", parent, config.name);
+                }
+                found
+            });
+            apply_motion(commands, ass, entity, config, parent, parent_mu(&scenario, config));
+        }
+    }
+
+    if applied.sky != scenario.sky {
+        if let Some(old) = sky.take() {
+            commands.entity(old).despawn_recursive();
+        }
+        *sky = scenario.sky.as_ref().map(|config| {
+            commands
+                .spawn((
+                    SceneBundle {
+                        scene: ass.load(config.model.clone()),
+                        transform: Transform::from_scale(Vec3::splat(config.scale)),
+                        ..default()
+                    },
+                    NotShadowCaster,
+                    FollowCamera,
+                ))
+                .insert(Name::new("Sky"))
+                .id()
+        });
+    }
+
+    *applied = scenario;
+}
+
+fn spawn_model(
+    commands: &mut Commands,
+    ass: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    config: &BodyConfig,
+) -> Option<Entity> {
+    let mut model = match &config.model {
+        ModelConfig::Hidden => return None,
+        ModelConfig::Scene { path, scale, rotation } => {
+            let (x, y, z) = *rotation;
+            commands.spawn(SceneBundle {
+                scene: ass.load(path.clone()),
+                transform: Transform::from_scale(Vec3::splat(*scale)).with_rotation(Quat::from_euler(
+                    EulerRot::XYZ,
+                    x.to_radians(),
+                    y.to_radians(),
+                    z.to_radians(),
+                )),
+                ..default()
+            })
+        }
+        ModelConfig::Sphere => {
+            // Same size as the bodies Horizons files add when no radius is given.
+            let radius = if config.radius > 0. {
+                config.radius as f32 * REAL_TO_WORLD
+            } else {
+                5.
+            };
+            commands.spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::UVSphere {
+                    radius,
+                    ..default()
+                })),
+                material: materials.add(Color::rgb(0.8, 0.8, 0.8).into()),
+                ..default()
+            })
+        }
+    };
+
+    Some(model.insert(Name::new(format!("{} model", config.name))).id())
+}
+
+// Orbit, rotation and parent. The body starts over from the new settings, apart from
+// elements a Horizons file gave it, which the file won't send again. That goes for the
+// Moon too, the built-in elements only go on if no file has.
+fn apply_motion(
+    commands: &mut Commands,
+    ass: &AssetServer,
+    entity: Entity,
+    config: &BodyConfig,
+    parent: Option<Entity>,
+    parent_mu: Option<f64>,
+) {
+    let mut body = commands.entity(entity);
+    body.remove::<(
+        Propagation,
+        ForceModels,
+        ParentBody,
+        RelativePosition,
+        RelativeVelocity,
+        RestRotation,
+        SimulationPosition,
+        EarthBody,
+        MoonBody,
+        SunBody,
+        ZonalHarmonics,
+        ExponentialAtmosphere,
+        EphemerisBody,
+        TleSource,
+        Satellite,
+    )>()
+    .insert(Transform::default());
+    if !matches!(config.orbit, OrbitSource::Horizons | OrbitSource::Moon) {
+        body.remove::<(OrbitalParameters, FromHorizons)>();
+    }
+
+    if let Some(parent) = parent {
+        body.insert(ParentBody(parent));
+    }
+
+    let mut orbit = match &config.orbit {
+        OrbitSource::Fixed => {
+            body.insert(SimulationPosition::default());
+            None
+        }
+        OrbitSource::Sun => {
+            body.insert(SunBody);
+            None
+        }
+        OrbitSource::Moon => {
+            body.insert(MoonBody);
+            Some(lunar_orbit())
+        }
+        OrbitSource::Horizons => {
+            body.insert((Propagation::default(), ForceModels::default()));
+            None
+        }
+        OrbitSource::Ephemeris { planet } => {
+            body.insert(EphemerisBody {
+                body: *planet,
+                center: Planet::Sun,
+            });
+            None
+        }
+        OrbitSource::Tle { catalogue } => {
+            body.insert((
+                TleSource(ass.load(catalogue.clone())),
+                Propagation::default(),
+                ForceModels::default(),
+            ));
+            None
+        }
+        OrbitSource::Elements {
+            semimajor_axis,
+            eccentricity,
+            inclination,
+            longitude_asc_node,
+            arg_of_periapsis,
+            mean_anomaly,
+            epoch,
+            frame,
+        } => {
+            let epoch = match epoch.as_deref().map(|date| time_scale::parse_date(date, TimeScale::Tdb)) {
+                Some(Ok(seconds)) => seconds,
+                Some(Err(err)) => {
+                    println!("Elements for {} at J2000: {}", config.name, err);
+                    0.
+                }
+                None => 0.,
+            };
+
+            match parent_mu {
+                Some(mu) => Some(OrbitalParameters {
+                    semimajor_axis: *semimajor_axis,
+                    eccentricity: *eccentricity,
+                    inclination: inclination.to_radians(),
+                    longitude_asc_node: longitude_asc_node.to_radians(),
+                    arg_of_periapsis: arg_of_periapsis.to_radians(),
+                    mean_anomaly_at_epoch: mean_anomaly.to_radians(),
+                    mass_of_parent: mu / G,
+                    grav_parameter: mu,
+                    period: if *eccentricity < 1. {
+                        2. * std::f64::consts::PI * (semimajor_axis.powi(3) / mu).sqrt()
+                    } else {
+                        f64::INFINITY
+                    },
+                    epoch,
+                    frame: *frame,
+                    ..Default::default()
+                }),
+                None => {
+                    println!("{} has elements but its parent has no grav_parameter.", config.name);
+                    None
+                }
+            }
+        }
+    };
+
+    match (&config.rotation, orbit.as_mut()) {
+        (RotationModel::Uniform { period }, Some(orbit)) => orbit.rotational_period = *period,
+        (RotationModel::Uniform { .. }, None) => {
+            println!("{} needs elements to spin with a uniform rotation.", config.name)
+        }
+        (RotationModel::Earth, _) => {
+            body.insert((EarthBody, ZonalHarmonics::earth(), ExponentialAtmosphere::earth()));
+        }
+        (RotationModel::Moon, _) => {
+            body.insert(MoonBody);
+        }
+        (RotationModel::Fixed, _) => {}
+    }
+
+    if let Some(orbit) = orbit {
+        body.insert((Propagation::default(), ForceModels::default()));
+        body.add(move |mut body: EntityWorldMut| {
+            if !body.contains::<FromHorizons>() {
+                body.insert(orbit);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn default_scenario_file_matches_the_built_in_one() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/default.scenario.ron");
+        let scenario = Scenario::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+        assert_eq!(scenario, Scenario::default());
+    }
+
+    #[test]
+    fn scenario_needs_one_earth() {
+        let no_earth = "(bodies: [(name: \"Moon\", orbit: Moon, rotation: Moon)])";
+        assert!(matches!(Scenario::parse(no_earth), Err(ScenarioError::Invalid(_))));
+
+        let twice = "(bodies: [(name: \"Earth\", rotation: Earth), (name: \"Earth\")])";
+        assert!(matches!(Scenario::parse(twice), Err(ScenarioError::Invalid(_))));
+
+        let minimal = "(bodies: [(name: \"Earth\", rotation: Earth)])";
+        let scenario = Scenario::parse(minimal).unwrap();
+        assert_eq!(scenario.bodies[0].model, ModelConfig::Sphere);
+        assert_eq!(scenario.camera, CameraConfig::default());
+        assert_eq!(scenario.sky, None);
+    }
+
+    // Runs `apply_scenario` the way setup and reload_scenario do.
+    fn apply(world: &mut World, scenario: Scenario) {
+        world.run_system_once(
+            move |mut commands: Commands,
+                  ass: Res<AssetServer>,
+                  registry: Res<BodyRegistry>,
+                  mut meshes: ResMut<Assets<Mesh>>,
+                  mut materials: ResMut<Assets<StandardMaterial>>,
+                  mut active: ResMut<ActiveScenario>| {
+                apply_scenario(
+                    &mut commands,
+                    &ass,
+                    &registry,
+                    &mut meshes,
+                    &mut materials,
+                    &mut active,
+                    scenario.clone(),
+                );
+            },
+        );
+    }
+
+    fn app(scenario: &Scenario) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_resource::<BodyRegistry>()
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_asset::<Scene>()
+            .init_asset::<crate::satellites::TleCatalogue>()
+            .insert_resource(ActiveScenario {
+                handle: Handle::default(),
+                applied: Scenario {
+                    bodies: Vec::new(),
+                    sky: None,
+                    ..scenario.clone()
+                },
+                bodies: HashMap::new(),
+                sky: None,
+            });
+        app
+    }
+
+    fn body(world: &World, name: &str) -> Entity {
+        world.resource::<ActiveScenario>().bodies[name].entity
+    }
+
+    #[test]
+    fn reloading_keeps_the_moons_horizons_elements() {
+        let text = "(bodies: [
+            (name: \"Earth\", rotation: Earth, grav_parameter: Some(398600.4418)),
+            (name: \"Moon\", parent: Some(\"Earth\"), orbit: Moon, rotation: Moon),
+        ])";
+        let scenario = Scenario::parse(text).unwrap();
+        let mut app = app(&scenario);
+        let world = &mut app.world;
+
+        apply(world, scenario.clone());
+        let moon = body(world, "Moon");
+        assert_eq!(
+            world.get::<OrbitalParameters>(moon).unwrap().semimajor_axis,
+            lunar_orbit().semimajor_axis
+        );
+
+        // What apply_horizons_elements does with moon.txt.
+        let from_file = OrbitalParameters {
+            semimajor_axis: 381218.7,
+            ..lunar_orbit()
+        };
+        world.entity_mut(moon).insert((from_file, FromHorizons));
+
+        // Earth's mass changing redoes the Moon's motion.
+        let mut reloaded = scenario.clone();
+        reloaded.bodies[0].grav_parameter = Some(398600.);
+        apply(world, reloaded.clone());
+        assert_eq!(world.get::<OrbitalParameters>(moon).unwrap().semimajor_axis, 381218.7);
+        assert!(world.get::<MoonBody>(moon).is_some());
+
+        // Unless the Moon is given something else.
+        let mut fixed = reloaded.clone();
+        fixed.bodies[1].orbit = OrbitSource::Fixed;
+        apply(world, fixed);
+        assert!(world.get::<OrbitalParameters>(moon).is_none());
+        apply(world, reloaded);
+        assert_eq!(
+            world.get::<OrbitalParameters>(moon).unwrap().semimajor_axis,
+            lunar_orbit().semimajor_axis
+        );
+    }
+
+    #[test]
+    fn planets_and_satellites_come_from_the_scenario() {
+        let text = "(bodies: [
+            (name: \"Earth\", rotation: Earth),
+            (name: \"Sun\", parent: Some(\"Earth\"), orbit: Sun),
+            (name: \"Mars\", parent: Some(\"Sun\"), orbit: Ephemeris(planet: Mars)),
+            (name: \"VANGUARD 1\", parent: Some(\"Earth\"), orbit: Tle(catalogue: \"satellites.tle\")),
+        ])";
+        let scenario = Scenario::parse(text).unwrap();
+        let mut app = app(&scenario);
+        let world = &mut app.world;
+
+        apply(world, scenario.clone());
+        let (earth, sun, mars) = (body(world, "Earth"), body(world, "Sun"), body(world, "Mars"));
+        let vanguard = body(world, "VANGUARD 1");
+
+        let planets: Vec<(Entity, Planet)> = world
+            .query::<(Entity, &EphemerisBody)>()
+            .iter(world)
+            .map(|(entity, binding)| (entity, binding.body))
+            .collect();
+        assert_eq!(planets, vec![(mars, Planet::Mars)]);
+        assert_eq!(world.get::<ParentBody>(mars).unwrap().0, sun);
+        assert!(world.get::<TleSource>(vanguard).is_some());
+        assert_eq!(world.get::<ParentBody>(vanguard).unwrap().0, earth);
+
+        // The Sun taken out and put back.
+        let mut without_sun = scenario.clone();
+        without_sun.bodies.remove(1);
+        apply(world, without_sun);
+        apply(world, scenario);
+        let sun = body(world, "Sun");
+        assert_eq!(world.get::<ParentBody>(mars).unwrap().0, sun);
+    }
+
+    #[test]
+    fn scene_models_can_be_turned() {
+        let text = "(bodies: [
+            (name: \"Earth\", rotation: Earth, model: Scene(path: \"earth.glb\")),
+            (name: \"Moon\", model: Scene(path: \"moon.glb\", scale: 2., rotation: (90., 0., -45.))),
+        ])";
+        let scenario = Scenario::parse(text).unwrap();
+
+        assert!(matches!(
+            scenario.bodies[0].model,
+            ModelConfig::Scene { scale, rotation: (0., 0., 0.), .. } if scale == 1.
+        ));
+        assert!(matches!(
+            scenario.bodies[1].model,
+            ModelConfig::Scene { scale, rotation: (90., 0., -45.), .. } if scale == 2.
+        ));
+    }
+}
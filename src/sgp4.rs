@@ -0,0 +1,1274 @@
+use bevy::math::DVec3;
+use std::f64::consts::PI;
+use std::fmt;
+
+use orbiter_physics::time_scale::J2000_JD;
+
+// Two-line element sets and the SGP4/SDP4 propagator from Spacetrack Report #3, as
+// revised by Vallado et al. (2006). Output is in the TEME frame in KM and KM/s.
+//
+// Orbits with a period of 225 minutes or more (GPS, geostationary, Molniya) take the
+// deep-space branch: lunar and solar perturbations, and the 12 and 24 hour geopotential
+// resonances. Like the reference code, this runs in "improved" mode, with the IAU-82
+// sidereal time at epoch.
+
+// WGS-72 constants, which the published element sets are fitted with.
+const MU: f64 = 398600.8; // KM^3s^-2
+const EARTH_RADIUS: f64 = 6378.135; // KM
+const J2: f64 = 0.001082616;
+const J3: f64 = -0.00000253881;
+const J4: f64 = -0.00000165597;
+const MINUTES_PER_DAY: f64 = 1440.;
+const TWO_PI: f64 = 2. * PI;
+
+fn xke() -> f64 {
+    60. / (EARTH_RADIUS * EARTH_RADIUS * EARTH_RADIUS / MU).sqrt()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TleError {
+    NotAscii(usize),
+    LineTooShort(usize),
+    BadChecksum(usize),
+    InvalidField(&'static str, String),
+    MissingLine,
+}
+
+impl fmt::Display for TleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TleError::NotAscii(line) => write!(f, "line {} has characters outside ASCII", line),
+            TleError::LineTooShort(line) => write!(f, "line {} is shorter than 69 columns", line),
+            TleError::BadChecksum(line) => write!(f, "line {} fails its checksum", line),
+            TleError::InvalidField(field, value) => write!(f, "could not parse {} from \"{}\"", field, value),
+            TleError::MissingLine => write!(f, "element set is missing a line"),
+        }
+    }
+}
+
+impl std::error::Error for TleError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sgp4Error {
+    Eccentricity(f64),
+    MeanMotion(f64),
+    SemiLatusRectum(f64),
+    Decayed,
+}
+
+impl fmt::Display for Sgp4Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sgp4Error::Eccentricity(e) => write!(f, "mean eccentricity {} is out of range", e),
+            Sgp4Error::MeanMotion(n) => write!(f, "mean motion {} is not positive", n),
+            Sgp4Error::SemiLatusRectum(p) => write!(f, "semi-latus rectum {} is negative", p),
+            Sgp4Error::Decayed => write!(f, "satellite has decayed"),
+        }
+    }
+}
+
+impl std::error::Error for Sgp4Error {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoLineElements {
+    pub name: String,
+    pub catalog_number: u32,
+    pub epoch_year: i32,
+    pub epoch_day: f64,           // Day of year, 1.0 is January 1st 00:00 UTC
+    pub bstar: f64,               // Earth radii^-1
+    pub inclination: f64,         // Radians
+    pub longitude_asc_node: f64,  // Radians
+    pub eccentricity: f64,        // Unitless
+    pub arg_of_periapsis: f64,    // Radians
+    pub mean_anomaly: f64,        // Radians
+    pub mean_motion: f64,         // Radians per minute
+}
+
+impl TwoLineElements {
+    pub fn parse(name: &str, line1: &str, line2: &str) -> Result<TwoLineElements, TleError> {
+        let line1 = line1.trim_end();
+        let line2 = line2.trim_end();
+
+        // Columns are counted in bytes below, which only lines up for ASCII.
+        for (number, line) in [(1, line1), (2, line2)] {
+            if !line.is_ascii() {
+                return Err(TleError::NotAscii(number));
+            }
+            if line.len() < 69 {
+                return Err(TleError::LineTooShort(number));
+            }
+            if !checksum_ok(line) {
+                return Err(TleError::BadChecksum(number));
+            }
+        }
+
+        let two_digit_year = field::<i32>(line1, 18, 20, "epoch year")?;
+        let revs_per_day = field::<f64>(line2, 52, 63, "mean motion")?;
+
+        Ok(TwoLineElements {
+            name: name.trim().to_string(),
+            catalog_number: field(line1, 2, 7, "catalog number")?,
+            epoch_year: if two_digit_year < 57 { 2000 + two_digit_year } else { 1900 + two_digit_year },
+            epoch_day: field(line1, 20, 32, "epoch day")?,
+            bstar: implied_decimal(&line1[53..61], "bstar")?,
+            inclination: field::<f64>(line2, 8, 16, "inclination")?.to_radians(),
+            longitude_asc_node: field::<f64>(line2, 17, 25, "longitude of ascending node")?.to_radians(),
+            eccentricity: field::<f64>(line2, 26, 33, "eccentricity")? * 1e-7,
+            arg_of_periapsis: field::<f64>(line2, 34, 42, "argument of periapsis")?.to_radians(),
+            mean_anomaly: field::<f64>(line2, 43, 51, "mean anomaly")?.to_radians(),
+            mean_motion: revs_per_day * TWO_PI / MINUTES_PER_DAY,
+        })
+    }
+
+    // Reads every element set in a catalogue, with or without name lines.
+    pub fn parse_catalogue(text: &str) -> Result<Vec<TwoLineElements>, TleError> {
+        let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+        let mut sets = Vec::new();
+        let mut i = 0;
+
+        while i < lines.len() {
+            let (name, first) = if lines[i].starts_with("1 ") {
+                ("", i)
+            } else {
+                (lines[i], i + 1)
+            };
+
+            if first + 1 >= lines.len() {
+                return Err(TleError::MissingLine);
+            }
+
+            let mut set = TwoLineElements::parse(name, lines[first], lines[first + 1])?;
+            if set.name.is_empty() {
+                set.name = set.catalog_number.to_string();
+            }
+            sets.push(set);
+            i = first + 2;
+        }
+
+        Ok(sets)
+    }
+
+    pub fn epoch_jd(&self) -> f64 {
+        // Julian Day Number of January 0th of the epoch year.
+        let year = self.epoch_year as f64;
+        let jan_0 = 367. * year - (7. * year / 4.).floor() + 30. + 1721013.5;
+        jan_0 + self.epoch_day
+    }
+
+    // Seconds past J2000
+    pub fn epoch(&self) -> f64 {
+        (self.epoch_jd() - J2000_JD) * 86400.
+    }
+}
+
+fn field<T: std::str::FromStr>(line: &str, start: usize, end: usize, name: &'static str) -> Result<T, TleError> {
+    let value = line[start..end].trim();
+    value
+        .parse::<T>()
+        .map_err(|_| TleError::InvalidField(name, value.to_string()))
+}
+
+// " 28098-4" -> 0.28098e-4
+fn implied_decimal(value: &str, name: &'static str) -> Result<f64, TleError> {
+    let trimmed = value.trim();
+    let invalid = || TleError::InvalidField(name, trimmed.to_string());
+
+    let (sign, digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => (-1., rest),
+        None => (1., trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+
+    let split = digits.rfind(['-', '+']).ok_or_else(invalid)?;
+    let mantissa = format!("0.{}", &digits[..split]).parse::<f64>().map_err(|_| invalid())?;
+    let exponent = digits[split..].parse::<i32>().map_err(|_| invalid())?;
+
+    Ok(sign * mantissa * 10f64.powi(exponent))
+}
+
+// Sum of the digits in the first 68 columns, minus signs counting as one, mod 10.
+fn checksum_ok(line: &str) -> bool {
+    let sum: u32 = line[..68]
+        .chars()
+        .map(|c| match c {
+            '-' => 1,
+            c => c.to_digit(10).unwrap_or(0),
+        })
+        .sum();
+
+    line[68..69].parse::<u32>().is_ok_and(|checksum| sum % 10 == checksum)
+}
+
+// Initialised SGP4 state for one element set.
+#[derive(Debug, Clone)]
+pub struct Sgp4 {
+    pub elements: TwoLineElements,
+    simple: bool,
+    mean_motion: f64, // Un-Kozai'd, radians per minute
+    con41: f64,
+    x1mth2: f64,
+    x7thm1: f64,
+    eta: f64,
+    cc1: f64,
+    cc4: f64,
+    cc5: f64,
+    d2: f64,
+    d3: f64,
+    d4: f64,
+    delmo: f64,
+    sinmao: f64,
+    mdot: f64,
+    argpdot: f64,
+    nodedot: f64,
+    nodecf: f64,
+    omgcof: f64,
+    xmcof: f64,
+    t2cof: f64,
+    t3cof: f64,
+    t4cof: f64,
+    t5cof: f64,
+    xlcof: f64,
+    aycof: f64,
+    deep: Option<DeepSpace>,
+}
+
+impl Sgp4 {
+    pub fn new(elements: TwoLineElements) -> Result<Sgp4, Sgp4Error> {
+        let xke = xke();
+        let j3oj2 = J3 / J2;
+        let ecco = elements.eccentricity;
+        let inclo = elements.inclination;
+        let argpo = elements.arg_of_periapsis;
+
+        // Recover the original mean motion and semimajor axis from the Kozai mean motion.
+        let eccsq = ecco * ecco;
+        let omeosq = 1. - eccsq;
+        let rteosq = omeosq.sqrt();
+        let cosio = inclo.cos();
+        let cosio2 = cosio * cosio;
+        let ak = (xke / elements.mean_motion).powf(2. / 3.);
+        let d1 = 0.75 * J2 * (3. * cosio2 - 1.) / (rteosq * omeosq);
+        let del = d1 / (ak * ak);
+        let adel = ak * (1. - del * del - del * (1. / 3. + 134. * del * del / 81.));
+        let del = d1 / (adel * adel);
+        let mean_motion = elements.mean_motion / (1. + del);
+        let deep_space = TWO_PI / mean_motion >= 225.;
+
+        let ao = (xke / mean_motion).powf(2. / 3.);
+        let sinio = inclo.sin();
+        let po = ao * omeosq;
+        let con42 = 1. - 5. * cosio2;
+        let con41 = -con42 - cosio2 - cosio2;
+        let posq = po * po;
+        let rp = ao * (1. - ecco);
+
+        // Perigees under 220 km use a simplified drag model, and so does deep space.
+        let simple = rp < 220. / EARTH_RADIUS + 1. || deep_space;
+
+        let mut sfour = 78. / EARTH_RADIUS + 1.;
+        let mut qzms24 = ((120. - 78.) / EARTH_RADIUS).powi(4);
+        let perigee = (rp - 1.) * EARTH_RADIUS;
+
+        if perigee < 156. {
+            sfour = if perigee < 98. { 20. } else { perigee - 78. };
+            qzms24 = ((120. - sfour) / EARTH_RADIUS).powi(4);
+            sfour = sfour / EARTH_RADIUS + 1.;
+        }
+
+        let pinvsq = 1. / posq;
+        let tsi = 1. / (ao - sfour);
+        let eta = ao * ecco * tsi;
+        let etasq = eta * eta;
+        let eeta = ecco * eta;
+        let psisq = (1. - etasq).abs();
+        let coef = qzms24 * tsi.powi(4);
+        let coef1 = coef / psisq.powf(3.5);
+        let cc2 = coef1
+            * mean_motion
+            * (ao * (1. + 1.5 * etasq + eeta * (4. + etasq))
+                + 0.375 * J2 * tsi / psisq * con41 * (8. + 3. * etasq * (8. + etasq)));
+        let cc1 = elements.bstar * cc2;
+        let cc3 = if ecco > 1.0e-4 {
+            -2. * coef * tsi * j3oj2 * mean_motion * sinio / ecco
+        } else {
+            0.
+        };
+        let x1mth2 = 1. - cosio2;
+        let cc4 = 2.
+            * mean_motion
+            * coef1
+            * ao
+            * omeosq
+            * (eta * (2. + 0.5 * etasq) + ecco * (0.5 + 2. * etasq)
+                - J2 * tsi / (ao * psisq)
+                    * (-3. * con41 * (1. - 2. * eeta + etasq * (1.5 - 0.5 * eeta))
+                        + 0.75 * x1mth2 * (2. * etasq - eeta * (1. + etasq)) * (2. * argpo).cos()));
+        let cc5 = 2. * coef1 * ao * omeosq * (1. + 2.75 * (etasq + eeta) + eeta * etasq);
+
+        let cosio4 = cosio2 * cosio2;
+        let temp1 = 1.5 * J2 * pinvsq * mean_motion;
+        let temp2 = 0.5 * temp1 * J2 * pinvsq;
+        let temp3 = -0.46875 * J4 * pinvsq * pinvsq * mean_motion;
+        let mdot = mean_motion
+            + 0.5 * temp1 * rteosq * con41
+            + 0.0625 * temp2 * rteosq * (13. - 78. * cosio2 + 137. * cosio4);
+        let argpdot = -0.5 * temp1 * con42
+            + 0.0625 * temp2 * (7. - 114. * cosio2 + 395. * cosio4)
+            + temp3 * (3. - 36. * cosio2 + 49. * cosio4);
+        let xhdot1 = -temp1 * cosio;
+        let nodedot = xhdot1 + (0.5 * temp2 * (4. - 19. * cosio2) + 2. * temp3 * (3. - 7. * cosio2)) * cosio;
+        let omgcof = elements.bstar * cc3 * argpo.cos();
+        let xmcof = if ecco > 1.0e-4 {
+            -2. / 3. * coef * elements.bstar / eeta
+        } else {
+            0.
+        };
+        let nodecf = 3.5 * omeosq * xhdot1 * cc1;
+        let t2cof = 1.5 * cc1;
+        let xlcof = if (cosio + 1.).abs() > 1.5e-12 {
+            -0.25 * j3oj2 * sinio * (3. + 5. * cosio) / (1. + cosio)
+        } else {
+            -0.25 * j3oj2 * sinio * (3. + 5. * cosio) / 1.5e-12
+        };
+        let aycof = -0.5 * j3oj2 * sinio;
+        let delmo = (1. + eta * elements.mean_anomaly.cos()).powi(3);
+        let sinmao = elements.mean_anomaly.sin();
+        let x7thm1 = 7. * cosio2 - 1.;
+
+        let (mut d2, mut d3, mut d4, mut t3cof, mut t4cof, mut t5cof) = (0., 0., 0., 0., 0., 0.);
+
+        if !simple {
+            let cc1sq = cc1 * cc1;
+            d2 = 4. * ao * tsi * cc1sq;
+            let temp = d2 * tsi * cc1 / 3.;
+            d3 = (17. * ao + sfour) * temp;
+            d4 = 0.5 * temp * ao * tsi * (221. * ao + 31. * sfour) * cc1;
+            t3cof = d2 + 2. * cc1sq;
+            t4cof = 0.25 * (3. * d3 + cc1 * (12. * d2 + 10. * cc1sq));
+            t5cof = 0.2 * (3. * d4 + 12. * cc1 * d3 + 6. * d2 * d2 + 15. * cc1sq * (2. * d2 + cc1sq));
+        }
+
+        let deep = deep_space.then(|| {
+            DeepSpace::new(&elements, mean_motion, eccsq, mdot, argpdot, nodedot)
+        });
+
+        Ok(Sgp4 {
+            elements,
+            simple,
+            mean_motion,
+            con41,
+            x1mth2,
+            x7thm1,
+            eta,
+            cc1,
+            cc4,
+            cc5,
+            d2,
+            d3,
+            d4,
+            delmo,
+            sinmao,
+            mdot,
+            argpdot,
+            nodedot,
+            nodecf,
+            omgcof,
+            xmcof,
+            t2cof,
+            t3cof,
+            t4cof,
+            t5cof,
+            xlcof,
+            aycof,
+            deep,
+        })
+    }
+
+    // Position (KM) and velocity (KM/s) in TEME, `t` seconds past J2000.
+    pub fn state_at(&self, t: f64) -> Result<(DVec3, DVec3), Sgp4Error> {
+        self.propagate((t - self.elements.epoch()) / 60.)
+    }
+
+    // Position (KM) and velocity (KM/s) in TEME, `tsince` minutes past the element epoch.
+    pub fn propagate(&self, tsince: f64) -> Result<(DVec3, DVec3), Sgp4Error> {
+        let xke = xke();
+        let el = &self.elements;
+        let t = tsince;
+
+        // Secular gravity and atmospheric drag.
+        let xmdf = el.mean_anomaly + self.mdot * t;
+        let argpdf = el.arg_of_periapsis + self.argpdot * t;
+        let nodedf = el.longitude_asc_node + self.nodedot * t;
+        let mut argpm = argpdf;
+        let mut mm = xmdf;
+        let t2 = t * t;
+        let mut nodem = nodedf + self.nodecf * t2;
+        let mut tempa = 1. - self.cc1 * t;
+        let mut tempe = el.bstar * self.cc4 * t;
+        let mut templ = self.t2cof * t2;
+
+        if !self.simple {
+            let delomg = self.omgcof * t;
+            let delm = self.xmcof * ((1. + self.eta * xmdf.cos()).powi(3) - self.delmo);
+            let temp = delomg + delm;
+            mm = xmdf + temp;
+            argpm = argpdf - temp;
+            let t3 = t2 * t;
+            let t4 = t3 * t;
+            tempa = tempa - self.d2 * t2 - self.d3 * t3 - self.d4 * t4;
+            tempe += el.bstar * self.cc5 * (mm.sin() - self.sinmao);
+            templ += self.t3cof * t3 + t4 * (self.t4cof + t * self.t5cof);
+        }
+
+        let mut nm = self.mean_motion;
+        let mut em = el.eccentricity;
+        let mut inclm = el.inclination;
+
+        if let Some(deep) = &self.deep {
+            let mut mean = MeanElements { em, inclm, argpm, nodem, mm, nm };
+            deep.secular(t, el.arg_of_periapsis, self.argpdot, self.mean_motion, &mut mean);
+            MeanElements { em, inclm, argpm, nodem, mm, nm } = mean;
+        }
+
+        if nm <= 0. {
+            return Err(Sgp4Error::MeanMotion(nm));
+        }
+
+        let am = (xke / nm).powf(2. / 3.) * tempa * tempa;
+        nm = xke / am.powf(1.5);
+        em -= tempe;
+
+        if !(-0.001..1.).contains(&em) {
+            return Err(Sgp4Error::Eccentricity(em));
+        }
+        em = em.max(1.0e-6);
+
+        mm += self.mean_motion * templ;
+        let xlm = (mm + argpm + nodem) % TWO_PI;
+        nodem %= TWO_PI;
+        argpm %= TWO_PI;
+        mm = (xlm - argpm - nodem) % TWO_PI;
+
+        // Lunar and solar periodics.
+        let (mut ep, mut xincp, mut argpp, mut nodep, mut mp) = (em, inclm, argpm, nodem, mm);
+        let (mut xlcof, mut aycof, mut con41, mut x1mth2, mut x7thm1) =
+            (self.xlcof, self.aycof, self.con41, self.x1mth2, self.x7thm1);
+
+        if let Some(deep) = &self.deep {
+            let mut perturbed = MeanElements { em, inclm, argpm, nodem, mm, nm };
+            deep.periodics(t, &mut perturbed);
+            (ep, xincp, argpp, nodep, mp) = (
+                perturbed.em,
+                perturbed.inclm,
+                perturbed.argpm,
+                perturbed.nodem,
+                perturbed.mm,
+            );
+
+            if xincp < 0. {
+                xincp = -xincp;
+                nodep += PI;
+                argpp -= PI;
+            }
+            if !(0. ..=1.).contains(&ep) {
+                return Err(Sgp4Error::Eccentricity(ep));
+            }
+
+            // The coefficients that depend on the inclination follow the perturbed one.
+            let (sinip, cosip) = xincp.sin_cos();
+            let j3oj2 = J3 / J2;
+            aycof = -0.5 * j3oj2 * sinip;
+            xlcof = if (cosip + 1.).abs() > 1.5e-12 {
+                -0.25 * j3oj2 * sinip * (3. + 5. * cosip) / (1. + cosip)
+            } else {
+                -0.25 * j3oj2 * sinip * (3. + 5. * cosip) / 1.5e-12
+            };
+            let cosisq = cosip * cosip;
+            con41 = 3. * cosisq - 1.;
+            x1mth2 = 1. - cosisq;
+            x7thm1 = 7. * cosisq - 1.;
+        }
+
+        // Long period periodics.
+        let sinip = xincp.sin();
+        let cosip = xincp.cos();
+        let axnl = ep * argpp.cos();
+        let temp = 1. / (am * (1. - ep * ep));
+        let aynl = ep * argpp.sin() + temp * aycof;
+        let xl = mp + argpp + nodep + temp * xlcof * axnl;
+
+        // Kepler's equation in terms of the equinoctial elements.
+        let u = (xl - nodep) % TWO_PI;
+        let mut eo1 = u;
+        let mut tem5: f64 = 9999.9;
+        let mut sineo1 = 0.;
+        let mut coseo1 = 0.;
+        let mut count = 1;
+
+        while tem5.abs() >= 1.0e-12 && count <= 10 {
+            sineo1 = eo1.sin();
+            coseo1 = eo1.cos();
+            tem5 = 1. - coseo1 * axnl - sineo1 * aynl;
+            tem5 = (u - aynl * coseo1 + axnl * sineo1 - eo1) / tem5;
+            tem5 = tem5.clamp(-0.95, 0.95);
+            eo1 += tem5;
+            count += 1;
+        }
+
+        // Short period periodics.
+        let ecose = axnl * coseo1 + aynl * sineo1;
+        let esine = axnl * sineo1 - aynl * coseo1;
+        let el2 = axnl * axnl + aynl * aynl;
+        let pl = am * (1. - el2);
+
+        if pl < 0. {
+            return Err(Sgp4Error::SemiLatusRectum(pl));
+        }
+
+        let rl = am * (1. - ecose);
+        let rdotl = am.sqrt() * esine / rl;
+        let rvdotl = pl.sqrt() / rl;
+        let betal = (1. - el2).sqrt();
+        let temp = esine / (1. + betal);
+        let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+        let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+        let mut su = sinu.atan2(cosu);
+        let sin2u = (cosu + cosu) * sinu;
+        let cos2u = 1. - 2. * sinu * sinu;
+        let temp = 1. / pl;
+        let temp1 = 0.5 * J2 * temp;
+        let temp2 = temp1 * temp;
+
+        let mrt = rl * (1. - 1.5 * temp2 * betal * con41) + 0.5 * temp1 * x1mth2 * cos2u;
+        su -= 0.25 * temp2 * x7thm1 * sin2u;
+        let xnode = nodep + 1.5 * temp2 * cosip * sin2u;
+        let xinc = xincp + 1.5 * temp2 * cosip * sinip * cos2u;
+        let mvt = rdotl - nm * temp1 * x1mth2 * sin2u / xke;
+        let rvdot = rvdotl + nm * temp1 * (x1mth2 * cos2u + 1.5 * con41) / xke;
+
+        // Orientation vectors.
+        let (sinsu, cossu) = su.sin_cos();
+        let (snod, cnod) = xnode.sin_cos();
+        let (sini, cosi) = xinc.sin_cos();
+        let xmx = -snod * cosi;
+        let xmy = cnod * cosi;
+        let u = DVec3::new(xmx * sinsu + cnod * cossu, xmy * sinsu + snod * cossu, sini * sinsu);
+        let v = DVec3::new(xmx * cossu - cnod * sinsu, xmy * cossu - snod * sinsu, sini * cossu);
+
+        if mrt < 1. {
+            return Err(Sgp4Error::Decayed);
+        }
+
+        let km_per_sec = EARTH_RADIUS * xke / 60.;
+        Ok((
+            mrt * EARTH_RADIUS * u,
+            (mvt * u + rvdot * v) * km_per_sec,
+        ))
+    }
+}
+
+// Mean elements on their way through the deep-space corrections, named as in the
+// reference code.
+#[derive(Debug, Clone, Copy)]
+struct MeanElements {
+    em: f64,
+    inclm: f64,
+    argpm: f64,
+    nodem: f64,
+    mm: f64,
+    nm: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Resonance {
+    None,
+    Synchronous, // 24 hour orbits
+    HalfDay,     // 12 hour orbits with e >= 0.5
+}
+
+// Lunar and solar terms and the resonance set-up for a deep-space element set, what
+// `dscom` and `dsinit` compute in the reference code.
+#[derive(Debug, Clone)]
+struct DeepSpace {
+    // Periodics
+    e3: f64,
+    ee2: f64,
+    se2: f64,
+    se3: f64,
+    sgh2: f64,
+    sgh3: f64,
+    sgh4: f64,
+    sh2: f64,
+    sh3: f64,
+    si2: f64,
+    si3: f64,
+    sl2: f64,
+    sl3: f64,
+    sl4: f64,
+    xgh2: f64,
+    xgh3: f64,
+    xgh4: f64,
+    xh2: f64,
+    xh3: f64,
+    xi2: f64,
+    xi3: f64,
+    xl2: f64,
+    xl3: f64,
+    xl4: f64,
+    zmol: f64,
+    zmos: f64,
+
+    // Secular rates and resonances
+    gsto: f64,
+    dedt: f64,
+    didt: f64,
+    dmdt: f64,
+    dnodt: f64,
+    domdt: f64,
+    resonance: Resonance,
+    d2201: f64,
+    d2211: f64,
+    d3210: f64,
+    d3222: f64,
+    d4410: f64,
+    d4422: f64,
+    d5220: f64,
+    d5232: f64,
+    d5421: f64,
+    d5433: f64,
+    del1: f64,
+    del2: f64,
+    del3: f64,
+    xfact: f64,
+    xlamo: f64,
+}
+
+// One body's contribution in `dscom`, the Sun's or the Moon's.
+struct ThirdBodyTerms {
+    s1: f64,
+    s2: f64,
+    s3: f64,
+    s4: f64,
+    s5: f64,
+    s6: f64,
+    s7: f64,
+    z1: f64,
+    z2: f64,
+    z3: f64,
+    z11: f64,
+    z12: f64,
+    z13: f64,
+    z21: f64,
+    z22: f64,
+    z23: f64,
+    z31: f64,
+    z32: f64,
+    z33: f64,
+}
+
+const ZES: f64 = 0.01675;
+const ZEL: f64 = 0.05490;
+const ZNS: f64 = 1.19459e-5;
+const ZNL: f64 = 1.5835218e-4;
+const RPTIM: f64 = 4.375_269_088_011_3e-3; // Earth's rotation, radians per minute
+
+// Greenwich mean sidereal time (IAU-82) in radians for a UT1 Julian Date.
+fn greenwich_sidereal_time(jd_ut1: f64) -> f64 {
+    let tut1 = (jd_ut1 - 2451545.) / 36525.;
+    let seconds = -6.2e-6 * tut1 * tut1 * tut1
+        + 0.093104 * tut1 * tut1
+        + (876600. * 3600. + 8640184.812866) * tut1
+        + 67310.54841;
+
+    (seconds.to_radians() / 240.).rem_euclid(TWO_PI)
+}
+
+impl DeepSpace {
+    fn new(
+        elements: &TwoLineElements,
+        no: f64,
+        eccsq: f64,
+        mdot: f64,
+        argpdot: f64,
+        nodedot: f64,
+    ) -> DeepSpace {
+        let ecco = elements.eccentricity;
+        let inclo = elements.inclination;
+        let nodeo = elements.longitude_asc_node;
+        let argpo = elements.arg_of_periapsis;
+        let mo = elements.mean_anomaly;
+        let gsto = greenwich_sidereal_time(elements.epoch_jd());
+
+        // dscom: the Sun's and Moon's positions at epoch and their effect on the elements.
+        let (snodm, cnodm) = nodeo.sin_cos();
+        let (sinomm, cosomm) = argpo.sin_cos();
+        let (sinim, cosim) = inclo.sin_cos();
+        let emsq = ecco * ecco;
+        let betasq = 1. - emsq;
+        let rtemsq = betasq.sqrt();
+
+        // Days since 1950 January 0.0, the reference code's epoch.
+        let day = elements.epoch_jd() - 2433281.5 + 18261.5;
+        let xnodce = (4.5236020 - 9.2422029e-4 * day) % TWO_PI;
+        let (stem, ctem) = xnodce.sin_cos();
+        let zcosil = 0.91375164 - 0.03568096 * ctem;
+        let zsinil = (1. - zcosil * zcosil).sqrt();
+        let zsinhl = 0.089683511 * stem / zsinil;
+        let zcoshl = (1. - zsinhl * zsinhl).sqrt();
+        let gam = 5.8351514 + 0.0019443680 * day;
+        let zx = (0.39785416 * stem / zsinil).atan2(zcoshl * ctem + 0.91744867 * zsinhl * stem);
+        let zx = gam + zx - xnodce;
+        let (zsingl, zcosgl) = zx.sin_cos();
+
+        let terms = |zcosg: f64, zsing: f64, zcosi: f64, zsini: f64, zcosh: f64, zsinh: f64, cc: f64| {
+            let a1 = zcosg * zcosh + zsing * zcosi * zsinh;
+            let a3 = -zsing * zcosh + zcosg * zcosi * zsinh;
+            let a7 = -zcosg * zsinh + zsing * zcosi * zcosh;
+            let a8 = zsing * zsini;
+            let a9 = zsing * zsinh + zcosg * zcosi * zcosh;
+            let a10 = zcosg * zsini;
+            let a2 = cosim * a7 + sinim * a8;
+            let a4 = cosim * a9 + sinim * a10;
+            let a5 = -sinim * a7 + cosim * a8;
+            let a6 = -sinim * a9 + cosim * a10;
+
+            let x1 = a1 * cosomm + a2 * sinomm;
+            let x2 = a3 * cosomm + a4 * sinomm;
+            let x3 = -a1 * sinomm + a2 * cosomm;
+            let x4 = -a3 * sinomm + a4 * cosomm;
+            let x5 = a5 * sinomm;
+            let x6 = a6 * sinomm;
+            let x7 = a5 * cosomm;
+            let x8 = a6 * cosomm;
+
+            let z31 = 12. * x1 * x1 - 3. * x3 * x3;
+            let z32 = 24. * x1 * x2 - 6. * x3 * x4;
+            let z33 = 12. * x2 * x2 - 3. * x4 * x4;
+            let z1 = 3. * (a1 * a1 + a2 * a2) + z31 * emsq;
+            let z2 = 6. * (a1 * a3 + a2 * a4) + z32 * emsq;
+            let z3 = 3. * (a3 * a3 + a4 * a4) + z33 * emsq;
+            let z11 = -6. * a1 * a5 + emsq * (-24. * x1 * x7 - 6. * x3 * x5);
+            let z12 = -6. * (a1 * a6 + a3 * a5)
+                + emsq * (-24. * (x2 * x7 + x1 * x8) - 6. * (x3 * x6 + x4 * x5));
+            let z13 = -6. * a3 * a6 + emsq * (-24. * x2 * x8 - 6. * x4 * x6);
+            let z21 = 6. * a2 * a5 + emsq * (24. * x1 * x5 - 6. * x3 * x7);
+            let z22 = 6. * (a4 * a5 + a2 * a6)
+                + emsq * (24. * (x2 * x5 + x1 * x6) - 6. * (x4 * x7 + x3 * x8));
+            let z23 = 6. * a4 * a6 + emsq * (24. * x2 * x6 - 6. * x4 * x8);
+            let s3 = cc / no;
+            let s4 = s3 * rtemsq;
+
+            ThirdBodyTerms {
+                s1: -15. * ecco * s4,
+                s2: -0.5 * s3 / rtemsq,
+                s3,
+                s4,
+                s5: x1 * x3 + x2 * x4,
+                s6: x2 * x3 + x1 * x4,
+                s7: x2 * x4 - x1 * x3,
+                z1: z1 + z1 + betasq * z31,
+                z2: z2 + z2 + betasq * z32,
+                z3: z3 + z3 + betasq * z33,
+                z11,
+                z12,
+                z13,
+                z21,
+                z22,
+                z23,
+                z31,
+                z32,
+                z33,
+            }
+        };
+
+        let sun = terms(0.1945905, -0.98088458, 0.91744867, 0.39785416, cnodm, snodm, 2.9864797e-6);
+        let moon = terms(
+            zcosgl,
+            zsingl,
+            zcosil,
+            zsinil,
+            zcoshl * cnodm + zsinhl * snodm,
+            snodm * zcoshl - cnodm * zsinhl,
+            4.7968065e-7,
+        );
+
+        let zmol = (4.7199672 + 0.22997150 * day - gam) % TWO_PI;
+        let zmos = (6.2565837 + 0.017201977 * day) % TWO_PI;
+
+        // dsinit: secular rates from the same terms, then the resonances.
+        let shallow = !(5.2359877e-2..=PI - 5.2359877e-2).contains(&inclo);
+        let ses = sun.s1 * ZNS * sun.s5;
+        let sis = sun.s2 * ZNS * (sun.z11 + sun.z13);
+        let sls = -ZNS * sun.s3 * (sun.z1 + sun.z3 - 14. - 6. * emsq);
+        let sghs = sun.s4 * ZNS * (sun.z31 + sun.z33 - 6.);
+        let mut shs = if shallow { 0. } else { -ZNS * sun.s2 * (sun.z21 + sun.z23) };
+        if sinim != 0. {
+            shs /= sinim;
+        }
+        let sgs = sghs - cosim * shs;
+
+        let dedt = ses + moon.s1 * ZNL * moon.s5;
+        let didt = sis + moon.s2 * ZNL * (moon.z11 + moon.z13);
+        let dmdt = sls - ZNL * moon.s3 * (moon.z1 + moon.z3 - 14. - 6. * emsq);
+        let sghl = moon.s4 * ZNL * (moon.z31 + moon.z33 - 6.);
+        let shll = if shallow { 0. } else { -ZNL * moon.s2 * (moon.z21 + moon.z23) };
+        let mut domdt = sgs + sghl;
+        let mut dnodt = shs;
+        if sinim != 0. {
+            domdt -= cosim / sinim * shll;
+            dnodt += shll / sinim;
+        }
+
+        let resonance = if no < 0.0052359877 && no > 0.0034906585 {
+            Resonance::Synchronous
+        } else if (8.26e-3..=9.24e-3).contains(&no) && ecco >= 0.5 {
+            Resonance::HalfDay
+        } else {
+            Resonance::None
+        };
+
+        let mut deep = DeepSpace {
+            e3: 2. * moon.s1 * moon.s7,
+            ee2: 2. * moon.s1 * moon.s6,
+            se2: 2. * sun.s1 * sun.s6,
+            se3: 2. * sun.s1 * sun.s7,
+            sgh2: 2. * sun.s4 * sun.z32,
+            sgh3: 2. * sun.s4 * (sun.z33 - sun.z31),
+            sgh4: -18. * sun.s4 * ZES,
+            sh2: -2. * sun.s2 * sun.z22,
+            sh3: -2. * sun.s2 * (sun.z23 - sun.z21),
+            si2: 2. * sun.s2 * sun.z12,
+            si3: 2. * sun.s2 * (sun.z13 - sun.z11),
+            sl2: -2. * sun.s3 * sun.z2,
+            sl3: -2. * sun.s3 * (sun.z3 - sun.z1),
+            sl4: -2. * sun.s3 * (-21. - 9. * emsq) * ZES,
+            xgh2: 2. * moon.s4 * moon.z32,
+            xgh3: 2. * moon.s4 * (moon.z33 - moon.z31),
+            xgh4: -18. * moon.s4 * ZEL,
+            xh2: -2. * moon.s2 * moon.z22,
+            xh3: -2. * moon.s2 * (moon.z23 - moon.z21),
+            xi2: 2. * moon.s2 * moon.z12,
+            xi3: 2. * moon.s2 * (moon.z13 - moon.z11),
+            xl2: -2. * moon.s3 * moon.z2,
+            xl3: -2. * moon.s3 * (moon.z3 - moon.z1),
+            xl4: -2. * moon.s3 * (-21. - 9. * emsq) * ZEL,
+            zmol,
+            zmos,
+            gsto,
+            dedt,
+            didt,
+            dmdt,
+            dnodt,
+            domdt,
+            resonance,
+            d2201: 0.,
+            d2211: 0.,
+            d3210: 0.,
+            d3222: 0.,
+            d4410: 0.,
+            d4422: 0.,
+            d5220: 0.,
+            d5232: 0.,
+            d5421: 0.,
+            d5433: 0.,
+            del1: 0.,
+            del2: 0.,
+            del3: 0.,
+            xfact: 0.,
+            xlamo: 0.,
+        };
+
+        let theta = gsto;
+        let aonv = (no / xke()).powf(2. / 3.);
+
+        match resonance {
+            Resonance::None => {}
+            Resonance::HalfDay => {
+                let cosisq = cosim * cosim;
+                let em = ecco;
+                let emsq = eccsq;
+                let eoc = em * emsq;
+                let g201 = -0.306 - (em - 0.64) * 0.440;
+
+                let (g211, g310, g322, g410, g422, g520) = if em <= 0.65 {
+                    (
+                        3.616 - 13.2470 * em + 16.2900 * emsq,
+                        -19.302 + 117.3900 * em - 228.4190 * emsq + 156.5910 * eoc,
+                        -18.9068 + 109.7927 * em - 214.6334 * emsq + 146.5816 * eoc,
+                        -41.122 + 242.6940 * em - 471.0940 * emsq + 313.9530 * eoc,
+                        -146.407 + 841.8800 * em - 1629.014 * emsq + 1083.4350 * eoc,
+                        -532.114 + 3017.977 * em - 5740.032 * emsq + 3708.2760 * eoc,
+                    )
+                } else {
+                    (
+                        -72.099 + 331.819 * em - 508.738 * emsq + 266.724 * eoc,
+                        -346.844 + 1582.851 * em - 2415.925 * emsq + 1246.113 * eoc,
+                        -342.585 + 1554.908 * em - 2366.899 * emsq + 1215.972 * eoc,
+                        -1052.797 + 4758.686 * em - 7193.992 * emsq + 3651.957 * eoc,
+                        -3581.690 + 16178.110 * em - 24462.770 * emsq + 12422.520 * eoc,
+                        if em > 0.715 {
+                            -5149.66 + 29936.92 * em - 54087.36 * emsq + 31324.56 * eoc
+                        } else {
+                            1464.74 - 4664.75 * em + 3763.64 * emsq
+                        },
+                    )
+                };
+
+                let (g533, g521, g532) = if em < 0.7 {
+                    (
+                        -919.22770 + 4988.6100 * em - 9064.7700 * emsq + 5542.21 * eoc,
+                        -822.71072 + 4568.6173 * em - 8491.4146 * emsq + 5337.524 * eoc,
+                        -853.66600 + 4690.2500 * em - 8624.7700 * emsq + 5341.4 * eoc,
+                    )
+                } else {
+                    (
+                        -37995.780 + 161616.52 * em - 229838.20 * emsq + 109377.94 * eoc,
+                        -51752.104 + 218913.95 * em - 309468.16 * emsq + 146349.42 * eoc,
+                        -40023.880 + 170470.89 * em - 242699.48 * emsq + 115605.82 * eoc,
+                    )
+                };
+
+                let sini2 = sinim * sinim;
+                let f220 = 0.75 * (1. + 2. * cosim + cosisq);
+                let f221 = 1.5 * sini2;
+                let f321 = 1.875 * sinim * (1. - 2. * cosim - 3. * cosisq);
+                let f322 = -1.875 * sinim * (1. + 2. * cosim - 3. * cosisq);
+                let f441 = 35. * sini2 * f220;
+                let f442 = 39.3750 * sini2 * sini2;
+                let f522 = 9.84375
+                    * sinim
+                    * (sini2 * (1. - 2. * cosim - 5. * cosisq)
+                        + 0.33333333 * (-2. + 4. * cosim + 6. * cosisq));
+                let f523 = sinim
+                    * (4.92187512 * sini2 * (-2. - 4. * cosim + 10. * cosisq)
+                        + 6.56250012 * (1. + 2. * cosim - 3. * cosisq));
+                let f542 = 29.53125
+                    * sinim
+                    * (2. - 8. * cosim + cosisq * (-12. + 8. * cosim + 10. * cosisq));
+                let f543 = 29.53125
+                    * sinim
+                    * (-2. - 8. * cosim + cosisq * (12. + 8. * cosim - 10. * cosisq));
+
+                let xno2 = no * no;
+                let ainv2 = aonv * aonv;
+                let mut temp1 = 3. * xno2 * ainv2;
+                let mut temp = temp1 * 1.7891679e-6;
+                deep.d2201 = temp * f220 * g201;
+                deep.d2211 = temp * f221 * g211;
+                temp1 *= aonv;
+                temp = temp1 * 3.7393792e-7;
+                deep.d3210 = temp * f321 * g310;
+                deep.d3222 = temp * f322 * g322;
+                temp1 *= aonv;
+                temp = 2. * temp1 * 7.3636953e-9;
+                deep.d4410 = temp * f441 * g410;
+                deep.d4422 = temp * f442 * g422;
+                temp1 *= aonv;
+                temp = temp1 * 1.1428639e-7;
+                deep.d5220 = temp * f522 * g520;
+                deep.d5232 = temp * f523 * g532;
+                temp = 2. * temp1 * 2.1765803e-9;
+                deep.d5421 = temp * f542 * g521;
+                deep.d5433 = temp * f543 * g533;
+                deep.xlamo = (mo + nodeo + nodeo - theta - theta) % TWO_PI;
+                deep.xfact = mdot + dmdt + 2. * (nodedot + dnodt - RPTIM) - no;
+            }
+            Resonance::Synchronous => {
+                let g200 = 1. + emsq * (-2.5 + 0.8125 * emsq);
+                let g310 = 1. + 2. * emsq;
+                let g300 = 1. + emsq * (-6. + 6.60937 * emsq);
+                let f220 = 0.75 * (1. + cosim) * (1. + cosim);
+                let f311 = 0.9375 * sinim * sinim * (1. + 3. * cosim) - 0.75 * (1. + cosim);
+                let f330 = 1.875 * (1. + cosim).powi(3);
+                let del1 = 3. * no * no * aonv * aonv;
+                deep.del2 = 2. * del1 * f220 * g200 * 1.7891679e-6;
+                deep.del3 = 3. * del1 * f330 * g300 * 2.2123015e-7 * aonv;
+                deep.del1 = del1 * f311 * g310 * 2.1460748e-6 * aonv;
+                deep.xlamo = (mo + nodeo + argpo - theta) % TWO_PI;
+                deep.xfact = mdot + argpdot + nodedot - RPTIM + dmdt + domdt + dnodt - no;
+            }
+        }
+
+        deep
+    }
+
+    // Secular lunar and solar effects, and the resonances integrated out from epoch
+    // (`dspace`). `t` is minutes past epoch.
+    fn secular(&self, t: f64, argpo: f64, argpdot: f64, no: f64, mean: &mut MeanElements) {
+        mean.em += self.dedt * t;
+        mean.inclm += self.didt * t;
+        mean.argpm += self.domdt * t;
+        mean.nodem += self.dnodt * t;
+        mean.mm += self.dmdt * t;
+
+        if self.resonance == Resonance::None {
+            return;
+        }
+
+        const STEP: f64 = 720.;
+        const STEP2: f64 = 259200.; // STEP squared over two
+        let theta = (self.gsto + t * RPTIM) % TWO_PI;
+        let delt = if t > 0. { STEP } else { -STEP };
+
+        // Rates of the resonant mean longitude and mean motion.
+        let rates = |atime: f64, xli: f64, xni: f64| {
+            let xldot = xni + self.xfact;
+            let (xndt, xnddt) = match self.resonance {
+                Resonance::HalfDay => {
+                    const G22: f64 = 5.7686396;
+                    const G32: f64 = 0.95240898;
+                    const G44: f64 = 1.8014998;
+                    const G52: f64 = 1.0508330;
+                    const G54: f64 = 4.4108898;
+                    let xomi = argpo + argpdot * atime;
+                    let x2omi = xomi + xomi;
+                    let x2li = xli + xli;
+                    let xndt = self.d2201 * (x2omi + xli - G22).sin()
+                        + self.d2211 * (xli - G22).sin()
+                        + self.d3210 * (xomi + xli - G32).sin()
+                        + self.d3222 * (-xomi + xli - G32).sin()
+                        + self.d4410 * (x2omi + x2li - G44).sin()
+                        + self.d4422 * (x2li - G44).sin()
+                        + self.d5220 * (xomi + xli - G52).sin()
+                        + self.d5232 * (-xomi + xli - G52).sin()
+                        + self.d5421 * (xomi + x2li - G54).sin()
+                        + self.d5433 * (-xomi + x2li - G54).sin();
+                    let xnddt = self.d2201 * (x2omi + xli - G22).cos()
+                        + self.d2211 * (xli - G22).cos()
+                        + self.d3210 * (xomi + xli - G32).cos()
+                        + self.d3222 * (-xomi + xli - G32).cos()
+                        + self.d5220 * (xomi + xli - G52).cos()
+                        + self.d5232 * (-xomi + xli - G52).cos()
+                        + 2. * (self.d4410 * (x2omi + x2li - G44).cos()
+                            + self.d4422 * (x2li - G44).cos()
+                            + self.d5421 * (xomi + x2li - G54).cos()
+                            + self.d5433 * (-xomi + x2li - G54).cos());
+                    (xndt, xnddt)
+                }
+                _ => {
+                    const FASX2: f64 = 0.13130908;
+                    const FASX4: f64 = 2.8843198;
+                    const FASX6: f64 = 0.37448087;
+                    let xndt = self.del1 * (xli - FASX2).sin()
+                        + self.del2 * (2. * (xli - FASX4)).sin()
+                        + self.del3 * (3. * (xli - FASX6)).sin();
+                    let xnddt = self.del1 * (xli - FASX2).cos()
+                        + 2. * self.del2 * (2. * (xli - FASX4)).cos()
+                        + 3. * self.del3 * (3. * (xli - FASX6)).cos();
+                    (xndt, xnddt)
+                }
+            };
+            (xldot, xndt, xnddt * xldot)
+        };
+
+        // Euler-Maclaurin steps of half a day from epoch, then a Taylor series the rest of
+        // the way.
+        let (mut atime, mut xli, mut xni) = (0., self.xlamo, no);
+        let (mut xldot, mut xndt, mut xnddt) = rates(atime, xli, xni);
+        while (t - atime).abs() >= STEP {
+            xli += xldot * delt + xndt * STEP2;
+            xni += xndt * delt + xnddt * STEP2;
+            atime += delt;
+            (xldot, xndt, xnddt) = rates(atime, xli, xni);
+        }
+
+        let ft = t - atime;
+        let nm = xni + xndt * ft + xnddt * ft * ft * 0.5;
+        let xl = xli + xldot * ft + xndt * ft * ft * 0.5;
+        mean.mm = match self.resonance {
+            Resonance::Synchronous => xl - mean.nodem - mean.argpm + theta,
+            _ => xl - 2. * mean.nodem + 2. * theta,
+        };
+        mean.nm = nm;
+    }
+
+    // Lunar and solar periodics (`dpper`), `t` minutes past epoch.
+    fn periodics(&self, t: f64, mean: &mut MeanElements) {
+        let periodic = |zm: f64, ze: f64| {
+            let zf = zm + 2. * ze * zm.sin();
+            let sinzf = zf.sin();
+            (0.5 * sinzf * sinzf - 0.25, -0.5 * sinzf * zf.cos(), sinzf)
+        };
+
+        let (f2, f3, sinzf) = periodic(self.zmos + ZNS * t, ZES);
+        let ses = self.se2 * f2 + self.se3 * f3;
+        let sis = self.si2 * f2 + self.si3 * f3;
+        let sls = self.sl2 * f2 + self.sl3 * f3 + self.sl4 * sinzf;
+        let sghs = self.sgh2 * f2 + self.sgh3 * f3 + self.sgh4 * sinzf;
+        let shs = self.sh2 * f2 + self.sh3 * f3;
+
+        let (f2, f3, sinzf) = periodic(self.zmol + ZNL * t, ZEL);
+        let sel = self.ee2 * f2 + self.e3 * f3;
+        let sil = self.xi2 * f2 + self.xi3 * f3;
+        let sll = self.xl2 * f2 + self.xl3 * f3 + self.xl4 * sinzf;
+        let sghl = self.xgh2 * f2 + self.xgh3 * f3 + self.xgh4 * sinzf;
+        let shll = self.xh2 * f2 + self.xh3 * f3;
+
+        let pe = ses + sel;
+        let pinc = sis + sil;
+        let pl = sls + sll;
+        let mut pgh = sghs + sghl;
+        let mut ph = shs + shll;
+
+        mean.inclm += pinc;
+        mean.em += pe;
+        let (sinip, cosip) = mean.inclm.sin_cos();
+
+        if mean.inclm >= 0.2 {
+            ph /= sinip;
+            pgh -= cosip * ph;
+            mean.argpm += pgh;
+            mean.nodem += ph;
+            mean.mm += pl;
+        } else {
+            // Lyddane's modification, which stays finite at low inclination.
+            let (sinop, cosop) = mean.nodem.sin_cos();
+            let alfdp = sinip * sinop + ph * cosop + pinc * cosip * sinop;
+            let betdp = sinip * cosop - ph * sinop + pinc * cosip * cosop;
+            let nodep = mean.nodem % TWO_PI;
+            let xls = mean.mm + mean.argpm + cosip * nodep + pl + pgh - pinc * nodep * sinip;
+            let mut node = alfdp.atan2(betdp);
+            if (nodep - node).abs() > PI {
+                node += if node < nodep { TWO_PI } else { -TWO_PI };
+            }
+            mean.mm += pl;
+            mean.nodem = node;
+            mean.argpm = xls - mean.mm - cosip * node;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From Vallado's verification set (SGP4-VER.TLE) and the tcppver.out it produces.
+    const VANGUARD: [&str; 2] = [
+        "1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753",
+        "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667",
+    ];
+    const DEEP_SPACE: [&str; 2] = [
+        "1 11801U          80230.29629788  .01431103  00000-0  14311-1      13",
+        "2 11801  46.7916 230.4354 7318036  47.4722  10.4117  2.28537848    13",
+    ];
+    const MOLNIYA: [&str; 2] = [
+        "1 08195U 75081A   06176.33215444  .00000099  00000-0  11873-3 0   813",
+        "2 08195  64.1586 279.0717 6877146 264.7651  20.2257  2.00491383225656",
+    ];
+    const GEOSTATIONARY: [&str; 2] = [
+        "1 14128U 83058A   06176.02844893 -.00000158  00000-0  10000-3 0  9627",
+        "2 14128  11.4384  35.2134 0011562  26.4582 333.5652  0.98870114 46093",
+    ];
+
+    fn propagator(lines: [&str; 2]) -> Sgp4 {
+        Sgp4::new(TwoLineElements::parse("", lines[0], lines[1]).unwrap()).unwrap()
+    }
+
+    // tsince (minutes), position (KM) and velocity (KM/s)
+    fn check(sgp4: &Sgp4, expected: &[(f64, [f64; 3], [f64; 3])]) {
+        for &(tsince, position, velocity) in expected {
+            let (r, v) = sgp4.propagate(tsince).unwrap();
+            assert!(
+                (r - DVec3::from(position)).length() < 1e-6,
+                "{} at {}: {:?} against {:?}",
+                sgp4.elements.catalog_number,
+                tsince,
+                r,
+                position
+            );
+            assert!(
+                (v - DVec3::from(velocity)).length() < 1e-8,
+                "{} at {}: {:?} against {:?}",
+                sgp4.elements.catalog_number,
+                tsince,
+                v,
+                velocity
+            );
+        }
+    }
+
+    #[test]
+    fn near_earth_matches_tcppver() {
+        let sgp4 = propagator(VANGUARD);
+        assert!(sgp4.deep.is_none());
+
+        check(
+            &sgp4,
+            &[
+                (0., [7022.46529266, -1400.08296755, 0.03995155], [1.893841015, 6.405893759, 4.534807250]),
+                (360., [-7154.03120202, -3783.17682504, -3536.19412294], [4.741887409, -4.151817765, -2.093935425]),
+                (720., [-7134.59340119, 6531.68641334, 3260.27186483], [-4.113793027, -2.911922039, -2.557327851]),
+                (1080., [5568.53901181, 4492.06992591, 3863.87641983], [-4.209106476, 5.159719888, 2.744852980]),
+                (1440., [-938.55923943, -6268.18748831, -4294.02924751], [7.536105209, -0.427127707, 0.989878080]),
+            ],
+        );
+    }
+
+    #[test]
+    fn deep_space_matches_tcppver() {
+        let sgp4 = propagator(DEEP_SPACE);
+        assert_eq!(sgp4.deep.as_ref().map(|deep| deep.resonance), Some(Resonance::None));
+
+        check(
+            &sgp4,
+            &[
+                (0., [7473.37102491, 428.94748312, 5828.74846783], [5.107155391, 6.444680305, -0.186133297]),
+                (720., [14271.29083858, 24110.44309009, -4725.76320143], [-0.320504528, 2.679841539, -2.084054355]),
+            ],
+        );
+    }
+
+    #[test]
+    fn resonant_orbits_start_where_tcppver_does() {
+        for (lines, resonance, position) in [
+            (MOLNIYA, Resonance::HalfDay, [2349.89483350, -14785.93811562, 0.02119378]),
+            (GEOSTATIONARY, Resonance::Synchronous, [34747.57932696, 24502.37114079, -1.32832986]),
+        ] {
+            let sgp4 = propagator(lines);
+            assert_eq!(sgp4.deep.as_ref().map(|deep| deep.resonance), Some(resonance));
+            assert!((sgp4.propagate(0.).unwrap().0 - DVec3::from(position)).length() < 1e-6);
+        }
+    }
+
+    // The resonance terms are integrated in half-day steps, the state mustn't jump where
+    // one step hands over to the next, and the orbit keeps its size.
+    #[test]
+    fn resonances_integrate_smoothly() {
+        for lines in [MOLNIYA, GEOSTATIONARY] {
+            let sgp4 = propagator(lines);
+            let semimajor_axis = (MU / (sgp4.elements.mean_motion / 60.).powi(2)).cbrt();
+
+            for tsince in [720., 1440., 4320., -720.] {
+                let (before, _) = sgp4.propagate(tsince - 1e-4).unwrap();
+                let (after, velocity) = sgp4.propagate(tsince + 1e-4).unwrap();
+                assert!((after - before).length() < 2e-4 * velocity.length() * 60. * 1.01);
+
+                // Vis-viva
+                let (r, v) = sgp4.propagate(tsince).unwrap();
+                let a = 1. / (2. / r.length() - v.length_squared() / MU);
+                assert!(((a - semimajor_axis) / semimajor_axis).abs() < 0.01, "{}", a);
+            }
+        }
+    }
+
+    #[test]
+    fn tles_parse() {
+        let catalogue = format!("VANGUARD 1\n{}\n{}\n{}\n{}\n", VANGUARD[0], VANGUARD[1], MOLNIYA[0], MOLNIYA[1]);
+        let sets = TwoLineElements::parse_catalogue(&catalogue).unwrap();
+
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].name, "VANGUARD 1");
+        assert_eq!(sets[1].name, "8195");
+        assert!((sets[0].bstar - 0.28098e-4).abs() < 1e-15);
+        assert!((sets[0].epoch_jd() - 2451723.28495062).abs() < 1e-8);
+    }
+
+    #[test]
+    fn non_ascii_lines_are_rejected() {
+        let line1 = VANGUARD[0].replacen("58002B", "58002É", 1);
+        assert_eq!(TwoLineElements::parse("", &line1, VANGUARD[1]), Err(TleError::NotAscii(1)));
+
+        // Multi-byte characters at the column boundaries used to panic.
+        let line2 = format!("{}é", &VANGUARD[1][..67]);
+        assert_eq!(TwoLineElements::parse("", VANGUARD[0], &line2), Err(TleError::NotAscii(2)));
+    }
+}
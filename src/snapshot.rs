@@ -0,0 +1,462 @@
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistryArc;
+use bevy::scene::serde::SceneDeserializer;
+use serde::de::DeserializeSeed;
+use std::any::TypeId;
+
+use crate::forces::ForceModels;
+use crate::nbody::{NumericalState, Propagation};
+use crate::orbit::OrbitalParameters;
+use crate::sphere_camera::{self, SphereCamera, ToggleCameraLock, ToggleLookOutward};
+use crate::time::{PhysicsTime, PhysicsTimeMode};
+use crate::topocentric_camera::AltitudeAzimuthCamera;
+use orbiter_physics::frames::ReferenceFrame;
+use orbiter_physics::time_scale::TimeScale;
+
+// Saves this exact moment and view to a file that can be handed to someone else: the clock
+// (mode, rate and time), the elements of every body, the Moon's included, how each body
+// is propagated along with its force models and integrated state, and both cameras along
+// with their lock and look-outward modes. It's a Bevy DynamicScene written through the
+// Reflect derives. On loading, each entity's components go onto the entity with the
+// same Name, or for the cameras, which have no name, onto the one entity with that
+// component. Names aren't unique once catalogues are loaded, a name matching more than
+// one entity goes onto the first and is warned about.
+//
+// Satellites' TLEs aren't saved, they come from the catalogue the scene loads. Their
+// propagation and integrated state are, so the snapshot is only exact against the same
+// catalogue.
+//
+// F5 saves to `path` and F9 loads it. Ticking `save` or `load` in the inspector does
+// the same.
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Snapshots>()
+            .init_resource::<CameraRestore>()
+            .add_systems(Update, (save_snapshot, load_snapshot))
+            .add_systems(
+                Update,
+                restore_camera
+                    .before(sphere_camera::lock_camera_to_rotation)
+                    .before(sphere_camera::toggle_look_outward_camera),
+            )
+            .register_type::<Snapshots>()
+            // Fields of the saved components, which have to be registered to be read back.
+            .register_type::<PhysicsTimeMode>()
+            .register_type::<TimeScale>()
+            .register_type::<ReferenceFrame>();
+    }
+}
+
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct Snapshots {
+    pub path: String, // Relative to the working directory
+    pub save: bool,
+    pub load: bool,
+}
+
+impl Default for Snapshots {
+    fn default() -> Self {
+        Snapshots {
+            path: "snapshot.scn.ron".to_string(),
+            save: false,
+            load: false,
+        }
+    }
+}
+
+// Camera state waiting for the camera to be in the snapshot's mode. Switching between
+// locked and looking outward respawns the camera and resets the view, so the modes are
+// stepped through first, a frame each, and the saved view is put back last.
+#[derive(Resource, Default)]
+pub struct CameraRestore {
+    pub sphere: Option<SphereCamera>,
+    pub altaz: Option<AltitudeAzimuthCamera>,
+}
+
+pub fn save_snapshot(world: &mut World) {
+    let pressed = world.resource::<Input<KeyCode>>().just_pressed(KeyCode::F5);
+    let mut snapshots = world.resource_mut::<Snapshots>();
+    if !pressed && !snapshots.save {
+        return;
+    }
+
+    snapshots.save = false;
+    let path = snapshots.path.clone();
+
+    match snapshot_to_ron(world).and_then(|text| std::fs::write(&path, text).map_err(|err| err.to_string())) {
+        Ok(()) => println!("Saved snapshot to {}.", path),
+        Err(err) => println!("Could not save snapshot to {}: {}", path, err),
+    }
+}
+
+pub fn load_snapshot(world: &mut World) {
+    let pressed = world.resource::<Input<KeyCode>>().just_pressed(KeyCode::F9);
+    let mut snapshots = world.resource_mut::<Snapshots>();
+    if !pressed && !snapshots.load {
+        return;
+    }
+
+    snapshots.load = false;
+    let path = snapshots.path.clone();
+
+    match std::fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| restore_from_ron(world, &text))
+    {
+        Ok(count) => println!("Loaded snapshot from {}, {} entities restored.", path, count),
+        Err(err) => println!("Could not load snapshot from {}: {}", path, err),
+    }
+}
+
+pub fn snapshot_to_ron(world: &mut World) -> Result<String, String> {
+    let entities: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(
+            With<PhysicsTime>,
+            With<OrbitalParameters>,
+            With<Propagation>,
+            With<SphereCamera>,
+            With<AltitudeAzimuthCamera>,
+        )>>()
+        .iter(world)
+        .collect();
+
+    let scene = DynamicSceneBuilder::from_world(world)
+        .allow::<Name>()
+        .allow::<PhysicsTime>()
+        .allow::<OrbitalParameters>()
+        .allow::<Propagation>()
+        .allow::<ForceModels>()
+        .allow::<NumericalState>()
+        .allow::<SphereCamera>()
+        .allow::<AltitudeAzimuthCamera>()
+        .extract_entities(entities.into_iter())
+        .build();
+
+    let registry: &TypeRegistryArc = world.resource::<AppTypeRegistry>();
+    scene.serialize_ron(registry).map_err(|err| err.to_string())
+}
+
+// Returns how many of the snapshot's entities found one to go onto.
+pub fn restore_from_ron(world: &mut World, text: &str) -> Result<usize, String> {
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+
+    let mut deserializer = ron::de::Deserializer::from_str(text).map_err(|err| err.to_string())?;
+    let scene = SceneDeserializer {
+        type_registry: &registry,
+    }
+    .deserialize(&mut deserializer)
+    .map_err(|err| err.to_string())?;
+
+    let mut restored = 0;
+
+    for saved in &scene.entities {
+        let name = saved
+            .components
+            .iter()
+            .find(|component| represents::<Name>(&***component))
+            .and_then(|component| Name::from_reflect(&**component));
+
+        let target = match &name {
+            Some(name) => {
+                let matches: Vec<Entity> = world
+                    .query::<(Entity, &Name)>()
+                    .iter(world)
+                    .filter(|(_, other)| other.as_str() == name.as_str())
+                    .map(|(entity, _)| entity)
+                    .collect();
+
+                if matches.len() > 1 {
+                    println!(
+                        "{} entities are named {}, restoring the snapshot's onto the first.",
+                        matches.len(),
+                        name.as_str()
+                    );
+                }
+                matches.first().copied()
+            }
+            None => saved
+                .components
+                .iter()
+                .find_map(|component| only_entity_with(world, &**component)),
+        };
+
+        let Some(target) = target else {
+            println!(
+                "Nothing in the scene for {} from the snapshot.",
+                name.as_ref().map_or("an unnamed entity", |name| name.as_str())
+            );
+            continue;
+        };
+
+        let integrated = saved
+            .components
+            .iter()
+            .any(|component| represents::<NumericalState>(&**component));
+
+        for component in &saved.components {
+            let component = &**component;
+
+            if represents::<Name>(component) {
+                continue;
+            } else if represents::<SphereCamera>(component) {
+                world.get_resource_or_insert_with(CameraRestore::default).sphere =
+                    SphereCamera::from_reflect(component);
+            } else if represents::<AltitudeAzimuthCamera>(component) {
+                world.get_resource_or_insert_with(CameraRestore::default).altaz =
+                    AltitudeAzimuthCamera::from_reflect(component);
+            } else if let Some(reflect_component) = component
+                .get_represented_type_info()
+                .and_then(|info| registry.get(info.type_id()))
+                .and_then(|registration| registration.data::<ReflectComponent>())
+            {
+                let mut entity = world.entity_mut(target);
+                reflect_component.apply_or_insert(&mut entity, component);
+
+                // Integration starts over from the restored elements, unless the snapshot
+                // has where it had got to.
+                if represents::<OrbitalParameters>(component) && !integrated {
+                    entity.remove::<NumericalState>();
+                }
+            }
+        }
+
+        restored += 1;
+    }
+
+    Ok(restored)
+}
+
+fn represents<T: 'static>(component: &dyn Reflect) -> bool {
+    component
+        .get_represented_type_info()
+        .is_some_and(|info| info.type_id() == TypeId::of::<T>())
+}
+
+fn only_entity_with(world: &World, component: &dyn Reflect) -> Option<Entity> {
+    let component_id = world
+        .components()
+        .get_id(component.get_represented_type_info()?.type_id())?;
+    let mut entities = world
+        .iter_entities()
+        .filter(|entity| entity.contains_id(component_id))
+        .map(|entity| entity.id());
+
+    match (entities.next(), entities.next()) {
+        (Some(entity), None) => Some(entity),
+        _ => None,
+    }
+}
+
+pub fn restore_camera(
+    mut restore: ResMut<CameraRestore>,
+    mut sphere_camera_q: Query<&mut SphereCamera>,
+    mut altaz_q: Query<&mut AltitudeAzimuthCamera>,
+    mut lock_toggles: EventWriter<ToggleCameraLock>,
+    mut outward_toggles: EventWriter<ToggleLookOutward>,
+) {
+    if let Some(target) = &restore.sphere {
+        let Ok(mut sphere_camera) = sphere_camera_q.get_single_mut() else {
+            return;
+        };
+
+        // Looking outward only works while locked, so it's undone first and redone last.
+        let look_outward = target.look_outward && target.locked;
+        if sphere_camera.look_outward && !look_outward {
+            outward_toggles.send(ToggleLookOutward);
+            return;
+        }
+        if sphere_camera.locked != target.locked {
+            lock_toggles.send(ToggleCameraLock);
+            return;
+        }
+        if sphere_camera.look_outward != look_outward {
+            outward_toggles.send(ToggleLookOutward);
+            return;
+        }
+
+        *sphere_camera = restore.sphere.take().unwrap();
+    }
+
+    if let Some(altaz) = restore.altaz.take() {
+        if let Ok(mut current) = altaz_q.get_single_mut() {
+            *current = altaz;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::orbit::lunar_orbit;
+    use bevy::math::DVec3;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(SnapshotPlugin)
+            .add_event::<ToggleCameraLock>()
+            .add_event::<ToggleLookOutward>()
+            .register_type::<PhysicsTime>()
+            .register_type::<OrbitalParameters>()
+            .register_type::<Propagation>()
+            .register_type::<ForceModels>()
+            .register_type::<NumericalState>()
+            .register_type::<SphereCamera>()
+            .register_type::<AltitudeAzimuthCamera>();
+        app
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut app = app();
+        let world = &mut app.world;
+
+        let clock = world
+            .spawn((
+                PhysicsTime {
+                    mode: PhysicsTimeMode::StopTick,
+                    clock_seconds: 7.6e8,
+                    scale: 3600.,
+                    ..default()
+                },
+                Name::new("Physics Time"),
+            ))
+            .id();
+        let moon = world.spawn((lunar_orbit(), Name::new("Moon"))).id();
+        let camera = world
+            .spawn(SphereCamera {
+                radius: 900.,
+                theta: 1.,
+                ..default()
+            })
+            .id();
+        world.spawn(AltitudeAzimuthCamera {
+            altitude: 0.5,
+            azimuth: 0.25,
+            roll: 0.,
+        });
+
+        let text = snapshot_to_ron(world).unwrap();
+
+        *world.get_mut::<PhysicsTime>(clock).unwrap() = PhysicsTime::default();
+        world.get_mut::<OrbitalParameters>(moon).unwrap().semimajor_axis = 1.;
+        world.get_mut::<SphereCamera>(camera).unwrap().radius = 1.;
+
+        assert_eq!(restore_from_ron(world, &text).unwrap(), 4);
+
+        let physics_time = world.get::<PhysicsTime>(clock).unwrap();
+        assert!(physics_time.mode == PhysicsTimeMode::StopTick);
+        assert_eq!(physics_time.clock_seconds, 7.6e8);
+        assert_eq!(physics_time.scale, 3600.);
+        let orbit = world.get::<OrbitalParameters>(moon).unwrap();
+        assert_eq!(orbit.semimajor_axis, lunar_orbit().semimajor_axis);
+        assert_eq!(orbit.arg_of_periapsis_rate, lunar_orbit().arg_of_periapsis_rate);
+
+        world.run_system_once(restore_camera);
+        let sphere_camera = world.get::<SphereCamera>(camera).unwrap();
+        assert_eq!((sphere_camera.radius, sphere_camera.theta), (900., 1.));
+        let altaz = world.query::<&AltitudeAzimuthCamera>().single(world);
+        assert_eq!((altaz.altitude, altaz.azimuth), (0.5, 0.25));
+    }
+
+    #[test]
+    fn integrated_state_is_restored() {
+        let mut app = app();
+        let world = &mut app.world;
+
+        let state = NumericalState {
+            position: DVec3::new(7000., 10., -20.),
+            velocity: DVec3::new(0.1, 7.5, 0.2),
+            time: 7.6e8,
+            rk45_step: 30.,
+            ..default()
+        };
+        let forces = ForceModels {
+            drag: false,
+            ballistic_coefficient: 0.05,
+            ..default()
+        };
+        let body = world
+            .spawn((lunar_orbit(), Propagation::Rk45, forces, state, Name::new("Moon")))
+            .id();
+        // A satellite, no elements.
+        let satellite = world
+            .spawn((Propagation::Yoshida, ForceModels::default(), Name::new("ISS (ZARYA)")))
+            .id();
+
+        let text = snapshot_to_ron(world).unwrap();
+
+        world.entity_mut(body).insert((
+            Propagation::Analytic,
+            ForceModels::default(),
+            NumericalState::default(),
+        ));
+        world.entity_mut(satellite).insert(Propagation::Analytic);
+
+        assert_eq!(restore_from_ron(world, &text).unwrap(), 2);
+
+        assert_eq!(*world.get::<Propagation>(body).unwrap(), Propagation::Rk45);
+        let restored = world.get::<ForceModels>(body).unwrap();
+        assert_eq!((restored.drag, restored.ballistic_coefficient), (false, 0.05));
+        let restored = world.get::<NumericalState>(body).unwrap();
+        assert_eq!(restored.position, state.position);
+        assert_eq!(restored.velocity, state.velocity);
+        assert_eq!((restored.time, restored.rk45_step), (state.time, state.rk45_step));
+        assert_eq!(*world.get::<Propagation>(satellite).unwrap(), Propagation::Yoshida);
+    }
+
+    #[test]
+    fn repeated_names_go_onto_the_first() {
+        let mut app = app();
+        let world = &mut app.world;
+
+        let first = world.spawn((Propagation::Rk4, Name::new("DEB"))).id();
+        let text = snapshot_to_ron(world).unwrap();
+
+        world.entity_mut(first).insert(Propagation::Analytic);
+        let second = world.spawn((Propagation::Analytic, Name::new("DEB"))).id();
+
+        assert_eq!(restore_from_ron(world, &text).unwrap(), 1);
+        assert_eq!(*world.get::<Propagation>(first).unwrap(), Propagation::Rk4);
+        assert_eq!(*world.get::<Propagation>(second).unwrap(), Propagation::Analytic);
+    }
+
+    #[test]
+    fn camera_modes_are_switched_before_the_view_is_restored() {
+        let mut app = app();
+        let world = &mut app.world;
+
+        let camera = world
+            .spawn(SphereCamera {
+                locked: true,
+                look_outward: true,
+                ..default()
+            })
+            .id();
+        world.resource_mut::<CameraRestore>().sphere = Some(SphereCamera {
+            radius: 900.,
+            ..default()
+        });
+
+        world.run_system_once(restore_camera);
+
+        // Out of looking outward first, without touching the view.
+        assert_eq!(world.resource::<Events<ToggleLookOutward>>().len(), 1);
+        assert_eq!(world.resource::<Events<ToggleCameraLock>>().len(), 0);
+        assert!(world.resource::<CameraRestore>().sphere.is_some());
+
+        world.get_mut::<SphereCamera>(camera).unwrap().look_outward = false;
+        world.run_system_once(restore_camera);
+        assert_eq!(world.resource::<Events<ToggleCameraLock>>().len(), 1);
+
+        world.get_mut::<SphereCamera>(camera).unwrap().locked = false;
+        world.run_system_once(restore_camera);
+        assert_eq!(world.get::<SphereCamera>(camera).unwrap().radius, 900.);
+        assert!(world.resource::<CameraRestore>().sphere.is_none());
+    }
+}
@@ -13,6 +13,8 @@ impl Plugin for SphericalCameraPlugin {
             .add_systems(Update, sync_base_theta_for_sphere_camera)
             .add_systems(Update, toggle_look_outward_camera)
             .add_systems(Update, disable_mouse_scroll)
+            .add_event::<ToggleCameraLock>()
+            .add_event::<ToggleLookOutward>()
             .register_type::<SphereCamera>();
     }
 }
@@ -20,6 +22,14 @@ impl Plugin for SphericalCameraPlugin {
 #[derive(Component)]
 pub struct FixMarker;
 
+// Same as pressing L, for switching modes from code (e.g. restoring a snapshot).
+#[derive(Event)]
+pub struct ToggleCameraLock;
+
+// Same as pressing R.
+#[derive(Event)]
+pub struct ToggleLookOutward;
+
 #[derive(Reflect, Component, Resource)]
 #[reflect(Component)]
 pub struct SphereCamera {
@@ -220,11 +230,13 @@ pub fn toggle_look_outward_camera(
     mut fix_marker_query: Query<Entity, With<FixMarker>>,
     ass: Res<AssetServer>,
     mut altaz: Query<&mut topocentric_camera::AltitudeAzimuthCamera>,
+    mut toggles: EventReader<ToggleLookOutward>,
 ) {
     let mut sphere_camera = sphere_camera_query.single_mut();
     let mut altaz_in = altaz.get_single_mut().unwrap();
+    let requested = toggles.read().count() > 0;
 
-    if keys.just_pressed(KeyCode::R) && sphere_camera.locked {
+    if (keys.just_pressed(KeyCode::R) || requested) && sphere_camera.locked {
         let camera_entity = camera_entity_query.get_single_mut().unwrap();
         let earth_entity = earth_entity_query.get_single_mut().unwrap();
 
@@ -336,12 +348,14 @@ pub fn lock_camera_to_rotation(
     mut camera_query: Query<Entity, With<Camera3d>>,
     mut earth_query: Query<Entity, With<orbit::EarthBody>>,
     mut commands: Commands,
+    mut toggles: EventReader<ToggleCameraLock>,
 ) {
     let camera_entity = camera_query.get_single_mut().unwrap();
     let earth_entity = earth_query.get_single_mut().unwrap();
+    let requested = toggles.read().count() > 0;
 
     for mut sphere_camera in query.iter_mut() {
-        if keys.just_pressed(KeyCode::L) && !sphere_camera.look_outward {
+        if (keys.just_pressed(KeyCode::L) || requested) && !sphere_camera.look_outward {
 
             if sphere_camera.locked {
                 // locked -> unlocked
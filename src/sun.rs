@@ -0,0 +1,128 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+
+use crate::atmosphere::AtmosphereSettings;
+use crate::orbit::{
+    BodyRegistry, EarthBody, OrbitSet, ParentBody, RelativePosition,
+    RelativeVelocity,
+};
+use crate::time::{PhysicsStep, PhysicsTime};
+use orbiter_physics::frames::ecliptic_to_icrf;
+use orbiter_physics::time_scale::TimeScale;
+
+// The Sun as a body in the scene. Its geocentric position comes from a low precision solar
+// ephemeris (Meeus, Astronomical Algorithms ch. 25, good to about 0.01 degrees), and it
+// drives both the directional light and the atmosphere shader's sun position.
+pub struct SunPlugin;
+
+impl Plugin for SunPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, parent_sun_to_earth.before(OrbitSet::Place))
+            .add_systems(PhysicsStep, propagate_sun.in_set(OrbitSet::Propagate))
+            .add_systems(
+                Update,
+                (aim_sunlight, sync_sun_to_atmosphere).after(OrbitSet::Render),
+            );
+    }
+}
+
+pub const AU: f64 = 149_597_870.7; // KM
+const SECONDS_PER_CENTURY: f64 = 36525. * 86400.;
+
+#[derive(Component)]
+pub struct SunBody;
+
+// Geocentric position of the Sun in KM, in the ecliptic and equinox of J2000, `t` seconds
+// past J2000.
+pub fn geocentric_position(t: f64) -> DVec3 {
+    let centuries = t / SECONDS_PER_CENTURY;
+
+    let mean_longitude = 280.46646 + 36000.76983 * centuries + 0.0003032 * centuries.powi(2);
+    let mean_anomaly = (357.52911 + 35999.05029 * centuries - 0.0001537 * centuries.powi(2)).to_radians();
+    let eccentricity = 0.016708634 - 0.000042037 * centuries - 0.0000001267 * centuries.powi(2);
+
+    let equation_of_center = (1.914602 - 0.004817 * centuries - 0.000014 * centuries.powi(2))
+        * mean_anomaly.sin()
+        + (0.019993 - 0.000101 * centuries) * (2. * mean_anomaly).sin()
+        + 0.000289 * (3. * mean_anomaly).sin();
+
+    // Meeus gives the longitude referred to the equinox of date, this takes it back to J2000.
+    let longitude = (mean_longitude + equation_of_center - 1.397 * centuries).to_radians();
+    let true_anomaly = mean_anomaly + equation_of_center.to_radians();
+    let distance = 1.000001018 * (1. - eccentricity.powi(2)) / (1. + eccentricity * true_anomaly.cos());
+
+    DVec3::new(longitude.cos(), longitude.sin(), 0.) * distance * AU
+}
+
+// The scenario spawns the Sun, and the Sun picks Earth up as its parent once it's
+// registered if the scenario doesn't give one.
+pub fn parent_sun_to_earth(
+    mut commands: Commands,
+    registry: Res<BodyRegistry>,
+    sun_query: Query<Entity, (With<SunBody>, Without<ParentBody>)>,
+) {
+    let Some(earth) = registry.by_name("Earth") else {
+        return;
+    };
+
+    for sun in &sun_query {
+        commands.entity(sun).insert(ParentBody(earth));
+    }
+}
+
+pub fn propagate_sun(
+    mut commands: Commands,
+    mut sun_query: Query<
+        (Entity, Option<&mut RelativePosition>, Option<&mut RelativeVelocity>),
+        With<SunBody>,
+    >,
+    physics_time_q: Query<&PhysicsTime>,
+) {
+    let physics_time = physics_time_q.single();
+    let t = physics_time.seconds(TimeScale::Tdb);
+
+    // The Sun moves about a degree a day, a central difference over a minute is plenty.
+    let posn = ecliptic_to_icrf(geocentric_position(t));
+    let vel = ecliptic_to_icrf(geocentric_position(t + 60.) - geocentric_position(t - 60.)) / 120.;
+
+    for (entity, relative, velocity) in &mut sun_query {
+        match (relative, velocity) {
+            (Some(mut relative), Some(mut velocity)) => {
+                relative.0 = posn;
+                velocity.0 = vel;
+            }
+            _ => {
+                commands
+                    .entity(entity)
+                    .insert((RelativePosition(posn), RelativeVelocity(vel)));
+            }
+        }
+    }
+}
+
+// Sunlight is parallel at Earth's distance, so the light just points from the Sun at Earth.
+pub fn aim_sunlight(
+    mut sun_query: Query<&mut Transform, With<SunBody>>,
+    earth_query: Query<&Transform, (With<EarthBody>, Without<SunBody>)>,
+) {
+    let Ok(earth) = earth_query.get_single() else {
+        return;
+    };
+
+    for mut transform in &mut sun_query {
+        transform.look_at(earth.translation, Vec3::Y);
+    }
+}
+
+pub fn sync_sun_to_atmosphere(
+    sun_query: Query<&Transform, With<SunBody>>,
+    mut atmosphere_q: Query<&mut AtmosphereSettings>,
+) {
+    let Ok(sun) = sun_query.get_single() else {
+        return;
+    };
+
+    for mut atmosphere in &mut atmosphere_q {
+        atmosphere.sunPosition = sun.translation;
+    }
+}
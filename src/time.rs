@@ -1,20 +1,20 @@
+use bevy::ecs::schedule::ScheduleLabel;
 use bevy::input::keyboard::KeyboardInput;
 use bevy::prelude::*;
+use std::time::Duration;
 use bevy_inspector_egui::InspectorOptions;
 use bevy_inspector_egui::prelude::ReflectInspectorOptions;
-use chrono::{prelude::*, Duration, DurationRound};
-use chrono::offset::LocalResult;
-
-use crate::orbit::{EarthBody, MoonBody, LunarOrbit};
+use orbiter_physics::time_scale::{self, TimeScale};
 
 #[derive(Reflect, Component, InspectorOptions)]
 #[reflect(Component, InspectorOptions)]
 pub struct PhysicsTime {
     pub mode: PhysicsTimeMode,
     pub tick_interval_seconds: f64, // when in StopTickMode, and we tick forward time, this determines the interval we wish to tick forward.
-    pub delta_seconds: f64, // how many physics seconds have 
-    pub clock_seconds: f64, // starts at clock_seconds = 0
+    pub delta_seconds: f64, // how many physics seconds the current physics sub-step covers
+    pub clock_seconds: f64, // seconds past J2000 in `time_scale`, starts at clock_seconds = 0
     pub scale: f64, // ratio of physics seconds to 1 bevy second, can be negative to turn back time.
+    pub time_scale: TimeScale, // the scale clock_seconds is kept in
 }
 
 #[derive(Reflect, PartialEq)]
@@ -26,9 +26,74 @@ pub enum PhysicsTimeMode {
 #[derive(Component)]
 pub struct TimeLabel;
 
+// Runs once per physics sub-step, after the clock has moved on by `delta_seconds`.
+// Propagation goes here so it sees the same sequence of times whatever the frame rate.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhysicsStep;
+
+// The clock moves in fixed steps of real time (`fixed_timestep` seconds, run from Bevy's
+// FixedUpdate), each split into sub-steps of at most `max_substep_seconds` of physics time.
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct PhysicsStepSettings {
+    pub fixed_timestep: f64,      // Real seconds per physics step
+    pub max_substep_seconds: f64, // Physics seconds per sub-step
+    pub max_substeps: u32,        // Beyond this sub-steps get longer instead of more numerous
+    pub max_steps_per_frame: u32, // A slow frame drops time rather than running more steps
+}
+
+impl Default for PhysicsStepSettings {
+    fn default() -> Self {
+        PhysicsStepSettings {
+            fixed_timestep: 1. / 60.,
+            max_substep_seconds: 600.,
+            max_substeps: 1000,
+            max_steps_per_frame: 8,
+        }
+    }
+}
+
+// Where the clock starts, TDB seconds past J2000. Set from `--epoch` or ORBITER_EPOCH.
+#[derive(Resource, Default)]
+pub struct StartEpoch(pub f64);
+
+// Moves the clock to a TDB time, in seconds past J2000. Everything that depends on the
+// date is worked out from the clock, so the whole scene follows.
+#[derive(Event)]
+pub struct JumpToDate(pub f64);
+
+// Type a date into `date` in the inspector and tick `jump` to go there. Takes anything
+// `time_scale::parse_date` does, e.g. "now", "2024-04-08T18:17:00Z" or "JD 2460409.26".
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource)]
+pub struct DateJump {
+    pub date: String,
+    pub jump: bool,
+}
+
 impl Default for PhysicsTime {
     fn default() -> Self {
-        return PhysicsTime { scale: 1., clock_seconds: 0., delta_seconds: 0., mode: PhysicsTimeMode::Elapsing, tick_interval_seconds: 86400. };
+        return PhysicsTime { scale: 1., clock_seconds: 0., delta_seconds: 0., mode: PhysicsTimeMode::Elapsing, tick_interval_seconds: 86400., time_scale: TimeScale::Tdb };
+    }
+}
+
+impl PhysicsTime {
+    // The clock as seconds past J2000 in any time scale. Ephemerides want TDB, Earth
+    // rotation and TLEs want UTC.
+    pub fn seconds(&self, scale: TimeScale) -> f64 {
+        time_scale::convert(self.clock_seconds, self.time_scale, scale)
+    }
+
+    pub fn julian_date(&self, scale: TimeScale) -> f64 {
+        time_scale::julian_date(self.seconds(scale))
+    }
+
+    pub fn modified_julian_date(&self, scale: TimeScale) -> f64 {
+        time_scale::modified_julian_date(self.seconds(scale))
+    }
+
+    pub fn iso_string(&self, scale: TimeScale) -> String {
+        time_scale::iso_string(self.seconds(scale), scale)
     }
 }
 
@@ -36,21 +101,31 @@ pub struct PhysicsTimePlugin;
 
 impl Plugin for PhysicsTimePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, sync_physics_clock)
-            .add_systems(Update, stop_tick_mode_input_moon)
-            .add_systems(Update, stop_tick_mode_input_earth)
+        app.insert_resource(PhysicsStepSettings::default())
+            .add_systems(FixedUpdate, run_physics_steps)
+            .add_systems(Update, apply_step_settings)
+            .add_systems(Update, (jump_to_now_input, request_date_jump, jump_to_date).chain())
             .add_systems(Update, stop_tick)
             .add_systems(Update, draw_date)
             .add_systems(Startup, setup)
+            .add_event::<JumpToDate>()
+            .init_resource::<StartEpoch>()
+            .init_resource::<DateJump>()
+            .register_type::<DateJump>()
+            .register_type::<PhysicsStepSettings>()
             .register_type::<PhysicsTime>();
     }
 }
 
 pub fn setup(
-    mut commands: Commands, ass: Res<AssetServer>,
+    mut commands: Commands, ass: Res<AssetServer>, start_epoch: Res<StartEpoch>,
 ) {
+    let time_scale = TimeScale::default();
+
     commands.spawn(
         PhysicsTime{
+            clock_seconds: time_scale::convert(start_epoch.0, TimeScale::Tdb, time_scale),
+            time_scale,
             ..default()
         }
     ).insert(Name::new("Physics Time"));
@@ -73,20 +148,59 @@ pub fn setup(
     ));
 }
 
-pub fn sync_physics_clock(
-    mut physics_time_q: Query<&mut PhysicsTime>,
-    mut bevy_time: Res<Time>,
+pub fn apply_step_settings(
+    settings: Res<PhysicsStepSettings>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
 ) {
-    let mut physics_time = physics_time_q.get_single_mut().unwrap();
-
-    if physics_time.mode == PhysicsTimeMode::StopTick {
+    if !settings.is_changed() {
         return;
     }
 
-    let bevy_time = bevy_time.delta_seconds_f64();
+    let timestep = settings.fixed_timestep.max(1e-4);
+    fixed_time.set_timestep_seconds(timestep);
+    // FixedUpdate runs as many steps as there's accumulated time for, capping how much a
+    // frame can add caps the number of steps.
+    virtual_time.set_max_delta(Duration::from_secs_f64(
+        timestep * settings.max_steps_per_frame.max(1) as f64,
+    ));
+}
+
+// Advances the clock by one fixed step and runs `PhysicsStep` for every sub-step of it.
+// While paused the clock only moves by Left/Right or a date jump, so a single step with no
+// time in it is enough to bring positions up to date.
+pub fn run_physics_steps(world: &mut World) {
+    let timestep = world.resource::<Time<Fixed>>().timestep().as_secs_f64();
+    let (max_substep_seconds, max_substeps) = {
+        let settings = world.resource::<PhysicsStepSettings>();
+        (settings.max_substep_seconds, settings.max_substeps.max(1))
+    };
+
+    let mut physics_time_q = world.query::<&mut PhysicsTime>();
+    let Ok(physics_time) = physics_time_q.get_single(world) else {
+        return;
+    };
+
+    let span = match physics_time.mode {
+        PhysicsTimeMode::Elapsing => physics_time.scale * timestep,
+        PhysicsTimeMode::StopTick => 0.,
+    };
+
+    let substeps = if max_substep_seconds > 0. {
+        ((span.abs() / max_substep_seconds).ceil() as u32).clamp(1, max_substeps)
+    } else {
+        1
+    };
+    let substep = span / substeps as f64;
+
+    for _ in 0..substeps {
+        if let Ok(mut physics_time) = physics_time_q.get_single_mut(world) {
+            physics_time.delta_seconds = substep;
+            physics_time.clock_seconds += substep;
+        }
 
-    physics_time.delta_seconds = physics_time.scale * bevy_time;
-    physics_time.clock_seconds += physics_time.delta_seconds;
+        world.run_schedule(PhysicsStep);
+    }
 }
 
 pub fn stop_tick(
@@ -108,65 +222,69 @@ pub fn stop_tick(
     }
 }
 
-pub fn stop_tick_mode_input_moon(
-    keys: Res<Input<KeyCode>>,
-    mut physics_time_q: Query<&mut PhysicsTime>,
-    mut moon_query: Query<&mut Transform, With<MoonBody>>,
-    lunar_orbit: ResMut<LunarOrbit>,
-) {
-    let mut moon_trans = moon_query.single_mut();
-    let mut physics_time = physics_time_q.single_mut();
+// Reads `--epoch <date>` (or `--epoch=<date>`) from the command line, falling back to the
+// ORBITER_EPOCH environment variable and then to J2000.
+pub fn start_epoch_from_args() -> StartEpoch {
+    let args: Vec<String> = std::env::args().collect();
 
-    if physics_time.mode == PhysicsTimeMode::Elapsing {
-        return;
+    let from_args = args.iter().enumerate().find_map(|(idx, arg)| {
+        if arg == "--epoch" {
+            args.get(idx + 1).cloned()
+        } else {
+            arg.strip_prefix("--epoch=").map(str::to_string)
+        }
+    });
+
+    let Some(date) = from_args.or(std::env::var("ORBITER_EPOCH").ok()) else {
+        return StartEpoch::default();
+    };
+
+    match time_scale::parse_date(&date, TimeScale::Tdb) {
+        Ok(seconds) => StartEpoch(seconds),
+        Err(err) => {
+            println!("Starting at J2000: {}", err);
+            StartEpoch::default()
+        }
     }
+}
 
-    if keys.just_pressed(KeyCode::Right) {
-        moon_trans.rotate_y(
-            ((physics_time.tick_interval_seconds
-                / lunar_orbit.orbit.rotational_period)
-                * 2.
-                * std::f64::consts::PI) as f32,
-        );
+// N jumps to the current date.
+pub fn jump_to_now_input(keys: Res<Input<KeyCode>>, mut jumps: EventWriter<JumpToDate>) {
+    if !keys.just_pressed(KeyCode::N) {
+        return;
     }
 
-    if keys.just_pressed(KeyCode::Left) {
-        moon_trans.rotate_y(
-            ((-physics_time.tick_interval_seconds
-                / lunar_orbit.orbit.rotational_period)
-                * 2.
-                * std::f64::consts::PI) as f32,
-        );
+    if let Ok(seconds) = time_scale::parse_date("now", TimeScale::Tdb) {
+        jumps.send(JumpToDate(seconds));
     }
 }
 
-pub fn stop_tick_mode_input_earth(
-    keys: Res<Input<KeyCode>>,
-    mut physics_time_q: Query<&mut PhysicsTime>,
-    mut earth_query: Query<&mut Transform, With<EarthBody>>,
-) {
-    let mut earth_trans = earth_query.single_mut();
-    let mut physics_time = physics_time_q.single_mut();
-
-    if physics_time.mode == PhysicsTimeMode::Elapsing {
+pub fn request_date_jump(mut date_jump: ResMut<DateJump>, mut jumps: EventWriter<JumpToDate>) {
+    if !date_jump.jump {
         return;
     }
 
-    if keys.just_pressed(KeyCode::Right) {
-        println!("Right");
-        let val = (physics_time.tick_interval_seconds / 86400.0) * 2. * std::f64::consts::PI;
-        println!("Rotating for {} radians", val);
-        earth_trans.rotate_y(val as f32);
-    }
+    date_jump.jump = false;
 
-    if keys.just_pressed(KeyCode::Left) {
-        println!("Left");
-        let val = (physics_time.tick_interval_seconds / 86400.0) * 2. * std::f64::consts::PI;
-        println!("Rotating for {} radians", -val);
-        earth_trans.rotate_y(-val as f32);
+    match time_scale::parse_date(&date_jump.date, TimeScale::Tdb) {
+        Ok(seconds) => jumps.send(JumpToDate(seconds)),
+        Err(err) => println!("{}", err),
     }
 }
 
+pub fn jump_to_date(
+    mut jumps: EventReader<JumpToDate>,
+    mut physics_time_q: Query<&mut PhysicsTime>,
+) {
+    let Some(jump) = jumps.read().last() else {
+        return;
+    };
+
+    let mut physics_time = physics_time_q.single_mut();
+    physics_time.clock_seconds = time_scale::convert(jump.0, TimeScale::Tdb, physics_time.time_scale);
+    physics_time.delta_seconds = 0.;
+}
+
 pub fn draw_date(
     mut physics_time_q: Query<&mut PhysicsTime>,
     mut text_query: Query<&mut Text, With<TimeLabel>>,
@@ -174,20 +292,145 @@ pub fn draw_date(
     let mut text = text_query.get_single_mut().unwrap();
     let physics_time = physics_time_q.get_single_mut().unwrap();
 
-    let seconds_since_j2000 = physics_time.clock_seconds;
+    text.sections[0].value = format!(
+        "{}\nJD {:.5} TDB",
+        physics_time.iso_string(TimeScale::Utc),
+        physics_time.julian_date(TimeScale::Tdb)
+    );
+}
 
-    println!("Clock seconds {}", seconds_since_j2000);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let reference_date = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap(); // `2014-07-08T09:10:11Z`
+    const SCALES: [TimeScale; 4] = [TimeScale::Utc, TimeScale::Tai, TimeScale::Tt, TimeScale::Tdb];
 
-    let duration = Duration::milliseconds((seconds_since_j2000 * 1000.) as i64);
+    fn clock_at(tdb: f64, time_scale: TimeScale) -> PhysicsTime {
+        PhysicsTime {
+            clock_seconds: time_scale::convert(tdb, TimeScale::Tdb, time_scale),
+            time_scale,
+            ..default()
+        }
+    }
 
-    let date = reference_date + duration;
+    #[test]
+    fn clock_reads_back_in_every_scale() {
+        for &clock_scale in &SCALES {
+            for &tdb in &[-4e8, 0., 7.6e8] {
+                let physics_time = clock_at(tdb, clock_scale);
+
+                assert!((physics_time.seconds(TimeScale::Tdb) - tdb).abs() < 1e-6);
+                assert!(((physics_time.julian_date(TimeScale::Tdb) - 2451545.) * 86400. - tdb).abs() < 1e-4);
+
+                for &scale in &SCALES {
+                    let seconds = physics_time.seconds(scale);
+                    assert!((time_scale::convert(seconds, scale, TimeScale::Tdb) - tdb).abs() < 1e-6);
+
+                    // Printed to the millisecond.
+                    let printed = physics_time.iso_string(scale);
+                    let parsed = time_scale::parse_date(&printed, TimeScale::Tdb).unwrap();
+                    assert!((parsed - tdb).abs() < 1e-3, "{} read back as {}", printed, parsed);
+                }
+            }
+        }
+    }
 
-    let date_string = date.to_rfc2822();
+    // The length of every sub-step `PhysicsStep` ran for.
+    #[derive(Resource, Default)]
+    struct Substeps(Vec<f64>);
+
+    // A clock with a 1/64 s fixed step, which a Duration holds exactly.
+    fn stepped_world(scale: f64, mode: PhysicsTimeMode, settings: PhysicsStepSettings) -> World {
+        let mut world = World::new();
+        world.insert_resource(Time::<Virtual>::default());
+        world.insert_resource(Time::<Fixed>::from_seconds(1. / 64.));
+        world.insert_resource(Time::<()>::default());
+        world.insert_resource(settings);
+        world.init_resource::<Substeps>();
+        world.spawn(PhysicsTime { scale, mode, ..default() });
+
+        let mut fixed_update = Schedule::new(FixedUpdate);
+        fixed_update.add_systems(run_physics_steps);
+        world.add_schedule(fixed_update);
+
+        let mut physics_step = Schedule::new(PhysicsStep);
+        physics_step.add_systems(|physics_time_q: Query<&PhysicsTime>, mut substeps: ResMut<Substeps>| {
+            substeps.0.push(physics_time_q.single().delta_seconds);
+        });
+        world.add_schedule(physics_step);
+
+        world
+    }
 
-    text.sections[0].value = format!(
-        "{}",
-        date_string
-    );
-}
\ No newline at end of file
+    // Runs frames of the given lengths in real seconds, the way Bevy does FixedUpdate.
+    fn run_frames(world: &mut World, frames: &[f64]) {
+        for &frame in frames {
+            world.resource_mut::<Time<Virtual>>().advance_by(Duration::from_secs_f64(frame));
+            bevy::time::run_fixed_update_schedule(world);
+        }
+    }
+
+    fn clock_seconds(world: &mut World) -> f64 {
+        world.query::<&PhysicsTime>().single(world).clock_seconds
+    }
+
+    #[test]
+    fn same_span_gives_the_same_steps_whatever_the_frames() {
+        // 1e5 physics seconds a real one is 1562.5 s a step, three sub-steps of 600 s or less.
+        let mut even = stepped_world(1e5, PhysicsTimeMode::Elapsing, default());
+        run_frames(&mut even, &[1. / 64.; 64]);
+        let mut uneven = stepped_world(1e5, PhysicsTimeMode::Elapsing, default());
+        run_frames(&mut uneven, &[0.3, 0.01, 0., 0.19, 0.5]);
+
+        assert!((clock_seconds(&mut even) - 1e5).abs() < 1e-6);
+        assert_eq!(clock_seconds(&mut even), clock_seconds(&mut uneven));
+
+        let substeps = &even.resource::<Substeps>().0;
+        assert_eq!(substeps.len(), 64 * 3);
+        assert!(substeps.iter().all(|&substep| substep == 1562.5 / 3.));
+        assert_eq!(substeps, &uneven.resource::<Substeps>().0);
+    }
+
+    #[test]
+    fn substeps_are_capped_then_get_longer() {
+        let settings = PhysicsStepSettings {
+            max_substeps: 4,
+            ..default()
+        };
+        // A step of -15625 s would take 27 sub-steps of 600 s.
+        let mut world = stepped_world(-1e6, PhysicsTimeMode::Elapsing, settings);
+        run_frames(&mut world, &[1. / 64.]);
+
+        assert_eq!(world.resource::<Substeps>().0, vec![-15625. / 4.; 4]);
+        assert_eq!(clock_seconds(&mut world), -15625.);
+    }
+
+    #[test]
+    fn stopped_clock_still_steps_without_moving() {
+        let mut world = stepped_world(1e5, PhysicsTimeMode::StopTick, default());
+        run_frames(&mut world, &[0.25, 0.25]);
+
+        assert_eq!(world.resource::<Substeps>().0, vec![0.; 32]);
+        assert_eq!(clock_seconds(&mut world), 0.);
+    }
+
+    #[test]
+    fn date_jump_lands_on_the_date_in_any_clock_scale() {
+        for &clock_scale in &SCALES {
+            let mut app = App::new();
+            app.add_event::<JumpToDate>()
+                .insert_resource(DateJump {
+                    date: "2024-04-08T18:17:00Z".to_string(),
+                    jump: true,
+                })
+                .add_systems(Update, (request_date_jump, jump_to_date).chain());
+            app.world.spawn(clock_at(0., clock_scale));
+
+            app.update();
+
+            let physics_time = app.world.query::<&PhysicsTime>().single(&app.world);
+            assert_eq!(physics_time.iso_string(TimeScale::Utc), "2024-04-08T18:17:00.000 UTC");
+            assert!(!app.world.resource::<DateJump>().jump);
+        }
+    }
+}
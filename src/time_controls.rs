@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+
+use crate::time::{PhysicsTime, PhysicsTimeMode};
+
+// Keyboard controls for the physics clock, and a panel under the date showing how fast
+// it's running.
+//
+//   Space    pause (StopTick) / play (Elapsing)
+//   . ,      next / previous warp preset
+//   1 - 6    pick a warp preset directly
+//   -        reverse
+//   ] [      next / previous step size, used by Left/Right while paused
+pub struct TimeControlsPlugin;
+
+impl Plugin for TimeControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TimeControlSettings::default())
+            .add_systems(Startup, setup)
+            .add_systems(
+                Update,
+                (toggle_mode_input, warp_input, step_size_input, draw_time_rate).chain(),
+            )
+            .register_type::<TimeControlSettings>();
+    }
+}
+
+const MINUTE: f64 = 60.;
+const HOUR: f64 = 3600.;
+const DAY: f64 = 86400.;
+const YEAR: f64 = 365.25 * DAY;
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct TimeControlSettings {
+    pub warp_presets: Vec<f64>, // Physics seconds per real second, all positive
+    pub step_presets: Vec<f64>, // Seconds per Left/Right press
+}
+
+impl Default for TimeControlSettings {
+    fn default() -> Self {
+        TimeControlSettings {
+            warp_presets: vec![1., MINUTE, HOUR, DAY, 30. * DAY, YEAR],
+            step_presets: vec![MINUTE, HOUR, DAY, 7. * DAY, 30. * DAY, YEAR],
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TimeRateLabel;
+
+pub fn setup(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(62.0),
+            right: Val::Px(12.0),
+            ..default()
+        }),
+        TimeRateLabel,
+    ));
+}
+
+pub fn toggle_mode_input(keys: Res<Input<KeyCode>>, mut physics_time_q: Query<&mut PhysicsTime>) {
+    if !keys.just_pressed(KeyCode::Space) {
+        return;
+    }
+
+    let mut physics_time = physics_time_q.single_mut();
+
+    physics_time.mode = match physics_time.mode {
+        PhysicsTimeMode::Elapsing => PhysicsTimeMode::StopTick,
+        PhysicsTimeMode::StopTick => PhysicsTimeMode::Elapsing,
+    };
+    physics_time.delta_seconds = 0.;
+}
+
+pub fn warp_input(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<TimeControlSettings>,
+    mut physics_time_q: Query<&mut PhysicsTime>,
+) {
+    let mut physics_time = physics_time_q.single_mut();
+    let presets = &settings.warp_presets;
+
+    if presets.is_empty() {
+        return;
+    }
+
+    let sign = if physics_time.scale < 0. { -1. } else { 1. };
+    let current = nearest_preset(presets, physics_time.scale.abs());
+
+    let digits = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+    ];
+
+    let mut chosen = None;
+
+    if keys.just_pressed(KeyCode::Period) {
+        chosen = Some((current + 1).min(presets.len() - 1));
+    }
+
+    if keys.just_pressed(KeyCode::Comma) {
+        chosen = Some(current.saturating_sub(1));
+    }
+
+    for (idx, key) in digits.iter().enumerate() {
+        if keys.just_pressed(*key) && idx < presets.len() {
+            chosen = Some(idx);
+        }
+    }
+
+    if let Some(idx) = chosen {
+        physics_time.scale = sign * presets[idx];
+    }
+
+    if keys.just_pressed(KeyCode::Minus) {
+        physics_time.scale = -physics_time.scale;
+    }
+}
+
+pub fn step_size_input(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<TimeControlSettings>,
+    mut physics_time_q: Query<&mut PhysicsTime>,
+) {
+    let mut physics_time = physics_time_q.single_mut();
+    let presets = &settings.step_presets;
+
+    if presets.is_empty() {
+        return;
+    }
+
+    let current = nearest_preset(presets, physics_time.tick_interval_seconds);
+
+    if keys.just_pressed(KeyCode::BracketRight) {
+        physics_time.tick_interval_seconds = presets[(current + 1).min(presets.len() - 1)];
+    }
+
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        physics_time.tick_interval_seconds = presets[current.saturating_sub(1)];
+    }
+}
+
+pub fn draw_time_rate(
+    physics_time_q: Query<&PhysicsTime>,
+    mut text_query: Query<&mut Text, With<TimeRateLabel>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    let physics_time = physics_time_q.single();
+
+    text.sections[0].value = match physics_time.mode {
+        PhysicsTimeMode::Elapsing => format!("Rate: {}", format_rate(physics_time.scale)),
+        PhysicsTimeMode::StopTick => format!(
+            "Paused, step {}",
+            format_duration(physics_time.tick_interval_seconds)
+        ),
+    };
+}
+
+// Index of the preset closest to `value` on a log scale, presets span several orders of
+// magnitude.
+fn nearest_preset(presets: &[f64], value: f64) -> usize {
+    let log_value = value.max(f64::MIN_POSITIVE).ln();
+
+    presets
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            let da = (a.max(f64::MIN_POSITIVE).ln() - log_value).abs();
+            let db = (b.max(f64::MIN_POSITIVE).ln() - log_value).abs();
+            da.total_cmp(&db)
+        })
+        .map_or(0, |(idx, _)| idx)
+}
+
+// "60x" below a minute per second, "1 h/s", "-1 d/s" and so on above it.
+pub fn format_rate(scale: f64) -> String {
+    if scale.abs() < MINUTE {
+        format!("{}x", round_for_display(scale))
+    } else {
+        format!("{}/s", format_duration(scale))
+    }
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let magnitude = seconds.abs();
+
+    let (unit, name) = if magnitude >= YEAR {
+        (YEAR, "yr")
+    } else if magnitude >= DAY {
+        (DAY, "d")
+    } else if magnitude >= HOUR {
+        (HOUR, "h")
+    } else if magnitude >= MINUTE {
+        (MINUTE, "min")
+    } else {
+        (1., "s")
+    };
+
+    format!("{} {}", round_for_display(seconds / unit), name)
+}
+
+fn round_for_display(value: f64) -> f64 {
+    (value * 100.).round() / 100.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_preset_is_nearest_on_a_log_scale() {
+        let presets = TimeControlSettings::default().warp_presets;
+
+        assert_eq!(nearest_preset(&presets, HOUR), 2);
+        // 1 and 60 meet at about 7.75, well short of the 30.5 halfway between them.
+        assert_eq!(nearest_preset(&presets, 7.), 0);
+        assert_eq!(nearest_preset(&presets, 10.), 1);
+        assert_eq!(nearest_preset(&presets, 10. * DAY), 4);
+        assert_eq!(nearest_preset(&presets, 100. * YEAR), 5);
+        // Reversed rates are looked up by their size, anything not above zero is the slowest.
+        assert_eq!(nearest_preset(&presets, (-DAY).abs()), 3);
+        assert_eq!(nearest_preset(&presets, -DAY), 0);
+        assert_eq!(nearest_preset(&presets, 0.), 0);
+        assert_eq!(nearest_preset(&[], HOUR), 0);
+    }
+
+    #[test]
+    fn rates_read_as_multiples_or_durations_per_second() {
+        assert_eq!(format_rate(1.), "1x");
+        assert_eq!(format_rate(0.25), "0.25x");
+        assert_eq!(format_rate(-30.), "-30x");
+        assert_eq!(format_rate(MINUTE), "1 min/s");
+        assert_eq!(format_rate(90.), "1.5 min/s");
+        assert_eq!(format_rate(-HOUR), "-1 h/s");
+        assert_eq!(format_rate(-2.5 * DAY), "-2.5 d/s");
+        assert_eq!(format_rate(YEAR), "1 yr/s");
+    }
+
+    #[test]
+    fn durations_pick_the_largest_whole_unit() {
+        assert_eq!(format_duration(45.), "45 s");
+        assert_eq!(format_duration(HOUR / 3.), "20 min");
+        assert_eq!(format_duration(-HOUR / 3.), "-20 min");
+        assert_eq!(format_duration(7. * DAY), "7 d");
+        assert_eq!(format_duration(30. * DAY), "30 d");
+        assert_eq!(format_duration(DAY - 36.), "23.99 h");
+        assert_eq!(format_duration(-1.5 * YEAR), "-1.5 yr");
+    }
+}